@@ -1 +1,113 @@
+//! Derives for the `Nbt` and `FromNbt` traits of flux-nbt.
+//!
+//! Structs become compounds, newtypes are transparent and other tuple structs become lists.
+//! Enums mirror `flux_nbt::ser`: unit variants are written as their name, any other variant
+//! as a compound holding its fields under the variant name, e.g. `{Custom:{pattern:'x'}}`.
+//!
+//! Container attributes:
+//! - `#[nbt(rename_all = "PascalCase")]` converts the case of all field and variant names
+//! - `#[nbt(crate = "flux_nbt")]` sets the path of flux-nbt, `fluxmc::nbt` by default
+//!
+//! Field and variant attributes:
+//! - `#[nbt(rename = "Slot")]` sets the key or variant name
+//! - `#[nbt(byte_array)]`, `#[nbt(int_array)]`, `#[nbt(long_array)]` force an array tag
+//! - `#[nbt(skip_none)]` skips the field when it is `None`, implied for `Option` fields
+//! - `#[nbt(flatten)]` merges the compound of the field into the parent
+//! - `#[nbt(default)]` falls back to `Default::default()` when the key is missing
+//! - `#[nbt(skip)]` never writes the field, and reads it as `Default::default()`
+//!
+//! Fields of tuple structs and variants only take the `*_array` attributes, as they have no key.
+//!
+//! The [`snbt!`] macro parses an SNBT literal at compile time instead.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
 mod proc;
+mod snbt;
+
+#[proc_macro_derive(Nbt, attributes(nbt))]
+pub fn derive_nbt(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc::derive_nbt(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FromNbt, attributes(nbt))]
+pub fn derive_from_nbt(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc::derive_from_nbt(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Parses an SNBT literal at compile time and expands to the construction of its `Value`.
+///
+/// `#{expr}` placeholders outside of quoted strings are converted through the `Nbt` trait.
+/// They stand for whole values, and can not be used as compound keys:
+///
+/// ```ignore
+/// let name = "Excalibur";
+/// let item = snbt!("{CustomModelData:5,display:{Name:#{name}}}");
+/// let value = snbt!(crate = flux_nbt, "[#{name}]");
+/// ```
+///
+/// Like the derives, `crate = path,` sets the path of flux-nbt, `fluxmc::nbt` by default.
+#[proc_macro]
+pub fn snbt(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as snbt::Input);
+    snbt::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    #[test]
+    fn test_tuple_field_attributes() {
+        for input in [
+            parse_quote!(
+                struct Skipped(i32, #[nbt(skip)] u8);
+            ),
+            parse_quote!(
+                enum Flattened {
+                    A(#[nbt(flatten)] Vec<u8>),
+                }
+            ),
+        ] {
+            let err = crate::proc::derive_nbt(input).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "`rename`, `skip_none`, `flatten`, `default` and `skip` are only supported on named fields"
+            );
+        }
+        let input = parse_quote!(
+            struct Ints(i32, #[nbt(int_array)] Vec<i32>);
+        );
+        assert!(crate::proc::derive_from_nbt(input).is_ok());
+    }
+
+    #[test]
+    fn test_snbt_placeholders() {
+        let expand = |input: &str| {
+            crate::snbt::expand(syn::parse_str(input).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            expand(r#""{a:1,\n#{key}:2}""#),
+            "placeholders can not be used as compound keys at line 2, column 1"
+        );
+        assert_eq!(
+            expand(r#""[#{1}b]""#),
+            "a placeholder must be a whole value at line 1, column 2"
+        );
+        assert_eq!(
+            expand(r#"crate = flux_nbt, "[#{1}""#),
+            "Expected `,` or `]`, found end of input at line 1, column 6"
+        );
+    }
+}
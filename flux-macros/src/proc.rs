@@ -1 +1,446 @@
+use convert_case::{Case, Casing};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Attribute, Data, DeriveInput, Fields, Generics, Ident, Lit, Meta, NestedMeta,
+    Path, Type,
+};
 
+/// Attributes of the derived struct or enum
+struct Container {
+    krate: Path,
+    rename_all: Option<Case>,
+}
+
+/// Forced tag of a sequence field
+#[derive(Clone, Copy)]
+enum Array {
+    Byte,
+    Int,
+    Long,
+}
+
+/// Attributes of a field or a variant
+#[derive(Default)]
+struct Options {
+    rename: Option<String>,
+    array: Option<Array>,
+    skip_none: bool,
+    flatten: bool,
+    default: bool,
+    skip: bool,
+}
+
+fn nbt_metas(attrs: &[Attribute]) -> syn::Result<Vec<Meta>> {
+    let mut metas = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("nbt")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Meta(meta) => metas.push(meta),
+                        NestedMeta::Lit(lit) => {
+                            return Err(syn::Error::new_spanned(lit, "expected an nbt attribute"))
+                        }
+                    }
+                }
+            }
+            other => return Err(syn::Error::new_spanned(other, "expected `#[nbt(...)]`")),
+        }
+    }
+    Ok(metas)
+}
+
+fn string_value(meta: &Meta) -> syn::Result<String> {
+    match meta {
+        Meta::NameValue(nv) => match &nv.lit {
+            Lit::Str(st) => Ok(st.value()),
+            other => Err(syn::Error::new_spanned(other, "expected a string")),
+        },
+        other => Err(syn::Error::new_spanned(other, "expected `name = \"...\"`")),
+    }
+}
+
+fn parse_case(meta: &Meta) -> syn::Result<Case> {
+    Ok(match string_value(meta)?.as_str() {
+        "lowercase" => Case::Flat,
+        "UPPERCASE" => Case::UpperFlat,
+        "PascalCase" => Case::Pascal,
+        "camelCase" => Case::Camel,
+        "snake_case" => Case::Snake,
+        "SCREAMING_SNAKE_CASE" => Case::UpperSnake,
+        "kebab-case" => Case::Kebab,
+        "SCREAMING-KEBAB-CASE" => Case::Cobol,
+        _ => return Err(syn::Error::new_spanned(meta, "unknown case convention")),
+    })
+}
+
+impl Container {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut container = Container {
+            krate: parse_quote!(::fluxmc::nbt),
+            rename_all: None,
+        };
+        for meta in nbt_metas(attrs)? {
+            if meta.path().is_ident("crate") {
+                container.krate = syn::parse_str(&string_value(&meta)?)?;
+            } else if meta.path().is_ident("rename_all") {
+                container.rename_all = Some(parse_case(&meta)?);
+            } else {
+                return Err(syn::Error::new_spanned(meta, "unknown container attribute"));
+            }
+        }
+        Ok(container)
+    }
+
+    /// Name of a field or variant in NBT
+    fn key(&self, ident: &Ident, options: &Options) -> String {
+        if let Some(rename) = &options.rename {
+            return rename.clone();
+        }
+        let name = ident.to_string();
+        let name = name.strip_prefix("r#").unwrap_or(&name);
+        match self.rename_all {
+            Some(case) => name.to_case(case),
+            None => name.to_owned(),
+        }
+    }
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = Options::default();
+        for meta in nbt_metas(attrs)? {
+            let path = meta.path();
+            if path.is_ident("rename") {
+                options.rename = Some(string_value(&meta)?);
+            } else if path.is_ident("byte_array") {
+                options.array = Some(Array::Byte);
+            } else if path.is_ident("int_array") {
+                options.array = Some(Array::Int);
+            } else if path.is_ident("long_array") {
+                options.array = Some(Array::Long);
+            } else if path.is_ident("skip_none") {
+                options.skip_none = true;
+            } else if path.is_ident("flatten") {
+                options.flatten = true;
+            } else if path.is_ident("default") {
+                options.default = true;
+            } else if path.is_ident("skip") {
+                options.skip = true;
+            } else {
+                return Err(syn::Error::new_spanned(meta, "unknown nbt attribute"));
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// A field along with the variable it is bound to
+struct Field<'a> {
+    ident: Option<&'a Ident>,
+    binding: Ident,
+    options: Options,
+    optional: bool,
+}
+
+fn fields(fields: &Fields) -> syn::Result<Vec<Field<'_>>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let options = Options::parse(&field.attrs)?;
+            let keyed = options.rename.is_some()
+                || options.skip_none
+                || options.flatten
+                || options.default
+                || options.skip;
+            if field.ident.is_none() && keyed {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "`rename`, `skip_none`, `flatten`, `default` and `skip` are only supported on named fields",
+                ));
+            }
+            Ok(Field {
+                ident: field.ident.as_ref(),
+                binding: format_ident!("__field{}", index),
+                optional: options.skip_none || is_option(&field.ty),
+                options,
+            })
+        })
+        .collect()
+}
+
+/// Pattern binding every field of a struct or variant by reference, skipped fields are ignored
+fn pattern(path: TokenStream, kind: &Fields, fields: &[Field]) -> TokenStream {
+    let bindings = fields.iter().map(|field| {
+        let binding = &field.binding;
+        if field.options.skip {
+            quote!(_)
+        } else {
+            quote!(#binding)
+        }
+    });
+    match kind {
+        Fields::Named(_) => {
+            let idents = fields.iter().map(|field| field.ident);
+            quote!(#path { #(#idents: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => path,
+    }
+}
+
+fn add_bounds(generics: &Generics, bound: TokenStream) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+pub fn derive_nbt(input: DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(&input.attrs)?;
+    let krate = &container.krate;
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            let pattern = pattern(quote!(Self), &data.fields, &fields);
+            let value = write_fields(&container, &name.to_string(), &data.fields, &fields)?;
+            quote! {
+                let #pattern = self;
+                #value
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let key = container.key(ident, &Options::parse(&variant.attrs)?);
+                let fields = fields(&variant.fields)?;
+                let pattern = pattern(quote!(Self::#ident), &variant.fields, &fields);
+                let value = write_fields(&container, &key, &variant.fields, &fields)?;
+                arms.push(match variant.fields {
+                    Fields::Unit => quote!(#pattern => { #value }),
+                    _ => quote!(#pattern => #krate::macros::__private::tagged(#key, { #value }),),
+                });
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "unions can not be converted to NBT",
+            ))
+        }
+    };
+    let generics = add_bounds(&input.generics, quote!(#krate::Nbt));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::Nbt for #name #ty_generics #where_clause {
+            fn nbt(&self) -> #krate::Value {
+                #body
+            }
+        }
+    })
+}
+
+/// Converts a bound field into a value, honoring forced array tags
+fn write_value(krate: &Path, binding: &Ident, options: &Options) -> TokenStream {
+    match options.array {
+        Some(Array::Byte) => quote!(#krate::macros::__private::byte_array(#binding)),
+        Some(Array::Int) => quote!(#krate::macros::__private::int_array(#binding)),
+        Some(Array::Long) => quote!(#krate::macros::__private::long_array(#binding)),
+        None => quote!(#krate::Nbt::nbt(#binding)),
+    }
+}
+
+/// Builds the value of a struct or variant from its bound fields
+fn write_fields(
+    container: &Container,
+    unit_name: &str,
+    kind: &Fields,
+    fields: &[Field],
+) -> syn::Result<TokenStream> {
+    let krate = &container.krate;
+    Ok(match kind {
+        Fields::Named(_) => {
+            let mut inserts = Vec::new();
+            for field in fields.iter().filter(|field| !field.options.skip) {
+                let binding = &field.binding;
+                let value = write_value(krate, binding, &field.options);
+                let insert = if field.options.flatten {
+                    quote!(#krate::macros::__private::flatten(&mut __map, #value);)
+                } else {
+                    let key = container.key(field.ident.unwrap(), &field.options);
+                    quote!(__map.insert(::std::string::String::from(#key), #value);)
+                };
+                inserts.push(if field.optional {
+                    quote! {
+                        if let ::core::option::Option::Some(#binding) = #binding {
+                            #insert
+                        }
+                    }
+                } else {
+                    insert
+                });
+            }
+            let len = inserts.len();
+            quote! {
+                let mut __map = #krate::Map::<::std::string::String, #krate::Value>::with_capacity(#len);
+                #(#inserts)*
+                #krate::Value::Compound(__map)
+            }
+        }
+        Fields::Unnamed(_) if fields.len() == 1 => {
+            write_value(krate, &fields[0].binding, &fields[0].options)
+        }
+        Fields::Unnamed(_) => {
+            let values = fields
+                .iter()
+                .map(|field| write_value(krate, &field.binding, &field.options));
+            quote!(#krate::Value::List(::std::vec![#(#values),*]))
+        }
+        Fields::Unit => quote!(#krate::Value::String(::std::string::String::from(#unit_name))),
+    })
+}
+
+pub fn derive_from_nbt(input: DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(&input.attrs)?;
+    let krate = &container.krate;
+    let name = &input.ident;
+    let private = quote!(#krate::macros::__private);
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            read_fields(&container, quote!(Self), &data.fields, &fields)?
+        }
+        Data::Enum(data) => {
+            let ty = name.to_string();
+            let mut units = Vec::new();
+            let mut tagged = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let key = container.key(ident, &Options::parse(&variant.attrs)?);
+                if let Fields::Unit = variant.fields {
+                    units.push(quote!(#key => ::core::result::Result::Ok(Self::#ident),));
+                    continue;
+                }
+                let fields = fields(&variant.fields)?;
+                let read = read_fields(&container, quote!(Self::#ident), &variant.fields, &fields)?;
+                tagged.push(quote! {
+                    #key => #private::within_variant(
+                        (|| -> ::core::result::Result<Self, #krate::macros::FromNbtError> {
+                            #read
+                        })(),
+                        #key,
+                    ),
+                });
+            }
+            quote! {
+                match #private::variant(__value) {
+                    ::core::option::Option::Some((__variant, ::core::option::Option::None)) => {
+                        match __variant {
+                            #(#units)*
+                            _ => ::core::result::Result::Err(#private::unknown_variant(#ty, __variant)),
+                        }
+                    }
+                    ::core::option::Option::Some((__variant, ::core::option::Option::Some(__value))) => {
+                        match __variant {
+                            #(#tagged)*
+                            _ => ::core::result::Result::Err(#private::unknown_variant(#ty, __variant)),
+                        }
+                    }
+                    ::core::option::Option::None => {
+                        ::core::result::Result::Err(#private::no_matching_variant(#ty))
+                    }
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "unions can not be read from NBT",
+            ))
+        }
+    };
+    let generics = add_bounds(&input.generics, quote!(#krate::FromNbt));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::FromNbt for #name #ty_generics #where_clause {
+            #[allow(clippy::redundant_closure_call)]
+            fn from_nbt(
+                __value: &#krate::Value,
+            ) -> ::core::result::Result<Self, #krate::macros::FromNbtError> {
+                #body
+            }
+        }
+    })
+}
+
+/// Reads a struct or variant from `__value`, returning from the enclosing function
+fn read_fields(
+    container: &Container,
+    path: TokenStream,
+    kind: &Fields,
+    fields: &[Field],
+) -> syn::Result<TokenStream> {
+    let krate = &container.krate;
+    let private = quote!(#krate::macros::__private);
+    let default = quote!(::core::default::Default::default());
+    Ok(match kind {
+        Fields::Named(_) => {
+            let read: Vec<String> = fields
+                .iter()
+                .filter(|field| !field.options.skip && !field.options.flatten)
+                .map(|field| container.key(field.ident.unwrap(), &field.options))
+                .collect();
+            let values = fields.iter().map(|field| {
+                let ident = field.ident.unwrap();
+                let key = container.key(ident, &field.options);
+                let value = if field.options.skip {
+                    default.clone()
+                } else if field.options.flatten {
+                    quote!(#krate::FromNbt::from_nbt(&#private::rest(__cmp, &[#(#read),*]))?)
+                } else if field.optional || field.options.default {
+                    quote!(#private::default_field(__cmp, #key)?)
+                } else {
+                    quote!(#private::field(__cmp, #key)?)
+                };
+                quote!(#ident: #value)
+            });
+            quote! {
+                let __cmp = #private::compound(__value)?;
+                ::core::result::Result::Ok(#path { #(#values),* })
+            }
+        }
+        Fields::Unnamed(_) if fields.len() == 1 => {
+            quote!(::core::result::Result::Ok(#path(#krate::FromNbt::from_nbt(__value)?)))
+        }
+        Fields::Unnamed(_) => {
+            let len = fields.len();
+            let values = (0..len).map(|index| quote!(#private::element(__list, #index)?));
+            quote! {
+                let __list = #private::list(__value, #len)?;
+                ::core::result::Result::Ok(#path(#(#values),*))
+            }
+        }
+        Fields::Unit => quote!(::core::result::Result::Ok(#path)),
+    })
+}
@@ -0,0 +1,301 @@
+use flux_nbt::{snbt, Value};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    Expr, LitStr, Path, Token,
+};
+
+/// Prefixes of the unquoted words placeholders are replaced with before parsing.
+///
+/// The source is parsed once with each, so a string only comes from a placeholder when the two
+/// parses disagree on it, and quoted text can never be mistaken for one.
+const MARKERS: [&str; 2] = ["__flux_snbt_a_", "__flux_snbt_b_"];
+
+/// Input of `snbt!`, an SNBT literal optionally preceded by `crate = path,`
+pub struct Input {
+    krate: Path,
+    literal: LitStr,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let krate = if input.peek(Token![crate]) {
+            input.parse::<Token![crate]>()?;
+            input.parse::<Token![=]>()?;
+            let krate = input.parse()?;
+            input.parse::<Token![,]>()?;
+            krate
+        } else {
+            syn::parse_quote!(::fluxmc::nbt)
+        };
+        Ok(Self {
+            krate,
+            literal: input.parse()?,
+        })
+    }
+}
+
+/// A `#{expr}` placeholder, with its location in chars in the original and the replaced source
+struct Placeholder {
+    expr: Expr,
+    start: usize,
+    len: usize,
+    replaced_start: usize,
+    replaced_len: usize,
+}
+
+pub fn expand(Input { krate, literal }: Input) -> syn::Result<TokenStream> {
+    let source = literal.value();
+    let (replaced, placeholders) = replace_placeholders(&source, &literal, MARKERS[0])?;
+    let value = snbt::from_str(&replaced).map_err(|err| {
+        let index = original_index(char_index(&replaced, err.line, err.column), &placeholders);
+        let (line, column) = line_column(&source, index);
+        syn::Error::new(
+            literal.span(),
+            format!("{} at line {line}, column {column}", err.kind),
+        )
+    })?;
+    let (replaced, _) = replace_placeholders(&source, &literal, MARKERS[1])?;
+    let other = snbt::from_str(&replaced)
+        .map_err(|err| syn::Error::new(literal.span(), err.kind.to_string()))?;
+    Construct {
+        krate: &krate,
+        literal: &literal,
+        source: &source,
+        placeholders: &placeholders,
+    }
+    .value(&value, &other)
+}
+
+/// Replaces every placeholder outside of quoted strings with a marker word
+fn replace_placeholders(
+    source: &str,
+    literal: &LitStr,
+    prefix: &str,
+) -> syn::Result<(String, Vec<Placeholder>)> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut replaced = String::with_capacity(source.len());
+    let mut replaced_len = 0;
+    let mut placeholders = Vec::new();
+    let mut quote = None;
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        match quote {
+            Some(_) if c == '\\' && index + 1 < chars.len() => {
+                replaced.push(c);
+                replaced.push(chars[index + 1]);
+                replaced_len += 2;
+                index += 2;
+                continue;
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && chars.get(index + 1) == Some(&'{') => {
+                let mut depth = 0;
+                let mut end = index + 1;
+                loop {
+                    match chars.get(end) {
+                        Some('{') => depth += 1,
+                        Some('}') if depth == 1 => break,
+                        Some('}') => depth -= 1,
+                        Some(_) => {}
+                        None => {
+                            return Err(syn::Error::new(
+                                literal.span(),
+                                "unclosed `#{` placeholder",
+                            ))
+                        }
+                    }
+                    end += 1;
+                }
+                let text: String = chars[index + 2..end].iter().collect();
+                let expr = syn::parse_str(&text).map_err(|err| {
+                    syn::Error::new(
+                        literal.span(),
+                        format!("invalid placeholder `{text}`: {err}"),
+                    )
+                })?;
+                let marker = format!("{prefix}{}", placeholders.len());
+                let marker_len = marker.chars().count();
+                placeholders.push(Placeholder {
+                    expr,
+                    start: index,
+                    len: end + 1 - index,
+                    replaced_start: replaced_len,
+                    replaced_len: marker_len,
+                });
+                replaced.push_str(&marker);
+                replaced_len += marker_len;
+                index = end + 1;
+                continue;
+            }
+            None => {}
+        }
+        replaced.push(c);
+        replaced_len += 1;
+        index += 1;
+    }
+    Ok((replaced, placeholders))
+}
+
+/// Converts a 1-based line and column into a char index
+fn char_index(source: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = source
+        .split('\n')
+        .take(line - 1)
+        .map(|line| line.chars().count() + 1)
+        .sum();
+    line_start + column - 1
+}
+
+fn line_column(source: &str, index: usize) -> (usize, usize) {
+    let before: Vec<char> = source.chars().take(index).collect();
+    let line = before.iter().filter(|c| **c == '\n').count() + 1;
+    let column = index
+        - before
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |pos| pos + 1)
+        + 1;
+    (line, column)
+}
+
+/// Maps a char index in the replaced source back to the original source
+fn original_index(index: usize, placeholders: &[Placeholder]) -> usize {
+    let mut shift = 0isize;
+    for placeholder in placeholders {
+        if index < placeholder.replaced_start {
+            break;
+        }
+        if index < placeholder.replaced_start + placeholder.replaced_len {
+            return placeholder.start;
+        }
+        shift = placeholder.start as isize + placeholder.len as isize
+            - (placeholder.replaced_start + placeholder.replaced_len) as isize;
+    }
+    (index as isize + shift) as usize
+}
+
+/// Builds the tokens constructing a value, from the parses with both markers
+struct Construct<'a> {
+    krate: &'a Path,
+    literal: &'a LitStr,
+    source: &'a str,
+    placeholders: &'a [Placeholder],
+}
+
+impl Construct<'_> {
+    /// Finds the placeholder a string came from, `Ok(None)` for strings of the source itself
+    fn placeholder(&self, st: &str, other: &str) -> syn::Result<Option<&Placeholder>> {
+        if st == other {
+            return Ok(None);
+        }
+        let index = st
+            .strip_prefix(MARKERS[0])
+            .zip(other.strip_prefix(MARKERS[1]))
+            .filter(|(index, other)| index == other)
+            .and_then(|(index, _)| index.parse::<usize>().ok())
+            .and_then(|index| self.placeholders.get(index));
+        match index {
+            Some(placeholder) => Ok(Some(placeholder)),
+            None => {
+                let start = self
+                    .placeholders
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(index, _)| st.contains(&format!("{}{index}", MARKERS[0])))
+                    .map_or(0, |(_, placeholder)| placeholder.start);
+                Err(self.error(start, "a placeholder must be a whole value"))
+            }
+        }
+    }
+
+    fn error(&self, index: usize, message: &str) -> syn::Error {
+        let (line, column) = line_column(self.source, index);
+        syn::Error::new(
+            self.literal.span(),
+            format!("{message} at line {line}, column {column}"),
+        )
+    }
+
+    fn value(&self, value: &Value, other: &Value) -> syn::Result<TokenStream> {
+        let krate = self.krate;
+        if let (Value::String(st), Value::String(other)) = (value, other) {
+            if let Some(placeholder) = self.placeholder(st, other)? {
+                let expr = &placeholder.expr;
+                return Ok(quote!(#krate::Nbt::nbt(&(#expr))));
+            }
+        }
+        Ok(match (value, other) {
+            (Value::List(list), Value::List(others)) => {
+                let elements = list
+                    .iter()
+                    .zip(others)
+                    .map(|(element, other)| self.value(element, other))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote!(#krate::Value::List(::std::vec![#(#elements),*]))
+            }
+            (Value::Compound(cmp), Value::Compound(others)) => {
+                let mut keys = Vec::with_capacity(cmp.len());
+                let mut values = Vec::with_capacity(cmp.len());
+                for ((key, value), (other_key, other)) in cmp.iter().zip(others) {
+                    if let Some(placeholder) = self.placeholder(key, other_key)? {
+                        return Err(self.error(
+                            placeholder.start,
+                            "placeholders can not be used as compound keys",
+                        ));
+                    }
+                    keys.push(key);
+                    values.push(self.value(value, other)?);
+                }
+                quote! {
+                    #krate::Value::Compound(#krate::Map::<::std::string::String, #krate::Value>::from([
+                        #((::std::string::String::from(#keys), #values)),*
+                    ]))
+                }
+            }
+            (value, _) => literal(krate, value),
+        })
+    }
+}
+
+/// Builds the tokens constructing a value without placeholders
+fn literal(krate: &Path, value: &Value) -> TokenStream {
+    match value {
+        Value::Byte(v) => quote!(#krate::Value::Byte(#v)),
+        Value::Short(v) => quote!(#krate::Value::Short(#v)),
+        Value::Int(v) => quote!(#krate::Value::Int(#v)),
+        Value::Long(v) => quote!(#krate::Value::Long(#v)),
+        Value::Float(v) if v.is_finite() => quote!(#krate::Value::Float(#v)),
+        Value::Float(v) => {
+            let bits = v.to_bits();
+            quote!(#krate::Value::Float(f32::from_bits(#bits)))
+        }
+        Value::Double(v) if v.is_finite() => quote!(#krate::Value::Double(#v)),
+        Value::Double(v) => {
+            let bits = v.to_bits();
+            quote!(#krate::Value::Double(f64::from_bits(#bits)))
+        }
+        Value::ByteArray(bytes) => quote!(#krate::Value::ByteArray(::std::vec![#(#bytes),*])),
+        Value::String(st) => quote!(#krate::Value::String(::std::string::String::from(#st))),
+        Value::IntArray(ints) => quote!(#krate::Value::IntArray(::std::vec![#(#ints),*])),
+        Value::LongArray(longs) => quote!(#krate::Value::LongArray(::std::vec![#(#longs),*])),
+        Value::List(list) => {
+            let elements = list.iter().map(|element| literal(krate, element));
+            quote!(#krate::Value::List(::std::vec![#(#elements),*]))
+        }
+        Value::Compound(cmp) => {
+            let keys = cmp.keys();
+            let values = cmp.values().map(|value| literal(krate, value));
+            quote! {
+                #krate::Value::Compound(#krate::Map::<::std::string::String, #krate::Value>::from([
+                    #((::std::string::String::from(#keys), #values)),*
+                ]))
+            }
+        }
+    }
+}
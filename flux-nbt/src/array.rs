@@ -0,0 +1,265 @@
+//! Explicit control over the tag type of serialized sequences.
+//!
+//! Sequences are serialized as `TAG_List` by default. The wrapper types and the
+//! `#[serde(with = ...)]` helpers in this module force a specific tag instead:
+//!
+//! ```
+//! # use serde::Serialize;
+//! #[derive(Serialize)]
+//! #[serde(rename_all = "PascalCase")]
+//! struct Explosion {
+//!     #[serde(with = "flux_nbt::array::int_array")]
+//!     colors: Vec<u32>,
+//!     fade_colors: flux_nbt::IntArray,
+//! }
+//! ```
+//!
+//! Both serializers recognize the marker newtype names used here, any other serializer
+//! sees a plain newtype struct.
+//!
+//! A [`Value`] is serialized untagged, so its arrays turn into lists like any other
+//! sequence. Fields holding one should use [`value`] to keep their tags.
+
+use std::ops::{Deref, DerefMut};
+
+use nbt::{Map, Value};
+use serde::{de::EnumAccess, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::stream::tag;
+
+const BYTE_ARRAY_MARKER: &str = "__flux_nbt_ByteArray";
+const INT_ARRAY_MARKER: &str = "__flux_nbt_IntArray";
+const LONG_ARRAY_MARKER: &str = "__flux_nbt_LongArray";
+const LIST_MARKER: &str = "__flux_nbt_List";
+pub(crate) const VALUE_MARKER: &str = "__flux_nbt_Value";
+
+/// Returns the tag forced by a marker newtype name
+pub(crate) fn marker_tag(name: &str) -> Option<u8> {
+    match name {
+        BYTE_ARRAY_MARKER => Some(tag::BYTE_ARRAY),
+        INT_ARRAY_MARKER => Some(tag::INT_ARRAY),
+        LONG_ARRAY_MARKER => Some(tag::LONG_ARRAY),
+        LIST_MARKER => Some(tag::LIST),
+        _ => None,
+    }
+}
+
+/// Returns the element tag of an array tag
+pub(crate) fn element_tag(array: u8) -> Option<u8> {
+    match array {
+        tag::BYTE_ARRAY => Some(tag::BYTE),
+        tag::INT_ARRAY => Some(tag::INT),
+        tag::LONG_ARRAY => Some(tag::LONG),
+        _ => None,
+    }
+}
+
+macro_rules! with_module {
+    ($($module:ident: $marker:ident => $tag:literal),* $(,)?) => {
+        $(
+            #[doc = concat!("Serializes any sequence as `", $tag, "`, for use with `#[serde(with = ...)]`")]
+            pub mod $module {
+                use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+                pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    T: ?Sized + Serialize,
+                    S: Serializer,
+                {
+                    serializer.serialize_newtype_struct(super::$marker, value)
+                }
+
+                pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+                where
+                    T: Deserialize<'de>,
+                    D: Deserializer<'de>,
+                {
+                    T::deserialize(deserializer)
+                }
+            }
+        )*
+    };
+}
+
+with_module! {
+    byte_array: BYTE_ARRAY_MARKER => "TAG_Byte_Array",
+    int_array: INT_ARRAY_MARKER => "TAG_Int_Array",
+    long_array: LONG_ARRAY_MARKER => "TAG_Long_Array",
+    list: LIST_MARKER => "TAG_List",
+}
+
+macro_rules! wrapper {
+    ($($(#[$meta:meta])* $name:ident<$($gen:ident)?>($inner:ty) => $module:ident),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+            pub struct $name<$($gen)?>(pub $inner);
+
+            impl<$($gen)?> From<$inner> for $name<$($gen)?> {
+                fn from(value: $inner) -> Self {
+                    Self(value)
+                }
+            }
+
+            impl<$($gen)?> Deref for $name<$($gen)?> {
+                type Target = $inner;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.0
+                }
+            }
+
+            impl<$($gen)?> DerefMut for $name<$($gen)?> {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    &mut self.0
+                }
+            }
+
+            impl<$($gen: Serialize)?> Serialize for $name<$($gen)?> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    $module::serialize(&self.0, serializer)
+                }
+            }
+
+            impl<'de, $($gen: Deserialize<'de>)?> Deserialize<'de> for $name<$($gen)?> {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    $module::deserialize(deserializer).map(Self)
+                }
+            }
+        )*
+    };
+}
+
+wrapper! {
+    /// A sequence of bytes that is always serialized as `TAG_Byte_Array`
+    ByteArray<>(Vec<i8>) => byte_array,
+    /// A sequence of ints that is always serialized as `TAG_Int_Array`
+    IntArray<>(Vec<i32>) => int_array,
+    /// A sequence of longs that is always serialized as `TAG_Long_Array`
+    LongArray<>(Vec<i64>) => long_array,
+    /// A sequence that is always serialized as `TAG_List`, even if it holds bytes, ints or longs
+    NbtList<T>(Vec<T>) => list,
+}
+
+/// Serializes a [`Value`] with the tags of its arrays, for use with `#[serde(with = ...)]`
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use flux_nbt::{nbt, Value};
+/// #[derive(Serialize, Deserialize)]
+/// struct Item {
+///     #[serde(with = "flux_nbt::array::value")]
+///     tag: Value,
+/// }
+///
+/// let item = Item { tag: nbt! { colors: [I; 1, 2] } };
+/// let nbt = flux_nbt::ser::to_nbt(&item).unwrap();
+/// assert_eq!(nbt, nbt! { tag: { colors: [I; 1, 2] } });
+/// ```
+///
+/// Deserializing relies on a marker newtype name that only [`from_nbt`](crate::de::from_nbt)
+/// recognizes, other deserializers fall back to the untagged implementation of [`Value`].
+pub mod value {
+    use nbt::Value;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+        super::Tagged(value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        super::TaggedValue::deserialize(deserializer).map(|value| value.0)
+    }
+}
+
+struct Tagged<'a>(&'a Value);
+
+impl Serialize for Tagged<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::ByteArray(bytes) => byte_array::serialize(bytes, serializer),
+            Value::IntArray(ints) => int_array::serialize(ints, serializer),
+            Value::LongArray(longs) => long_array::serialize(longs, serializer),
+            Value::List(list) => list::serialize(&TaggedList(list), serializer),
+            Value::Compound(cmp) => {
+                serializer.collect_map(cmp.iter().map(|(key, value)| (key, Tagged(value))))
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+struct TaggedList<'a>(&'a [Value]);
+
+impl Serialize for TaggedList<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Tagged))
+    }
+}
+
+/// A [`Value`] read through [`VALUE_MARKER`], which [`crate::de::Deserializer`] answers with
+/// an enum whose variant is the tag id
+struct TaggedValue(Value);
+
+impl<'de> Deserialize<'de> for TaggedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_newtype_struct(VALUE_MARKER, TaggedVisitor)
+            .map(Self)
+    }
+}
+
+struct TaggedVisitor;
+
+impl<'de> serde::de::Visitor<'de> for TaggedVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an NBT value")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+        use serde::de::{Error, Unexpected, VariantAccess};
+
+        let (id, variant) = data.variant::<u8>()?;
+        Ok(match id {
+            tag::BYTE => Value::Byte(variant.newtype_variant()?),
+            tag::SHORT => Value::Short(variant.newtype_variant()?),
+            tag::INT => Value::Int(variant.newtype_variant()?),
+            tag::LONG => Value::Long(variant.newtype_variant()?),
+            tag::FLOAT => Value::Float(variant.newtype_variant()?),
+            tag::DOUBLE => Value::Double(variant.newtype_variant()?),
+            tag::STRING => Value::String(variant.newtype_variant()?),
+            tag::BYTE_ARRAY => Value::ByteArray(variant.newtype_variant()?),
+            tag::INT_ARRAY => Value::IntArray(variant.newtype_variant()?),
+            tag::LONG_ARRAY => Value::LongArray(variant.newtype_variant()?),
+            tag::LIST => Value::List(
+                variant
+                    .newtype_variant::<Vec<TaggedValue>>()?
+                    .into_iter()
+                    .map(|value| value.0)
+                    .collect(),
+            ),
+            tag::COMPOUND => Value::Compound(
+                variant
+                    .newtype_variant::<Map<String, TaggedValue>>()?
+                    .into_iter()
+                    .map(|(key, value)| (key, value.0))
+                    .collect(),
+            ),
+            id => {
+                return Err(A::Error::invalid_value(
+                    Unexpected::Unsigned(id.into()),
+                    &self,
+                ))
+            }
+        })
+    }
+}
@@ -0,0 +1,606 @@
+use nbt::Value;
+use serde::{
+    de::{
+        value::{BorrowedStrDeserializer, U8Deserializer, UnitDeserializer},
+        DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize,
+};
+use thiserror::Error;
+
+use crate::{array, stream::tag};
+
+/// Deserializes a `T` from an already parsed NBT value.
+///
+/// Missing compound keys are treated as `None` for optional fields, and narrower
+/// numeric tags are widened to fit the requested type (e.g. `TAG_Byte` to `bool` or `i32`).
+/// Unit variants and newtype variants wrapping an absent value (`None` or `()`) are read
+/// from strings and other variants from `{Variant: value}` compounds, which is how
+/// [`to_nbt`](crate::ser::to_nbt) writes them.
+pub fn from_nbt<'de, T: Deserialize<'de>>(value: &'de Value) -> Result<T, DeError> {
+    T::deserialize(Deserializer::new(value))
+}
+
+#[derive(Debug, Error)]
+pub enum DeError {
+    #[error("Expected {expected}, found {found}")]
+    InvalidType {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("Expected a compound with exactly one variant key, found {0} keys")]
+    InvalidEnum(usize),
+    #[error("A serde error has occurred: {0}")]
+    Custom(String),
+}
+
+impl serde::de::Error for DeError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        Self::Custom(msg.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Deserializer<'de> {
+    value: &'de Value,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(value: &'de Value) -> Self {
+        Self { value }
+    }
+
+    fn mismatch(&self, expected: &'static str) -> DeError {
+        DeError::InvalidType {
+            expected,
+            found: tag::name(self.value.id()).to_owned(),
+        }
+    }
+
+    fn number(&self, expected: &'static str) -> Result<Number, DeError> {
+        match *self.value {
+            Value::Byte(v) => Ok(Number::Byte(v)),
+            Value::Short(v) => Ok(Number::Short(v)),
+            Value::Int(v) => Ok(Number::Int(v)),
+            Value::Long(v) => Ok(Number::Long(v)),
+            Value::Float(v) => Ok(Number::Float(v)),
+            Value::Double(v) => Ok(Number::Double(v)),
+            _ => Err(self.mismatch(expected)),
+        }
+    }
+}
+
+macro_rules! forward_to_number {
+    ($($method:ident => $expected:literal),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.number($expected)?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::String(str) => visitor.visit_borrowed_str(str),
+            Value::List(list) => visitor.visit_seq(Seq {
+                iter: list.iter().map(Deserializer::new),
+            }),
+            Value::ByteArray(bytes) => visitor.visit_seq(Seq {
+                iter: bytes.iter().map(|each| Number::Byte(*each)),
+            }),
+            Value::IntArray(ints) => visitor.visit_seq(Seq {
+                iter: ints.iter().map(|each| Number::Int(*each)),
+            }),
+            Value::LongArray(longs) => visitor.visit_seq(Seq {
+                iter: longs.iter().map(|each| Number::Long(*each)),
+            }),
+            Value::Compound(cmp) => visitor.visit_map(Map {
+                iter: cmp.iter(),
+                value: None,
+            }),
+            _ => self.number("any value")?.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_number! {
+        deserialize_bool => "a boolean",
+        deserialize_i8 => "a byte",
+        deserialize_i16 => "a short",
+        deserialize_i32 => "an int",
+        deserialize_i64 => "a long",
+        deserialize_u8 => "a byte",
+        deserialize_u16 => "a short",
+        deserialize_u32 => "an int",
+        deserialize_u64 => "a long",
+        deserialize_f32 => "a float",
+        deserialize_f64 => "a double",
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::String(str) if str.chars().count() == 1 => {
+                visitor.visit_char(str.chars().next().unwrap())
+            }
+            _ => Err(self.mismatch("a single character string")),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::String(str) => visitor.visit_borrowed_str(str),
+            _ => Err(self.mismatch("a string")),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::ByteArray(bytes) => {
+                visitor.visit_byte_buf(bytes.iter().map(|byte| *byte as u8).collect())
+            }
+            Value::List(_) => self.deserialize_seq(visitor),
+            _ => Err(self.mismatch("a byte array")),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // absent values are never serialized, so anything present is `Some`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == array::VALUE_MARKER {
+            return visitor.visit_enum(Tagged(self));
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::List(_) | Value::ByteArray(_) | Value::IntArray(_) | Value::LongArray(_) => {
+                self.deserialize_any(visitor)
+            }
+            _ => Err(self.mismatch("a list or an array")),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Compound(_) => self.deserialize_any(visitor),
+            _ => Err(self.mismatch("a compound")),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::String(variant) => visitor.visit_enum(UnitVariant(variant)),
+            Value::Compound(cmp) if cmp.len() == 1 => {
+                let (variant, value) = cmp.iter().next().unwrap();
+                visitor.visit_enum(Enum {
+                    variant,
+                    value: Deserializer::new(value),
+                })
+            }
+            Value::Compound(cmp) => Err(DeError::InvalidEnum(cmp.len())),
+            _ => Err(self.mismatch("a string or a compound")),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// A numeric tag, either standalone or an element of a typed array
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+impl<'de> serde::Deserializer<'de> for Number {
+    type Error = DeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Number::Byte(v) => visitor.visit_i8(v),
+            Number::Short(v) => visitor.visit_i16(v),
+            Number::Int(v) => visitor.visit_i32(v),
+            Number::Long(v) => visitor.visit_i64(v),
+            Number::Float(v) => visitor.visit_f32(v),
+            Number::Double(v) => visitor.visit_f64(v),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Number::Byte(v) => visitor.visit_bool(v != 0),
+            Number::Short(v) => visitor.visit_bool(v != 0),
+            Number::Int(v) => visitor.visit_bool(v != 0),
+            Number::Long(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // unsigned values are serialized with a wrapping cast into the tag of the same
+    // width, so they have to be cast back the same way
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Number::Byte(v) => visitor.visit_u8(v as u8),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Number::Short(v) => visitor.visit_u16(v as u16),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Number::Int(v) => visitor.visit_u32(v as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Number::Long(v) => visitor.visit_u64(v as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Seq<I> {
+    iter: I,
+}
+
+impl<'de, I, D> SeqAccess<'de> for Seq<I>
+where
+    I: ExactSizeIterator<Item = D>,
+    D: serde::Deserializer<'de, Error = DeError>,
+{
+    type Error = DeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(element) => seed.deserialize(element).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct Map<'de, I> {
+    iter: I,
+    value: Option<&'de Value>,
+}
+
+impl<'de, I> MapAccess<'de> for Map<'de, I>
+where
+    I: ExactSizeIterator<Item = (&'de String, &'de Value)>,
+{
+    type Error = DeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Deserializer::new(value)),
+            None => Err(DeError::Custom("Value requested before its key".to_owned())),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct Enum<'de> {
+    variant: &'de str,
+    value: Deserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for Enum<'de> {
+    type Error = DeError;
+
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+/// A variant written as a string, either a unit variant or one wrapping an absent value
+struct UnitVariant<'de>(&'de str);
+
+impl<'de> EnumAccess<'de> for UnitVariant<'de> {
+    type Error = DeError;
+
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.0))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for UnitVariant<'de> {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(UnitDeserializer::new())
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(DeError::InvalidType {
+            expected: "a compound",
+            found: tag::name(tag::STRING).to_owned(),
+        })
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.tuple_variant(0, visitor)
+    }
+}
+
+/// Exposes the tag id of a value as the variant, see [`array::value`]
+struct Tagged<'de>(Deserializer<'de>);
+
+impl<'de> EnumAccess<'de> for Tagged<'de> {
+    type Error = DeError;
+
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let id = seed.deserialize(U8Deserializer::<DeError>::new(self.0.value.id()))?;
+        Ok((id, self.0))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer<'de> {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_map(self, visitor)
+    }
+}
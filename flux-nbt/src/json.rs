@@ -0,0 +1,547 @@
+//! Conversion between NBT and JSON.
+//!
+//! [`JsonMode::Typed`] writes every value as an object with a single key naming its tag,
+//! e.g. `{"compound": {"Count": {"byte": 1}}}`, which converts back to exactly the same NBT.
+//! Longs and non-finite floats are written as strings, as JSON numbers can not hold them.
+//!
+//! [`JsonMode::Loose`] writes plain JSON and infers tags when reading, the way the game
+//! converts JSON to NBT: booleans become bytes, integers become ints or longs, any other
+//! number becomes a double and lists of mixed numbers are widened to the largest type.
+
+use std::fmt::{self, Display};
+
+use nbt::{Map, Value};
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use thiserror::Error;
+
+use crate::{snbt::MAX_DEPTH, stream::tag};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonMode {
+    /// Keeps the exact tag of every value
+    #[default]
+    Typed,
+    /// Plain JSON, tags are inferred when reading
+    Loose,
+}
+
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct JsonError(#[from] serde_json::Error);
+
+pub fn to_string(value: &Value, mode: JsonMode) -> String {
+    // neither representation can fail to serialize
+    match mode {
+        JsonMode::Typed => serde_json::to_string(&Typed(value)),
+        JsonMode::Loose => serde_json::to_string(&Loose(value)),
+    }
+    .unwrap()
+}
+
+pub fn to_string_pretty(value: &Value, mode: JsonMode) -> String {
+    match mode {
+        JsonMode::Typed => serde_json::to_string_pretty(&Typed(value)),
+        JsonMode::Loose => serde_json::to_string_pretty(&Loose(value)),
+    }
+    .unwrap()
+}
+
+/// Converts to a [`serde_json::Value`], whose compounds are only kept in order if
+/// serde_json's `preserve_order` feature is enabled
+pub fn to_json(value: &Value, mode: JsonMode) -> serde_json::Value {
+    match mode {
+        JsonMode::Typed => serde_json::to_value(Typed(value)),
+        JsonMode::Loose => serde_json::to_value(Loose(value)),
+    }
+    .unwrap()
+}
+
+/// Reads NBT nested up to 512 compounds and lists deep, like the game
+pub fn from_str(json: &str, mode: JsonMode) -> Result<Value, JsonError> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    // typed mode spends two JSON objects per level, so the seeds limit the depth
+    // instead and the stack grows on demand
+    deserializer.disable_recursion_limit();
+    let stacked = serde_stacker::Deserializer::new(&mut deserializer);
+    let value = match mode {
+        JsonMode::Typed => TypedSeed { depth: 0 }.deserialize(stacked)?,
+        JsonMode::Loose => LooseSeed { depth: 0 }.deserialize(stacked)?,
+    };
+    deserializer.end()?;
+    Ok(value)
+}
+
+pub fn from_json(json: &serde_json::Value, mode: JsonMode) -> Result<Value, JsonError> {
+    let stacked = serde_stacker::Deserializer::new(json);
+    Ok(match mode {
+        JsonMode::Typed => TypedSeed { depth: 0 }.deserialize(stacked)?,
+        JsonMode::Loose => LooseSeed { depth: 0 }.deserialize(stacked)?,
+    })
+}
+
+/// Serializes a value in the typed representation, for embedding it into other serde types
+#[derive(Debug, Clone, Copy)]
+pub struct Typed<'a>(pub &'a Value);
+
+/// Serializes a value in the loose representation, for embedding it into other serde types
+#[derive(Debug, Clone, Copy)]
+pub struct Loose<'a>(pub &'a Value);
+
+const BYTE: &str = "byte";
+const SHORT: &str = "short";
+const INT: &str = "int";
+const LONG: &str = "long";
+const FLOAT: &str = "float";
+const DOUBLE: &str = "double";
+const BYTE_ARRAY: &str = "byte_array";
+const STRING: &str = "string";
+const LIST: &str = "list";
+const COMPOUND: &str = "compound";
+const INT_ARRAY: &str = "int_array";
+const LONG_ARRAY: &str = "long_array";
+
+const TAGS: &[&str] = &[
+    BYTE, SHORT, INT, LONG, FLOAT, DOUBLE, BYTE_ARRAY, STRING, LIST, COMPOUND, INT_ARRAY,
+    LONG_ARRAY,
+];
+
+impl Serialize for Typed<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self.0 {
+            Value::Byte(v) => map.serialize_entry(BYTE, v)?,
+            Value::Short(v) => map.serialize_entry(SHORT, v)?,
+            Value::Int(v) => map.serialize_entry(INT, v)?,
+            Value::Long(v) => map.serialize_entry(LONG, &Long(*v))?,
+            Value::Float(v) => map.serialize_entry(FLOAT, &Float(*v))?,
+            Value::Double(v) => map.serialize_entry(DOUBLE, &Double(*v))?,
+            Value::ByteArray(bytes) => map.serialize_entry(BYTE_ARRAY, bytes)?,
+            Value::String(st) => map.serialize_entry(STRING, st)?,
+            Value::List(list) => map.serialize_entry(LIST, &Elements(list, Typed))?,
+            Value::Compound(cmp) => map.serialize_entry(COMPOUND, &Entries(cmp, Typed))?,
+            Value::IntArray(ints) => map.serialize_entry(INT_ARRAY, ints)?,
+            Value::LongArray(longs) => {
+                map.serialize_entry(LONG_ARRAY, &Elements(longs, |v: &i64| Long(*v)))?
+            }
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Loose<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Byte(v) => serializer.serialize_i8(*v),
+            Value::Short(v) => serializer.serialize_i16(*v),
+            Value::Int(v) => serializer.serialize_i32(*v),
+            Value::Long(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f32(*v),
+            Value::Double(v) => serializer.serialize_f64(*v),
+            Value::ByteArray(bytes) => bytes.serialize(serializer),
+            Value::String(st) => serializer.serialize_str(st),
+            Value::List(list) => Elements(list, Loose).serialize(serializer),
+            Value::Compound(cmp) => Entries(cmp, Loose).serialize(serializer),
+            Value::IntArray(ints) => ints.serialize(serializer),
+            Value::LongArray(longs) => longs.serialize(serializer),
+        }
+    }
+}
+
+struct Elements<'a, T, F>(&'a [T], F);
+
+impl<'a, T, F, S> Serialize for Elements<'a, T, F>
+where
+    F: Fn(&'a T) -> S + Copy,
+    S: Serialize,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(self.0.iter().map(self.1))
+    }
+}
+
+struct Entries<'a, F>(&'a Map<String, Value>, F);
+
+impl<'a, F, S> Serialize for Entries<'a, F>
+where
+    F: Fn(&'a Value) -> S + Copy,
+    S: Serialize,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, (self.1)(value))))
+    }
+}
+
+/// A long written as a string, as JSON parsers commonly read numbers as doubles
+struct Long(i64);
+
+impl Serialize for Long {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Long {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LongVisitor;
+
+        impl Visitor<'_> for LongVisitor {
+            type Value = Long;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a long as a number or a string")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Long, E> {
+                Ok(Long(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Long, E> {
+                i64::try_from(v)
+                    .map(Long)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Long, E> {
+                v.parse()
+                    .map(Long)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(LongVisitor)
+    }
+}
+
+/// A float written as a number if finite, otherwise as a string holding its bits in hex
+struct Float(f32);
+
+/// A double written as a number if finite, otherwise as a string holding its bits in hex
+struct Double(f64);
+
+impl Serialize for Float {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_finite() {
+            serializer.serialize_f32(self.0)
+        } else {
+            serializer.collect_str(&format_args!("{:#010x}", self.0.to_bits()))
+        }
+    }
+}
+
+impl Serialize for Double {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_finite() {
+            serializer.serialize_f64(self.0)
+        } else {
+            serializer.collect_str(&format_args!("{:#018x}", self.0.to_bits()))
+        }
+    }
+}
+
+/// Accepts any JSON number or a hex string of raw bits, converted by the given functions
+struct FloatVisitor<T> {
+    from_f64: fn(f64) -> T,
+    from_bits: fn(u64) -> Option<T>,
+}
+
+impl<T> Visitor<'_> for FloatVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number or a hex string of its bits")
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        Ok((self.from_f64)(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        Ok((self.from_f64)(v as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        Ok((self.from_f64)(v as f64))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.strip_prefix("0x")
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .and_then(self.from_bits)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Float {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FloatVisitor {
+            from_f64: |v| Float(v as f32),
+            from_bits: |bits| {
+                u32::try_from(bits)
+                    .ok()
+                    .map(|bits| Float(f32::from_bits(bits)))
+            },
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Double {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FloatVisitor {
+            from_f64: Double,
+            from_bits: |bits| Some(Double(f64::from_bits(bits))),
+        })
+    }
+}
+
+/// Counts one more compound or list, failing past the depth limit
+fn nested<E: de::Error>(depth: usize) -> Result<usize, E> {
+    if depth >= MAX_DEPTH {
+        return Err(E::custom(format_args!(
+            "Nesting is deeper than the limit of {MAX_DEPTH}"
+        )));
+    }
+    Ok(depth + 1)
+}
+
+/// Deserializes the typed representation, `depth` counts the compounds and lists around it
+#[derive(Clone, Copy)]
+struct TypedSeed {
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for TypedSeed {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TypedSeed {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an object with a single key naming the tag")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let Some(tag) = map.next_key::<String>()? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        let value = match tag.as_str() {
+            BYTE => Value::Byte(map.next_value()?),
+            SHORT => Value::Short(map.next_value()?),
+            INT => Value::Int(map.next_value()?),
+            LONG => Value::Long(map.next_value::<Long>()?.0),
+            FLOAT => Value::Float(map.next_value::<Float>()?.0),
+            DOUBLE => Value::Double(map.next_value::<Double>()?.0),
+            BYTE_ARRAY => Value::ByteArray(map.next_value()?),
+            STRING => Value::String(map.next_value()?),
+            LIST => Value::List(map.next_value_seed(ListSeed {
+                element: TypedSeed {
+                    depth: nested(self.depth)?,
+                },
+                widen: false,
+            })?),
+            COMPOUND => Value::Compound(map.next_value_seed(CompoundSeed(TypedSeed {
+                depth: nested(self.depth)?,
+            }))?),
+            INT_ARRAY => Value::IntArray(map.next_value()?),
+            LONG_ARRAY => Value::LongArray(
+                map.next_value::<Vec<Long>>()?
+                    .into_iter()
+                    .map(|long| long.0)
+                    .collect(),
+            ),
+            other => return Err(de::Error::unknown_variant(other, TAGS)),
+        };
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(2, &self));
+        }
+        Ok(value)
+    }
+}
+
+/// Deserializes plain JSON, inferring the tags, `depth` counts the compounds and lists around it
+#[derive(Clone, Copy)]
+struct LooseSeed {
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for LooseSeed {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LooseSeed {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value except null")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Byte(v as i8))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(i32::try_from(v).map_or(Value::Long(v), Value::Int))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        match i64::try_from(v) {
+            Ok(v) => self.visit_i64(v),
+            Err(_) => Ok(Value::Double(v as f64)),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Value, A::Error> {
+        ListSeed {
+            element: LooseSeed {
+                depth: nested(self.depth)?,
+            },
+            widen: true,
+        }
+        .visit_seq(seq)
+        .map(Value::List)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Value, A::Error> {
+        CompoundSeed(LooseSeed {
+            depth: nested(self.depth)?,
+        })
+        .visit_map(map)
+        .map(Value::Compound)
+    }
+}
+
+/// Deserializes a list of elements, which have to share a tag after widening numbers, if enabled
+struct ListSeed<S> {
+    element: S,
+    widen: bool,
+}
+
+impl<'de, S: DeserializeSeed<'de, Value = Value> + Copy> DeserializeSeed<'de> for ListSeed<S> {
+    type Value = Vec<Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<Value>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de, Value = Value> + Copy> Visitor<'de> for ListSeed<S> {
+    type Value = Vec<Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<Value>, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1024));
+        while let Some(element) = seq.next_element_seed(self.element)? {
+            list.push(element);
+        }
+        if self.widen {
+            widen_numbers(&mut list);
+        }
+        if let Some(first) = list.first() {
+            if let Some(other) = list.iter().find(|element| element.id() != first.id()) {
+                return Err(de::Error::custom(MixedList(first, other)));
+            }
+        }
+        Ok(list)
+    }
+}
+
+struct MixedList<'a>(&'a Value, &'a Value);
+
+impl Display for MixedList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Can not insert {} into a list of {}",
+            tag::name(self.1.id()),
+            tag::name(self.0.id())
+        )
+    }
+}
+
+/// Converts every number of a list to the largest number type in it, if all elements are numbers
+fn widen_numbers(list: &mut [Value]) {
+    let Some(widest) = list
+        .iter()
+        .map(number_rank)
+        .try_fold(0, |widest, rank| rank.map(|rank| widest.max(rank)))
+    else {
+        return;
+    };
+    for element in list {
+        *element = match (widest, &*element) {
+            (1, Value::Byte(v)) => Value::Int(*v as i32),
+            (2, Value::Byte(v)) => Value::Long(*v as i64),
+            (2, Value::Int(v)) => Value::Long(*v as i64),
+            (3, Value::Byte(v)) => Value::Double(*v as f64),
+            (3, Value::Int(v)) => Value::Double(*v as f64),
+            (3, Value::Long(v)) => Value::Double(*v as f64),
+            _ => continue,
+        }
+    }
+}
+
+/// Order of the number types loose mode produces, from narrowest to widest
+fn number_rank(value: &Value) -> Option<u8> {
+    match value {
+        Value::Byte(_) => Some(0),
+        Value::Int(_) => Some(1),
+        Value::Long(_) => Some(2),
+        Value::Double(_) => Some(3),
+        _ => None,
+    }
+}
+
+/// Deserializes a compound, keeping the order of its keys
+struct CompoundSeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de, Value = Value> + Copy> DeserializeSeed<'de> for CompoundSeed<S> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de, Value = Value> + Copy> Visitor<'de> for CompoundSeed<S> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a compound")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut cmp = Map::with_capacity(map.size_hint().unwrap_or(0).min(1024));
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(self.0)?;
+            cmp.insert(key, value);
+        }
+        Ok(cmp)
+    }
+}
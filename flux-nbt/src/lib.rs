@@ -1,10 +1,19 @@
+pub mod array;
+pub mod de;
+pub mod json;
 pub mod macros;
+pub mod patch;
+pub mod path;
 pub mod ser;
 pub mod snbt;
+pub mod stream;
 
-pub use macros::Nbt;
+pub use array::{ByteArray, IntArray, LongArray, NbtList};
+pub use macros::{FromNbt, Nbt};
 pub use nbt as bin;
-pub use nbt::Value;
+pub use nbt::{Map, Value};
+pub use patch::NbtPatch;
+pub use path::NbtPath;
 
 #[cfg(test)]
 mod tests {
@@ -25,6 +34,46 @@ mod tests {
         assert_eq!(tag.tag_name(), "TAG_Compound")
     }
 
+    #[test]
+    pub fn test_macro_grammar() {
+        let base = nbt! { id: "minecraft:stone", Count: 1b };
+        let slot = 3;
+        let name: Option<&str> = Some("Rock");
+        let lore: Option<&str> = None;
+        let value = nbt! {
+            ..base,
+            Count: 64b,
+            Damage: -5s,
+            Seed: 0xFF_FFL,
+            Big: 255u8,
+            Health: 19.5f,
+            Motion: [0.0d, -1.5d, 2e1],
+            "Slot": 0B,
+            [format!("Slot{slot}")]: (slot * 2),
+            tag: {
+                "display": { Name?: name, Lore?: lore },
+                [slot.to_string()]: r#"raw "quoted""#,
+                escaped: "tab\there\u{21}",
+            },
+        };
+        assert_eq!(
+            snbt::to_string(&value),
+            "{id:'minecraft:stone',Count:64b,Damage:-5s,Seed:65535L,Big:-1b,Health:19.5f,\
+             Motion:[0d,-1.5d,20d],Slot:0b,Slot3:6,tag:{display:{Name:'Rock'},\
+             3:'raw \"quoted\"',escaped:'tab\there!'}}"
+        );
+        assert_eq!(nbt! { flag: true, neg: -2147483648 }, {
+            let mut map = Map::new();
+            map.insert("flag".to_owned(), Value::Byte(1));
+            map.insert("neg".to_owned(), Value::Int(i32::MIN));
+            Value::Compound(map)
+        });
+        assert_eq!(
+            nbt! { a: 0B, b: -0B, c: 0i8, d: 0b0B },
+            nbt! { a: (0i8), b: (0i8), c: (0i8), d: (0i8) }
+        );
+    }
+
     #[test]
     pub fn test_snbt() {
         let value = nbt! {
@@ -37,4 +86,1252 @@ mod tests {
         };
         println!("{}", snbt::to_string(&value))
     }
+
+    #[test]
+    pub fn test_snbt_parse() {
+        let value = nbt! {
+            first: "Hello, World!",
+            second: [I; 1, 2, 3],
+            third: {
+                a: 1,
+                b: [B; 1, 2, 3],
+                c: [L; 4, 5],
+                d: [1.5f32, 2f32],
+                e: 3i16,
+            }
+        };
+        let parsed = snbt::from_str(&snbt::to_string(&value)).unwrap();
+        assert_eq!(parsed, value);
+
+        let parsed = snbt::from_str(
+            r#"{ Count: 1b, id: "minecraft:stone", tag: {Damage: 3s, Time: 10L, x: 0.5, y: 2f, z: +1.e2d, flag: true, word: abc, escaped: 'it\'s'} }"#,
+        )
+        .unwrap();
+        let expected = nbt! {
+            Count: 1i8,
+            id: "minecraft:stone",
+            tag: {
+                Damage: 3i16,
+                Time: 10i64,
+                x: 0.5f64,
+                y: 2f32,
+                z: 100f64,
+                flag: true,
+                word: "abc",
+                escaped: "it's",
+            }
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    pub fn test_snbt_parse_errors() {
+        let err = snbt::from_str("{a: 1,\n b 2}").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert_eq!(
+            err.kind,
+            snbt::SnbtErrorKind::Expected {
+                expected: "`:`",
+                found: Some('2')
+            }
+        );
+        assert_eq!(
+            snbt::from_str("{v: [1, 2b, {'': 3}, {a: 4}]}").unwrap(),
+            nbt! { v: [{ "": 1 }, { "": 2b }, { "": { "": 3 } }, { a: 4 }] }
+        );
+        let err = snbt::from_str("{a:'unterminated}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected closing quote, found end of input at line 1, column 18"
+        );
+        let err = snbt::from_str("[B;1b,'a']").unwrap_err();
+        assert_eq!(
+            err.kind,
+            snbt::SnbtErrorKind::InvalidArrayElement {
+                array: "TAG_Byte_Array",
+                found: "TAG_String"
+            }
+        );
+
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(snbt::from_str(&nested(512)).is_ok());
+        let err = snbt::from_str(&"[".repeat(100_000)).unwrap_err();
+        assert_eq!(err.kind, snbt::SnbtErrorKind::DepthLimitExceeded(512));
+        let err = snbt::from_str(&"{a:".repeat(513)).unwrap_err();
+        assert_eq!(err.kind, snbt::SnbtErrorKind::DepthLimitExceeded(512));
+    }
+
+    #[test]
+    pub fn test_snbt_formatter() {
+        let value = nbt! {
+            name: "stone",
+            count: 3i8,
+            lore: ["a", "b"],
+            data: {
+                ids: [I; 1, 2, 3],
+                empty: {},
+            }
+        };
+        let pretty = snbt::SnbtFormatter::pretty()
+            .with_indent(2)
+            .with_quote(snbt::QuoteStyle::Double)
+            .format(&value);
+        assert_eq!(
+            pretty,
+            "{\n  count: 3b,\n  data: {\n    empty: {},\n    ids: [I; 1, 2, 3]\n  },\n  lore: [\"a\", \"b\"],\n  name: \"stone\"\n}"
+        );
+        assert_eq!(snbt::from_str(&pretty).unwrap(), value);
+
+        let wrapped = snbt::SnbtFormatter::pretty()
+            .with_max_inline_width(12)
+            .format(&nbt! { ids: [I; 100, 200, 300] });
+        assert_eq!(
+            wrapped,
+            "{\n    ids: [I;\n        100,\n        200,\n        300\n    ]\n}"
+        );
+
+        let minified = snbt::SnbtFormatter::new()
+            .with_sorted_keys(true)
+            .format(&value);
+        assert_eq!(
+            minified,
+            "{count:3b,data:{empty:{},ids:[I;1,2,3]},lore:['a','b'],name:'stone'}"
+        );
+
+        let decimals = nbt! {
+            nan: (f32::NAN),
+            inf: (f64::INFINITY),
+            neg_inf: (f32::NEG_INFINITY),
+            large: 1e300,
+            small: 1.5e-5f,
+            plain: 1234.5,
+            edge: 1e7f,
+        };
+        let written = snbt::to_string(&decimals);
+        assert_eq!(
+            written,
+            "{nan:0f,inf:1.7976931348623157e308d,neg_inf:-3.4028235e38f,large:1e300d,\
+             small:1.5e-5f,plain:1234.5d,edge:1e7f}"
+        );
+        assert_eq!(
+            snbt::from_str(&written).unwrap(),
+            nbt! {
+                nan: 0f,
+                inf: (f64::MAX),
+                neg_inf: (f32::MIN),
+                large: 1e300,
+                small: 1.5e-5f,
+                plain: 1234.5,
+                edge: 1e7f,
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_snbt_escaping() {
+        let value = nbt! {
+            "minecraft:custom_data": "it's",
+            "my key": "say \"hi\"",
+            "": "both ' and \" \\",
+            plain_key: "",
+        };
+        let snbt = snbt::SnbtFormatter::new()
+            .with_sorted_keys(true)
+            .format(&value);
+        assert_eq!(
+            snbt,
+            r#"{'':'both \' and " \\','minecraft:custom_data':"it's",'my key':'say "hi"',plain_key:''}"#
+        );
+        assert_eq!(snbt::from_str(&snbt).unwrap(), value);
+
+        let double = snbt::SnbtFormatter::new()
+            .with_quote(snbt::QuoteStyle::Double)
+            .format(&nbt! { "a b": "it's" });
+        assert_eq!(double, r#"{"a b":"it's"}"#);
+    }
+
+    #[test]
+    pub fn test_snbt_versions() {
+        use snbt::{SnbtFormatter, SnbtVersion};
+
+        let legacy = SnbtFormatter::new().with_target_version(SnbtVersion::Legacy);
+        let modern = SnbtFormatter::new().with_target_version(SnbtVersion::V1_21_5);
+        assert_eq!(SnbtVersion::from_data_version(4189), SnbtVersion::Legacy);
+        assert_eq!(SnbtVersion::from_data_version(4325), SnbtVersion::V1_21_5);
+
+        let mixed = nbt! { list: [1, "a", { id: 3 }], bytes: [1b, 0b1B] };
+        assert_eq!(
+            legacy.format(&mixed),
+            "{list:[{'':1},{'':'a'},{id:3}],bytes:[1b,1b]}"
+        );
+        assert_eq!(modern.format(&mixed), "{list:[1,'a',{id:3}],bytes:[1b,1b]}");
+        assert_eq!(
+            modern.format(&snbt::from_str(&legacy.format(&mixed)).unwrap()),
+            modern.format(&mixed)
+        );
+        let parsed = snbt::from_str(&modern.format(&mixed)).unwrap();
+        assert_eq!(
+            parsed,
+            nbt! { list: [{ "": 1 }, { "": "a" }, { id: 3 }], bytes: [1b, 1b] }
+        );
+        assert_eq!(modern.format(&parsed), modern.format(&mixed));
+        assert_eq!(legacy.format(&parsed), legacy.format(&mixed));
+
+        let text = nbt! { Text: "line\nnext\u{1}" };
+        assert_eq!(legacy.format(&text), "{Text:'line\nnext\u{1}'}");
+        assert_eq!(modern.format(&text), "{Text:'line\\nnext\\x01'}");
+        assert_eq!(snbt::from_str(&modern.format(&text)).unwrap(), text);
+
+        // text components in either form print the same way for each version
+        let json = nbt! {
+            components: {
+                "minecraft:custom_name": r#"{"text":"Sword","bold":true}"#,
+                "minecraft:lore": ["plain", r#"{"text":"quoted"}"#]
+            }
+        };
+        let structured = nbt! {
+            components: {
+                "minecraft:custom_name": { text: "Sword", bold: true },
+                "minecraft:lore": ["plain", { text: "quoted" }]
+            }
+        };
+        for value in [&json, &structured] {
+            assert_eq!(
+                legacy.format(value),
+                r#"{components:{'minecraft:custom_name':'{"text":"Sword","bold":true}',"#
+                    .to_owned()
+                    + r#"'minecraft:lore':['"plain"','{"text":"quoted"}']}}"#
+            );
+            assert_eq!(
+                SnbtFormatter::new()
+                    .with_sorted_keys(true)
+                    .with_target_version(SnbtVersion::V1_21_5)
+                    .format(value),
+                "{components:{'minecraft:custom_name':{bold:1b,text:'Sword'},".to_owned()
+                    + "'minecraft:lore':['plain',{text:'quoted'}]}}"
+            );
+        }
+
+        // only keys the game reads text components from are converted
+        let elsewhere = nbt! {
+            CustomName: "Bob",
+            display: { Name: "Axe", Lore: ["a"] },
+            front_text: { messages: ["hi"] },
+            lore: ["kept"],
+            components: {
+                "minecraft:custom_data": { lore: ["hi"], messages: ["a"], CustomName: "Bob" }
+            }
+        };
+        assert_eq!(
+            legacy.format(&elsewhere),
+            r#"{CustomName:'"Bob"',display:{Name:'"Axe"',Lore:['"a"']},"#.to_owned()
+                + r#"front_text:{messages:['"hi"']},lore:['kept'],"#
+                + "components:{'minecraft:custom_data':{lore:['hi'],messages:['a'],CustomName:'Bob'}}}"
+        );
+        let quoted = nbt! { CustomName: "\"Quoted Name\"" };
+        assert_eq!(modern.format(&quoted), r#"{CustomName:'"Quoted Name"'}"#);
+        assert_eq!(legacy.format(&quoted), r#"{CustomName:'"Quoted Name"'}"#);
+    }
+
+    #[test]
+    pub fn test_compound_order() {
+        #[derive(serde::Serialize)]
+        struct Item {
+            zeta: i32,
+            alpha: i32,
+            middle: i32,
+        }
+
+        let value = ser::to_nbt(&Item {
+            zeta: 1,
+            alpha: 2,
+            middle: 3,
+        })
+        .unwrap();
+        assert_eq!(snbt::to_string(&value), "{zeta:1,alpha:2,middle:3}");
+
+        let value = nbt! { zeta: 1, alpha: { y: 2, b: 3 }, middle: 4 };
+        let snbt = snbt::to_string(&value);
+        assert_eq!(snbt, "{zeta:1,alpha:{y:2,b:3},middle:4}");
+        assert_eq!(snbt::to_string(&snbt::from_str(&snbt).unwrap()), snbt);
+
+        let bytes = {
+            let mut out = Vec::new();
+            stream::write_value(&mut out, &value, &stream::NbtOptions::new()).unwrap();
+            out
+        };
+        let (_, read) = stream::read_value(bytes.as_slice(), &stream::NbtOptions::new()).unwrap();
+        assert_eq!(snbt::to_string(&read), snbt);
+    }
+
+    #[test]
+    pub fn test_ser_errors() {
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Line {
+            Text(&'static str),
+            Number(i32),
+        }
+
+        #[derive(Serialize)]
+        #[allow(non_snake_case)]
+        struct Display {
+            Lore: Vec<Line>,
+        }
+
+        #[derive(Serialize)]
+        struct Tag {
+            display: Display,
+        }
+
+        #[derive(Serialize)]
+        struct Item {
+            tag: Tag,
+        }
+
+        let item = Item {
+            tag: Tag {
+                display: Display {
+                    Lore: vec![Line::Text("a"), Line::Text("b"), Line::Number(3)],
+                },
+            },
+        };
+        let err = ser::to_nbt(&item).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ser::SerErrorKind::HeterogeneousList {
+                expected: "TAG_String",
+                found: "TAG_Int"
+            }
+        );
+        assert_eq!(err.path_string(), "tag.display.Lore[2]");
+        let err = stream::to_bytes(&item, &stream::NbtOptions::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can not insert TAG_Int into a list of TAG_String at `tag.display.Lore[2]`"
+        );
+        assert_eq!(
+            err.to_string(),
+            "Can not insert TAG_Int into a list of TAG_String at `tag.display.Lore[2]`"
+        );
+
+        let err = ser::to_nbt(&vec![vec![Line::Number(1), Line::Text("a")]]).unwrap_err();
+        assert_eq!(err.path_string(), "[0][1]");
+
+        let err = ser::to_nbt(&std::collections::BTreeMap::from([(1, 2)])).unwrap_err();
+        assert_eq!(err.kind, ser::SerErrorKind::InvalidKey);
+
+        let err = ser::to_nbt(&None::<i32>).unwrap_err();
+        assert_eq!(err.kind, ser::SerErrorKind::AbsentRoot);
+
+        assert_eq!(
+            ser::to_nbt(&Vec::<i32>::new()).unwrap(),
+            Value::List(Vec::new())
+        );
+
+        let values = std::collections::BTreeMap::from([("v", vec![Some(1), None, Some(2)])]);
+        let err = ser::to_nbt(&values).unwrap_err();
+        assert_eq!(err.kind, ser::SerErrorKind::AbsentElement);
+        assert_eq!(err.path_string(), "v[1]");
+        let err = stream::to_bytes(&values, &stream::NbtOptions::new()).unwrap_err();
+        let stream::StreamError::Ser(err) = err else {
+            panic!("{err}")
+        };
+        assert_eq!(err.kind, ser::SerErrorKind::AbsentElement);
+        assert_eq!(err.path_string(), "v[1]");
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        enum Slot {
+            Item(Option<i32>),
+            Empty(()),
+        }
+
+        for (slot, expected) in [
+            (Slot::Item(Some(1)), nbt! { v: { Item: 1 } }),
+            (Slot::Item(None), nbt! { v: "Item" }),
+            (Slot::Empty(()), nbt! { v: "Empty" }),
+        ] {
+            let slot = std::collections::BTreeMap::from([("v", slot)]);
+            let nbt = ser::to_nbt(&slot).unwrap();
+            assert_eq!(nbt, expected);
+            let read: std::collections::BTreeMap<String, Slot> = de::from_nbt(&nbt).unwrap();
+            assert_eq!(read["v"], slot["v"]);
+            let bytes = stream::to_bytes(&slot, &stream::NbtOptions::new()).unwrap();
+            assert_eq!(
+                stream::read_value(bytes.as_slice(), &stream::NbtOptions::new())
+                    .unwrap()
+                    .1,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_ser_flatten() {
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        struct Inner {
+            b: ByteArray,
+            c: Option<i32>,
+        }
+
+        #[derive(Serialize)]
+        struct Outer {
+            a: LongArray,
+            #[serde(flatten)]
+            inner: Inner,
+            #[serde(with = "array::int_array")]
+            d: Vec<i32>,
+            e: Vec<i32>,
+        }
+
+        let value = ser::to_nbt(&Outer {
+            a: vec![1, 2].into(),
+            inner: Inner {
+                b: vec![3].into(),
+                c: None,
+            },
+            d: vec![4, 5],
+            e: vec![6, 7],
+        })
+        .unwrap();
+        assert_eq!(
+            value,
+            nbt! { a: [L; 1, 2], b: [B; 3], d: [I; 4, 5], e: [6, 7] }
+        );
+    }
+
+    #[test]
+    pub fn test_array_errors() {
+        #[derive(serde::Serialize)]
+        struct Colors {
+            #[serde(with = "array::int_array")]
+            colors: Vec<i8>,
+            #[serde(with = "array::long_array")]
+            single: i64,
+        }
+
+        let colors = Colors {
+            colors: vec![1],
+            single: 2,
+        };
+        let err = ser::to_nbt(&colors).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ser::SerErrorKind::InvalidArrayElement {
+                array: "TAG_Int_Array",
+                found: "TAG_Byte"
+            }
+        );
+        assert_eq!(err.path_string(), "colors[0]");
+        let err = stream::to_bytes(&colors, &stream::NbtOptions::new()).unwrap_err();
+        let stream::StreamError::Ser(err) = err else {
+            panic!("{err}")
+        };
+        assert!(matches!(
+            err.kind,
+            ser::SerErrorKind::InvalidArrayElement { .. }
+        ));
+        assert_eq!(err.path_string(), "colors[0]");
+
+        let err = ser::to_nbt(&Colors {
+            colors: vec![],
+            single: 2,
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can not serialize TAG_Long as TAG_Long_Array at `single`"
+        );
+    }
+
+    #[test]
+    pub fn test_value_field() {
+        use serde::{Deserialize, Serialize};
+        use stream::NbtOptions;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Item {
+            id: String,
+            #[serde(with = "array::value")]
+            tag: Value,
+        }
+
+        let item = Item {
+            id: "minecraft:firework_star".into(),
+            tag: nbt! {
+                colors: [I; 1, 2],
+                flags: [B; 1],
+                seeds: [L; 3],
+                bytes: [1b, 2b],
+                nested: [{ ids: [I; 4] }, { ids: [I; 5] }],
+                name: "star",
+            },
+        };
+        let nbt = ser::to_nbt(&item).unwrap();
+        assert_eq!(
+            nbt,
+            nbt! { id: "minecraft:firework_star", tag: (item.tag.clone()) }
+        );
+        assert_eq!(de::from_nbt::<Item>(&nbt).unwrap(), item);
+
+        let bytes = stream::to_bytes(&item, &NbtOptions::new()).unwrap();
+        assert_eq!(
+            stream::read_value(bytes.as_slice(), &NbtOptions::new())
+                .unwrap()
+                .1,
+            nbt
+        );
+        assert_eq!(
+            stream::from_reader_buffered::<_, Item>(bytes.as_slice(), &NbtOptions::new()).unwrap(),
+            item
+        );
+    }
+
+    #[test]
+    pub fn test_deserialize() {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Item {
+            id: String,
+            count: i8,
+            damage: Option<i32>,
+            unbreakable: bool,
+            colors: Vec<i32>,
+            lore: Vec<String>,
+            slot: Slot,
+            meta: Meta,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Slot {
+            MainHand,
+            Head,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(untagged)]
+        enum Meta {
+            Book { pages: Vec<String> },
+            Map { map: u32 },
+        }
+
+        let item = Item {
+            id: "minecraft:stone".to_owned(),
+            count: 3,
+            damage: None,
+            unbreakable: true,
+            colors: vec![1, 2, 3],
+            lore: vec!["first".to_owned(), "second".to_owned()],
+            slot: Slot::Head,
+            meta: Meta::Map { map: 7 },
+        };
+        let value = ser::to_nbt(&item).unwrap();
+        assert_eq!(de::from_nbt::<Item>(&value).unwrap(), item);
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Tagged {
+            Unit,
+            Newtype(i64),
+            Tuple(i32, i32),
+            Struct { a: i16 },
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Tags {
+            tags: Vec<Tagged>,
+            single: Tagged,
+        }
+
+        let tags = Tags {
+            tags: vec![
+                Tagged::Newtype(5),
+                Tagged::Tuple(1, 2),
+                Tagged::Struct { a: 2 },
+            ],
+            single: Tagged::Unit,
+        };
+        let value = ser::to_nbt(&tags).unwrap();
+        assert_eq!(
+            value,
+            nbt! {
+                tags: [{ Newtype: 5L }, { Tuple: [1, 2] }, { Struct: { a: 2s } }],
+                single: "Unit"
+            }
+        );
+        assert_eq!(de::from_nbt::<Tags>(&value).unwrap(), tags);
+        let bytes = stream::to_bytes(&tags, &Default::default()).unwrap();
+        assert_eq!(
+            stream::read_value(bytes.as_slice(), &Default::default())
+                .unwrap()
+                .1,
+            value
+        );
+        let newtype = ser::to_nbt(&Tagged::Newtype(5)).unwrap();
+        assert_eq!(
+            de::from_nbt::<Tagged>(&newtype).unwrap(),
+            Tagged::Newtype(5)
+        );
+        let bytes = stream::to_bytes(&Tagged::Struct { a: 2 }, &Default::default()).unwrap();
+        assert_eq!(
+            stream::from_reader_buffered::<_, Tagged>(bytes.as_slice(), &Default::default())
+                .unwrap(),
+            Tagged::Struct { a: 2 }
+        );
+
+        let err = de::from_nbt::<Item>(&nbt! { Id: 5 }).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a string, found TAG_Int");
+        let err = de::from_nbt::<String>(&Value::IntArray(vec![1])).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a string, found TAG_Int_Array");
+    }
+
+    #[test]
+    pub fn test_stream() {
+        use serde::{Deserialize, Serialize};
+        use stream::{Compression, NbtOptions};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Structure {
+            #[serde(rename = "DataVersion")]
+            data_version: i32,
+            size: IntArray,
+            name: Option<String>,
+            blocks: Vec<Block>,
+            empty: Vec<String>,
+            #[serde(with = "array::byte_array")]
+            flags: Vec<bool>,
+            lengths: LongArray,
+            list: NbtList<i64>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Block {
+            pos: (i16, i16, i16),
+            state: u8,
+        }
+
+        let structure = Structure {
+            data_version: 3120,
+            size: vec![2, 1, 1].into(),
+            name: None,
+            blocks: vec![
+                Block {
+                    pos: (0, 0, 0),
+                    state: 1,
+                },
+                Block {
+                    pos: (1, 0, 0),
+                    state: 200,
+                },
+            ],
+            empty: vec![],
+            flags: vec![true, false],
+            lengths: vec![1, 2].into(),
+            list: vec![3].into(),
+        };
+        let options = NbtOptions::new().with_root_name("structure");
+        let bytes = stream::to_bytes(&structure, &options).unwrap();
+        let (name, value) = stream::read_value(bytes.as_slice(), &options).unwrap();
+        assert_eq!(name, "structure");
+        let expected = nbt! {
+            DataVersion: 3120,
+            size: [I; 2, 1, 1],
+            blocks: [
+                { pos: [0i16, 0i16, 0i16], state: 1i8 },
+                { pos: [1i16, 0i16, 0i16], state: (Value::Byte(-56)) }
+            ],
+            empty: [],
+            flags: [B; 1, 0],
+            lengths: [L; 1, 2],
+            list: [3i64],
+        };
+        assert_eq!(value, expected);
+
+        let mut written = Vec::new();
+        stream::write_value(&mut written, &nbt! { a: [I; 1, 2], b: "c" }, &options).unwrap();
+        let (_, value) = stream::read_value(written.as_slice(), &options).unwrap();
+        assert_eq!(value, nbt! { a: [I; 1, 2], b: "c" });
+
+        let options = options.with_compression(Compression::Gzip);
+        let bytes = stream::to_bytes(&structure, &options).unwrap();
+        assert_eq!(&bytes[..2], &[0x1f, 0x8b]);
+        let read: Structure = stream::from_reader_buffered(bytes.as_slice(), &options).unwrap();
+        assert_eq!(read, structure);
+
+        let err = stream::to_bytes(&Option::<i32>::None, &options).unwrap_err();
+        assert!(matches!(err, stream::StreamError::InvalidRoot(_)));
+    }
+
+    #[test]
+    pub fn test_pull_parser() {
+        use stream::{Encoding, Event, NbtOptions, NbtReader};
+
+        let value = nbt! {
+            skipped: { list: [[1, 2], [3]], longs: [L; 1, 2], name: "abc" },
+            list: [1i16, 2i16],
+            ints: [I; 7],
+            after: 5i64,
+        };
+        for encoding in [
+            Encoding::Java,
+            Encoding::BedrockLittleEndian,
+            Encoding::BedrockNetwork,
+        ] {
+            let options = NbtOptions::new()
+                .with_root_name("root")
+                .with_encoding(encoding);
+            let mut bytes = Vec::new();
+            stream::write_value(&mut bytes, &value, &options).unwrap();
+
+            let mut reader = NbtReader::new(bytes.as_slice(), encoding);
+            let name = |name: &str| Some(name.to_owned());
+            assert_eq!(
+                reader.next_event().unwrap(),
+                Some(Event::StartCompound(name("root")))
+            );
+            assert_eq!(
+                reader.next_event().unwrap(),
+                Some(Event::StartCompound(name("skipped")))
+            );
+            reader.skip().unwrap();
+            assert_eq!(
+                reader.next_event().unwrap(),
+                Some(Event::StartList(name("list"), 2, 2))
+            );
+            assert_eq!(reader.next_event().unwrap(), Some(Event::Short(None, 1)));
+            assert_eq!(reader.depth(), 2);
+            reader.skip().unwrap();
+            assert_eq!(
+                reader.next_event().unwrap(),
+                Some(Event::IntArray(name("ints"), vec![7]))
+            );
+            assert_eq!(
+                reader.read_value().unwrap(),
+                Some((name("after"), Value::Long(5)))
+            );
+            assert_eq!(reader.next_event().unwrap(), Some(Event::End));
+            assert_eq!(reader.next_event().unwrap(), None);
+
+            let (root, read) = stream::read_value(bytes.as_slice(), &options).unwrap();
+            assert_eq!(root, "root");
+            assert_eq!(read, value);
+        }
+    }
+
+    #[test]
+    pub fn test_bedrock_encodings() {
+        use serde::{Deserialize, Serialize};
+        use stream::{Encoding, NbtOptions};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Simple {
+            x: i32,
+            name: String,
+            longs: Vec<i64>,
+        }
+
+        let value = nbt! { a: 1 };
+        let mut little_endian = Vec::new();
+        let options = NbtOptions::new().with_encoding(Encoding::BedrockLittleEndian);
+        stream::write_value(&mut little_endian, &value, &options).unwrap();
+        assert_eq!(little_endian, [10, 0, 0, 3, 1, 0, b'a', 1, 0, 0, 0, 0]);
+
+        let mut network = Vec::new();
+        let options = NbtOptions::new().with_encoding(Encoding::BedrockNetwork);
+        stream::write_value(&mut network, &value, &options).unwrap();
+        assert_eq!(network, [10, 0, 3, 1, b'a', 2, 0]);
+
+        let value = nbt! {
+            int: (Value::Int(-300)),
+            long: (Value::Long(i64::MIN)),
+            short: 5i16,
+            string: "Hello",
+            list: [{ a: 1.5f32 }, { a: 2.5f32 }],
+            ints: [I; 1, (-1), (i32::MAX)],
+            longs: [L; (-5)],
+            bytes: [B; 1, 2],
+        };
+        for encoding in [
+            Encoding::Java,
+            Encoding::BedrockLittleEndian,
+            Encoding::BedrockNetwork,
+        ] {
+            let options = NbtOptions::new()
+                .with_encoding(encoding)
+                .with_root_name("root");
+            let mut bytes = Vec::new();
+            stream::write_value(&mut bytes, &value, &options).unwrap();
+            let (name, read) = stream::read_value(bytes.as_slice(), &options).unwrap();
+            assert_eq!(name, "root");
+            assert_eq!(read, value);
+            let simple = Simple {
+                x: -7,
+                name: "a".to_owned(),
+                longs: vec![i64::MAX],
+            };
+            let bytes = stream::to_bytes(&simple, &options).unwrap();
+            assert_eq!(
+                stream::from_reader_buffered::<_, Simple>(bytes.as_slice(), &options).unwrap(),
+                simple
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_nameless_root() {
+        use stream::{Encoding, NbtOptions};
+
+        // the root name is ignored, as it is not part of the encoding
+        let options = NbtOptions::new()
+            .with_encoding(Encoding::JavaNetwork)
+            .with_root_name("ignored");
+        let text = stream::to_bytes(&"Hello", &options).unwrap();
+        assert_eq!(text, [8, 0, 5, b'H', b'e', b'l', b'l', b'o']);
+        let (name, value) = stream::read_value(text.as_slice(), &options).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(value, Value::String("Hello".to_owned()));
+
+        let mut cmp = Vec::new();
+        stream::write_value(&mut cmp, &nbt! { a: 1 }, &options).unwrap();
+        assert_eq!(cmp, [10, 3, 0, 1, b'a', 0, 0, 0, 1, 0]);
+
+        let list = stream::to_bytes(&vec!["a", "b"], &options).unwrap();
+        assert_eq!(list, [9, 8, 0, 0, 0, 2, 0, 1, b'a', 0, 1, b'b']);
+        #[derive(serde::Serialize)]
+        struct Ints(#[serde(with = "array::int_array")] std::collections::BTreeSet<i32>);
+
+        let ints = stream::to_bytes(&Ints((1..3).collect()), &options).unwrap();
+        assert_eq!(ints, [11, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2]);
+        let (_, value) = stream::read_value(ints.as_slice(), &options).unwrap();
+        assert_eq!(value, Value::IntArray(vec![1, 2]));
+    }
+
+    #[test]
+    pub fn test_nbt_path() {
+        use path::PathError;
+
+        let get = |value: &Value, path: &str| {
+            NbtPath::parse(path)
+                .unwrap()
+                .get(value)
+                .unwrap()
+                .into_owned()
+        };
+        let mut player = nbt! {
+            Inventory: [
+                { Slot: (Value::Byte(0)), id: "minecraft:stone", Count: (Value::Byte(64)) },
+                { Slot: (Value::Byte(1)), id: "minecraft:dirt", Count: (Value::Byte(3)) }
+            ],
+            Pos: [1.5, 64.0, (Value::Double(-3.5))],
+            UUID: [I; 1, 2, 3, 4],
+            "odd key": { tags: ["a", "b"] }
+        };
+
+        let path: NbtPath = "Inventory[{Slot:1b}].id".parse().unwrap();
+        assert_eq!(path.to_string(), "Inventory[{Slot:1b}].id");
+        assert_eq!(
+            *path.get(&player).unwrap(),
+            Value::String("minecraft:dirt".to_owned())
+        );
+        let path = NbtPath::parse("Pos[-1]").unwrap();
+        assert_eq!(*path.get(&player).unwrap(), Value::Double(-3.5));
+        let path = NbtPath::parse("UUID[2]").unwrap();
+        assert_eq!(*path.get(&player).unwrap(), Value::Int(3));
+        let path = NbtPath::parse("\"odd key\".tags[]").unwrap();
+        assert_eq!(path.to_string(), "\"odd key\".tags[]");
+        assert_eq!(path.get_all(&player).len(), 2);
+        assert_eq!(path.get(&player), Err(PathError::MultipleMatches(2)));
+        let path = NbtPath::parse("{Pos:[64.0d]}.Inventory[0].Count").unwrap();
+        assert_eq!(*path.get(&player).unwrap(), Value::Byte(64));
+        let path = NbtPath::parse("Inventory[{id:\"minecraft:air\"}]").unwrap();
+        assert!(matches!(path.get(&player), Err(PathError::NotFound(_))));
+
+        // writes follow the rules of the /data command
+        let path = NbtPath::parse("Inventory[].Count").unwrap();
+        assert_eq!(path.set(&mut player, Value::Byte(3)), 1);
+        assert_eq!(path.set(&mut player, Value::Byte(3)), 0);
+        let path = NbtPath::parse("UUID[0]").unwrap();
+        assert_eq!(path.set(&mut player, Value::Double(7.9)), 1);
+        assert_eq!(get(&player, "UUID"), Value::IntArray(vec![7, 2, 3, 4]));
+        let path = NbtPath::parse("Pos[]").unwrap();
+        assert_eq!(path.set(&mut player, Value::String("no".to_owned())), 0);
+        let path = NbtPath::parse("Pos").unwrap();
+        assert_eq!(path.insert(&mut player, -1, Value::Double(0.0)), 1);
+        assert_eq!(path.insert(&mut player, 0, Value::Int(0)), 0);
+        assert_eq!(
+            get(&player, "Pos"),
+            Value::List(vec![
+                Value::Double(1.5),
+                Value::Double(64.0),
+                Value::Double(-3.5),
+                Value::Double(0.0),
+            ])
+        );
+
+        let path = NbtPath::parse("tag.display.Lore[{text:\"hi\"}].bold").unwrap();
+        assert_eq!(path.set(&mut player, Value::Byte(1)), 1);
+        assert_eq!(
+            snbt::to_string(&get(&player, "tag")),
+            "{display:{Lore:[{text:'hi',bold:1b}]}}"
+        );
+        let path = NbtPath::parse("Inventory[{Slot:0b}]").unwrap();
+        assert_eq!(path.remove(&mut player), 1);
+        assert_eq!(
+            NbtPath::parse("Inventory[].id")
+                .unwrap()
+                .get_all(&player)
+                .len(),
+            1
+        );
+        assert_eq!(NbtPath::parse("tag").unwrap().remove(&mut player), 1);
+        let Value::Compound(cmp) = &player else {
+            unreachable!()
+        };
+        assert_eq!(
+            cmp.keys().collect::<Vec<_>>(),
+            ["Inventory", "Pos", "UUID", "odd key"]
+        );
+
+        assert_eq!(
+            NbtPath::parse("a..b"),
+            Err(PathError::Expected {
+                expected: "a key",
+                column: 3
+            })
+        );
+        assert_eq!(
+            NbtPath::parse("a[x]"),
+            Err(PathError::Expected {
+                expected: "an index, `{` or `]`",
+                column: 3
+            })
+        );
+        assert!(matches!(
+            NbtPath::parse("a{b:}"),
+            Err(PathError::Filter { column: 2, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_patch() {
+        use patch::{PatchEntry, PatchError};
+
+        let base = nbt! {
+            id: "minecraft:bow",
+            tag: {
+                display: { Name: "Bow", Lore: ["a", "b", "c"] },
+                Enchantments: [{ id: "power", lvl: 1 }]
+            }
+        };
+        let mut item = base.clone();
+        patch::merge(
+            &mut item,
+            &nbt! { tag: { display: { Lore: ["z"] }, Enchantments: [{ id: "power", lvl: 5 }], Damage: 3 } },
+        );
+        assert_eq!(
+            snbt::to_string(&item),
+            "{id:'minecraft:bow',tag:{display:{Name:'Bow',Lore:['z']},Enchantments:[{id:'power',lvl:5}],Damage:3}}"
+        );
+
+        let patch = patch::diff(&base, &item);
+        assert_eq!(
+            patch.to_string(),
+            "~ tag.display.Lore[0]: 'a' -> 'z'\n\
+             - tag.display.Lore[2]: 'c'\n\
+             - tag.display.Lore[1]: 'b'\n\
+             ~ tag.Enchantments[0].lvl: 1 -> 5\n\
+             + tag.Damage: 3\n"
+        );
+        let mut applied = base.clone();
+        patch.apply(&mut applied).unwrap();
+        assert_eq!(applied, item);
+        assert!(patch::diff(&item, &applied).is_empty());
+
+        // patches survive a round trip through NBT, keeping the exact value types
+        let stored = ser::to_nbt(&patch).unwrap();
+        let restored: NbtPatch = de::from_nbt(&stored).unwrap();
+        assert_eq!(restored, patch);
+        let root = patch::diff(&Value::Byte(1), &Value::Short(1));
+        let restored: NbtPatch = de::from_nbt(&ser::to_nbt(&root).unwrap()).unwrap();
+        assert_eq!(restored.entries[0].path(), &NbtPath::root());
+
+        // a diverged target is detected instead of silently overwritten
+        assert_eq!(
+            patch.apply(&mut item),
+            Err(PatchError {
+                path: NbtPath::parse("tag.display.Lore[0]").unwrap()
+            })
+        );
+        let add = PatchEntry::Add {
+            path: NbtPath::root().key("id"),
+            value: Value::Int(1),
+        };
+        assert!(NbtPatch { entries: vec![add] }.apply(&mut item).is_err());
+
+        // values SNBT can not express exactly are stored the way the formatter writes them
+        let base = nbt! { id: "minecraft:arrow", data: { speed: 1f } };
+        let new = nbt! {
+            id: "minecraft:arrow",
+            data: { speed: (f32::NAN), limit: (f64::INFINITY) },
+            mixed: [1, "a", { id: 3 }],
+        };
+        let json = serde_json::to_string(&patch::diff(&base, &new)).unwrap();
+        let restored: NbtPatch = serde_json::from_str(&json).unwrap();
+        let mut applied = base.clone();
+        restored.apply(&mut applied).unwrap();
+        assert_eq!(
+            applied,
+            nbt! {
+                id: "minecraft:arrow",
+                data: { speed: 0f, limit: (f64::MAX) },
+                mixed: [{ "": 1 }, { "": "a" }, { id: 3 }],
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_json() {
+        use json::JsonMode;
+
+        let value = nbt! {
+            Count: (Value::Byte(3)),
+            Damage: (Value::Short(-2)),
+            Seed: (Value::Long(-4_611_686_018_427_387_905)),
+            Health: (Value::Float(19.5)),
+            Motion: [(Value::Double(f64::NAN)), (Value::Double(f64::NEG_INFINITY)), 0.25],
+            UUID: [I; 1, 2, 3, 4],
+            Bytes: (Value::ByteArray(vec![-1])),
+            Longs: [L; 7],
+            Empty: [],
+            Name: "Steve",
+            Tags: { z: 1, a: 2 }
+        };
+        let typed = json::to_string(&value, JsonMode::Typed);
+        assert_eq!(
+            typed,
+            r#"{"compound":{"Count":{"byte":3},"Damage":{"short":-2},"Seed":{"long":"-4611686018427387905"},"#.to_owned()
+                + r#""Health":{"float":19.5},"Motion":{"list":[{"double":"0x7ff8000000000000"},{"double":"0xfff0000000000000"},{"double":0.25}]},"#
+                + r#""UUID":{"int_array":[1,2,3,4]},"Bytes":{"byte_array":[-1]},"Longs":{"long_array":["7"]},"#
+                + r#""Empty":{"list":[]},"Name":{"string":"Steve"},"Tags":{"compound":{"z":{"int":1},"a":{"int":2}}}}}"#
+        );
+        let parsed = json::from_str(&typed, JsonMode::Typed).unwrap();
+        let mut bytes = Vec::new();
+        let mut round_trip = Vec::new();
+        let options = stream::NbtOptions::new();
+        stream::write_value(&mut bytes, &value, &options).unwrap();
+        stream::write_value(&mut round_trip, &parsed, &options).unwrap();
+        assert_eq!(bytes, round_trip);
+        // `serde_json::Value` sorts keys by default, and NaN never equals itself
+        let formatter = snbt::SnbtFormatter::pretty();
+        let from_value = json::from_json(&json::to_json(&parsed, JsonMode::Typed), JsonMode::Typed);
+        assert_eq!(
+            formatter.format(&from_value.unwrap()),
+            formatter.format(&value)
+        );
+        assert!(json::from_str(r#"{"list":[{"int":1},{"byte":1}]}"#, JsonMode::Typed).is_err());
+        assert!(json::from_str(r#"{"int":1,"byte":1}"#, JsonMode::Typed).is_err());
+        assert!(json::from_str(r#"{"short":40000}"#, JsonMode::Typed).is_err());
+        let err = json::from_str(r#"{"list":[{"int":1},{"int_array":[1]}]}"#, JsonMode::Typed)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Can not insert TAG_Int_Array into a list of TAG_Int"));
+
+        // Typed mode spends two JSON objects per level, which is deeper than serde_json allows by default
+        let nested =
+            |depth: usize| (1..depth).fold(nbt! { leaf: 1 }, |inner, _| nbt! { inner: inner });
+        for depth in [70, 512] {
+            for mode in [JsonMode::Typed, JsonMode::Loose] {
+                let value = nested(depth);
+                assert_eq!(
+                    json::from_str(&json::to_string(&value, mode), mode).unwrap(),
+                    value
+                );
+            }
+        }
+        let err = json::from_str(
+            &json::to_string(&nested(513), JsonMode::Typed),
+            JsonMode::Typed,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Nesting is deeper than the limit of 512"));
+        let bomb = r#"{"list":["#.repeat(100_000);
+        assert!(json::from_str(&bomb, JsonMode::Typed).is_err());
+        assert!(json::from_str(&"[".repeat(100_000), JsonMode::Loose).is_err());
+
+        let loose = json::to_string(
+            &nbt! { Count: (Value::Byte(3)), Pos: [1.5, 2.5] },
+            JsonMode::Loose,
+        );
+        assert_eq!(loose, r#"{"Count":3,"Pos":[1.5,2.5]}"#);
+        let inferred = json::from_str(
+            r#"{"flag":true,"small":1,"big":3000000000,"ratio":0.5,"mixed":[1,3000000000,0.5],"ints":[1,2]}"#,
+            JsonMode::Loose,
+        )
+        .unwrap();
+        assert_eq!(
+            snbt::to_string(&inferred),
+            "{flag:1b,small:1,big:3000000000L,ratio:0.5d,mixed:[1d,3e9d,0.5d],ints:[1,2]}"
+        );
+        assert!(json::from_str(r#"[1, "a"]"#, JsonMode::Loose).is_err());
+        assert!(json::from_str(r#"{"a":null}"#, JsonMode::Loose).is_err());
+    }
+
+    #[test]
+    pub fn test_mutf8() {
+        use crate::stream::{self, Encoding, NbtOptions, StreamError};
+
+        let options = NbtOptions::new().with_encoding(Encoding::JavaNetwork);
+        let mut bytes = Vec::new();
+        stream::write_value(
+            &mut bytes,
+            &Value::String("\0\u{1F600}é".to_owned()),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            bytes,
+            [8, 0, 10, 0xc0, 0x80, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, 0xc3, 0xa9]
+        );
+        let (_, read) = stream::read_value(bytes.as_slice(), &options).unwrap();
+        assert_eq!(read, Value::String("\0\u{1F600}é".to_owned()));
+
+        // Plain UTF-8 from other tools is read as well
+        let plain = [8, 0, 5, b'a', 0xf0, 0x9f, 0x98, 0x80];
+        let (_, read) = stream::read_value(plain.as_slice(), &options).unwrap();
+        assert_eq!(read, Value::String("a\u{1F600}".to_owned()));
+        let unpaired = [8, 0, 3, 0xed, 0xa0, 0xbd];
+        assert!(matches!(
+            stream::read_value(unpaired.as_slice(), &options),
+            Err(StreamError::InvalidString)
+        ));
+
+        // Bedrock Edition uses plain UTF-8
+        let options = NbtOptions::new().with_encoding(Encoding::BedrockLittleEndian);
+        let mut bytes = Vec::new();
+        stream::write_value(&mut bytes, &nbt! { a: "\u{1F600}" }, &options).unwrap();
+        assert!(bytes.ends_with(&[4, 0, 0xf0, 0x9f, 0x98, 0x80, 0]));
+
+        let options = NbtOptions::new();
+        let mut bytes = Vec::new();
+        let fits = "a".repeat(65535);
+        stream::write_value(&mut bytes, &nbt! { s: (fits) }, &options).unwrap();
+        let long = "\u{1F600}".repeat(11000);
+        let err = stream::write_value(Vec::new(), &nbt! { s: (long) }, &options).unwrap_err();
+        assert!(matches!(err, StreamError::StringTooLong(66000)));
+        assert_eq!(
+            err.to_string(),
+            "String of 66000 encoded bytes is longer than the limit of 65535 bytes"
+        );
+    }
+
+    #[test]
+    pub fn test_read_limits() {
+        use crate::stream::{self, Compression, NbtOptions, NbtReader, ReadLimits, StreamError};
+
+        // In Java encoding, every byte of the input is accounted
+        let value = nbt! { a: [I; 1, 2], b: [{ c: "d" }], e: [L; 3], f: 1.5, g: [B; 1] };
+        let mut bytes = Vec::new();
+        stream::write_value(&mut bytes, &value, &NbtOptions::new()).unwrap();
+        let mut reader = NbtReader::new(bytes.as_slice(), Default::default());
+        reader.read_value().unwrap();
+        assert_eq!(reader.accounted(), bytes.len() as u64);
+
+        // Lists nested `depth` times
+        let nested = |depth: usize| {
+            let mut bytes = vec![9, 0, 0];
+            for _ in 1..depth {
+                bytes.extend_from_slice(&[9, 0, 0, 0, 1]);
+            }
+            bytes.extend_from_slice(&[0, 0, 0, 0, 0]);
+            bytes
+        };
+        assert!(stream::read_value(nested(512).as_slice(), &NbtOptions::new()).is_ok());
+        let bomb = nested(100_000);
+        assert!(matches!(
+            stream::read_value(bomb.as_slice(), &NbtOptions::new()),
+            Err(StreamError::DepthLimitExceeded(512))
+        ));
+        let mut reader = NbtReader::new(bomb.as_slice(), Default::default());
+        reader.next_event().unwrap();
+        assert!(matches!(
+            reader.skip(),
+            Err(StreamError::DepthLimitExceeded(512))
+        ));
+        let shallow = NbtOptions::new().with_limits(ReadLimits::new().with_max_depth(8));
+        assert!(stream::read_value(nested(8).as_slice(), &shallow).is_ok());
+        assert!(matches!(
+            stream::read_value(nested(9).as_slice(), &shallow),
+            Err(StreamError::DepthLimitExceeded(8))
+        ));
+
+        // A byte array declaring 2 GiB without containing them
+        let declared = [7, 0, 0, 0x7f, 0xff, 0xff, 0xff, 1, 2, 3];
+        let short = NbtOptions::new().with_limits(ReadLimits::new().with_max_len(1024));
+        let err = stream::read_value(declared.as_slice(), &short).unwrap_err();
+        assert!(matches!(
+            err,
+            StreamError::LengthLimitExceeded {
+                len: 0x7fffffff,
+                limit: 1024
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Length 2147483647 is larger than the limit of 1024"
+        );
+        assert!(matches!(
+            stream::read_value(declared.as_slice(), &NbtOptions::new()),
+            Err(StreamError::Io(_))
+        ));
+
+        // A megabyte of zeros compresses to a few kilobytes
+        let gzip = NbtOptions::new().with_compression(Compression::Gzip);
+        let mut bytes = Vec::new();
+        let zeros = Value::ByteArray(vec![0; 1 << 20]);
+        stream::write_value(&mut bytes, &nbt! { zeros: zeros }, &gzip).unwrap();
+        let limited = gzip.with_limits(ReadLimits::new().with_max_bytes(1 << 16));
+        assert!(matches!(
+            stream::read_value(bytes.as_slice(), &limited),
+            Err(StreamError::SizeLimitExceeded(65536))
+        ));
+
+        // SNBT is read within the same limits
+        let kind = |snbt: &str, limits: ReadLimits| {
+            snbt::from_str_with_limits(snbt, limits).unwrap_err().kind
+        };
+        let shallow = ReadLimits::new().with_max_depth(2);
+        assert!(snbt::from_str_with_limits("{a:[1]}", shallow).is_ok());
+        assert_eq!(
+            kind("{a:[{}]}", shallow),
+            snbt::SnbtErrorKind::DepthLimitExceeded(2)
+        );
+        let short = ReadLimits::new().with_max_len(2);
+        assert!(snbt::from_str_with_limits("[[I;1,2],[I;3]]", short).is_ok());
+        let err = snbt::from_str_with_limits("[B;1b,2b,3b]", short).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Length is larger than the limit of 2 at line 1, column 10"
+        );
+        assert_eq!(
+            kind("[a,b,c]", short),
+            snbt::SnbtErrorKind::LengthLimitExceeded(2)
+        );
+        assert_eq!(
+            kind("{a:'bcd'}", ReadLimits::new().with_max_bytes(8)),
+            snbt::SnbtErrorKind::SizeLimitExceeded(8)
+        );
+        assert_eq!(
+            kind(&"[".repeat(513), ReadLimits::default()),
+            snbt::SnbtErrorKind::DepthLimitExceeded(512)
+        );
+    }
 }
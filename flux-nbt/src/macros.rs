@@ -1,66 +1,149 @@
-use nbt::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use nbt::{Map, Value};
+use thiserror::Error;
 use uuid::Uuid;
 
+use crate::{
+    ser::{At, PathSegment},
+    stream::tag,
+    ByteArray, IntArray, LongArray, NbtList,
+};
+
+/// Builds a compound [`Value`] with an SNBT-like syntax.
+///
+/// ```
+/// # use flux_nbt::{nbt, Value};
+/// let name = "Steve";
+/// let base = nbt! { Health: 20.0f };
+/// let team: Option<&str> = None;
+/// let player = nbt! {
+///     Name: name,
+///     "UUID": [I; 1, 2, 3, -4],
+///     Inventory: [{ Slot: 0B, Count: 64b, "id": "minecraft:stone" }],
+///     [format!("Score{}", 1)]: 300L,
+///     Motion: [0.0d, -1.5d, 0.0d],
+///     Level: (name.len() as i32),
+///     Team?: team,
+///     ..base
+/// };
+/// ```
+///
+/// Keys are identifiers, string literals or `[expr]`. Values are literals, variables,
+/// `(expr)`, compounds, lists and arrays, where any variable and expression is converted with
+/// the [`Nbt`] trait. Number literals take the SNBT suffixes `b`, `s`, `L`, `f` and `d` as well
+/// as the Rust suffixes of the matching types. `key?: value` only inserts the entry if the
+/// variable or `(expr)` is `Some`, and `..expr` copies all entries of another compound.
+///
+/// Rust lexes `0b` as a binary literal without digits before any macro sees it, so a zero
+/// byte has to be written `0B` or `0i8` instead:
+///
+/// ```compile_fail
+/// # use flux_nbt::nbt;
+/// let slot = nbt! { Slot: 0b };
+/// ```
 #[macro_export]
 macro_rules! nbt {
-    (
-        $($k:tt: $v:tt),* $(,)*
-    ) => {{
-        #[allow(unused_imports)]
-        use $crate::Nbt;
-        $crate::Value::Compound(std::collections::HashMap::<String, $crate::Value>::from([
-            $(
-            ($crate::__nbt_key!($k), $crate::__nbt_val!($v)),
-            )*
-        ]))
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut map = $crate::Map::<::std::string::String, $crate::Value>::new();
+        $crate::__nbt_entries!(map; $($body)*);
+        $crate::Value::Compound(map)
     }};
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __nbt_entries {
+    ($map:ident;) => {};
+    ($map:ident; , $($rest:tt)*) => {
+        $crate::__nbt_entries!($map; $($rest)*);
+    };
+    ($map:ident; .. $spread:expr $(, $($rest:tt)*)?) => {
+        $crate::macros::__private::spread(&mut $map, $crate::Nbt::nbt(&$spread));
+        $crate::__nbt_entries!($map; $($($rest)*)?);
+    };
+    ($map:ident; $k:tt ?: $v:tt $(, $($rest:tt)*)?) => {
+        if let ::core::option::Option::Some(value) = &$v {
+            $map.insert($crate::__nbt_key!($k), $crate::Nbt::nbt(value));
+        }
+        $crate::__nbt_entries!($map; $($($rest)*)?);
+    };
+    ($map:ident; $k:tt : - $v:literal $(, $($rest:tt)*)?) => {
+        $map.insert($crate::__nbt_key!($k), $crate::__nbt_val!(- $v));
+        $crate::__nbt_entries!($map; $($($rest)*)?);
+    };
+    ($map:ident; $k:tt : $v:tt $(, $($rest:tt)*)?) => {
+        $map.insert($crate::__nbt_key!($k), $crate::__nbt_val!($v));
+        $crate::__nbt_entries!($map; $($($rest)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __nbt_elements {
+    ($list:ident;) => {};
+    ($list:ident; - $v:literal $(, $($rest:tt)*)?) => {
+        $list.push($crate::__nbt_val!(- $v));
+        $crate::__nbt_elements!($list; $($($rest)*)?);
+    };
+    ($list:ident; $v:tt $(, $($rest:tt)*)?) => {
+        $list.push($crate::__nbt_val!($v));
+        $crate::__nbt_elements!($list; $($($rest)*)?);
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __nbt_key {
     ($str:literal) => {
-        $str.to_string()
+        ::std::string::String::from($str)
     };
-    ([$var:ident]) => {
-        $var.to_owned()
+    ([$key:expr]) => {
+        ::std::string::ToString::to_string(&$key)
     };
     ($name:ident) => {
-        stringify!($name).to_owned()
+        ::std::string::String::from(stringify!($name))
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __nbt_val {
-    ({
-        $(
-            $k:ident: $v:tt
-        ),* $(,)*
-    }) => {
-        nbt!($($k:$v,)*)
+    ({ $($body:tt)* }) => {
+        $crate::nbt! { $($body)* }
     };
-    ($var:ident) => {
-        $var.nbt()
-    };
-    ($lit:literal) => {
-        $lit.nbt()
+    ([B; $($element:expr),* $(,)?]) => {
+        $crate::Value::ByteArray(::std::vec![$($element),*])
     };
-    ([$($ele:tt),* $(,)*]) => {
-        $crate::Value::List(vec![$($crate::__nbt_val!($ele),)*])
+    ([I; $($element:expr),* $(,)?]) => {
+        $crate::Value::IntArray(::std::vec![$($element),*])
     };
-    ([I;$($ele:tt),* $(,)*]) => {
-        $crate::Value::IntArray(vec![$($ele,)*])
+    ([L; $($element:expr),* $(,)?]) => {
+        $crate::Value::LongArray(::std::vec![$($element),*])
     };
-    ([L;$($ele:tt),* $(,)*]) => {
-        $crate::Value::LongArray(vec![$($ele,)*])
+    ([$($elements:tt)*]) => {{
+        #[allow(unused_mut)]
+        let mut list = ::std::vec::Vec::new();
+        $crate::__nbt_elements!(list; $($elements)*);
+        $crate::Value::List(list)
+    }};
+    (- $lit:literal) => {{
+        const LITERAL: $crate::macros::__private::Literal =
+            $crate::macros::__private::Literal::parse(concat!("-", stringify!($lit)));
+        LITERAL.value(concat!("-", stringify!($lit)))
+    }};
+    ($lit:literal) => {{
+        const LITERAL: $crate::macros::__private::Literal =
+            $crate::macros::__private::Literal::parse(stringify!($lit));
+        LITERAL.value(stringify!($lit))
+    }};
+    ($var:ident) => {
+        $crate::Nbt::nbt(&$var)
     };
-    ([B;$($ele:tt),* $(,)*]) => {
-        $crate::Value::ByteArray(vec![$($ele,)*])
+    (($value:expr)) => {
+        $crate::Nbt::nbt(&($value))
     };
-    ($($tks:tt)*) => {
-        $($tks)*
-    }
 }
 
 macro_rules! existing_impls {
@@ -120,6 +203,12 @@ impl<T: Nbt> Nbt for Vec<T> {
     }
 }
 
+impl<T: Nbt, const N: usize> Nbt for [T; N] {
+    fn nbt(&self) -> Value {
+        Value::List(self.iter().map(Nbt::nbt).collect())
+    }
+}
+
 impl Nbt for Uuid {
     fn nbt(&self) -> Value {
         Value::String(self.to_string())
@@ -139,3 +228,627 @@ existing_impls!(
     f32,
     f64
 );
+
+impl Nbt for Value {
+    fn nbt(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl<T: Nbt + ?Sized> Nbt for Box<T> {
+    fn nbt(&self) -> Value {
+        (**self).nbt()
+    }
+}
+
+impl<T: Nbt> Nbt for Map<String, T> {
+    fn nbt(&self) -> Value {
+        Value::Compound(self.iter().map(|(k, v)| (k.clone(), v.nbt())).collect())
+    }
+}
+
+impl<T: Nbt> Nbt for HashMap<String, T> {
+    fn nbt(&self) -> Value {
+        Value::Compound(self.iter().map(|(k, v)| (k.clone(), v.nbt())).collect())
+    }
+}
+
+impl<T: Nbt> Nbt for BTreeMap<String, T> {
+    fn nbt(&self) -> Value {
+        Value::Compound(self.iter().map(|(k, v)| (k.clone(), v.nbt())).collect())
+    }
+}
+
+impl Nbt for ByteArray {
+    fn nbt(&self) -> Value {
+        Value::ByteArray(self.0.clone())
+    }
+}
+
+impl Nbt for IntArray {
+    fn nbt(&self) -> Value {
+        Value::IntArray(self.0.clone())
+    }
+}
+
+impl Nbt for LongArray {
+    fn nbt(&self) -> Value {
+        Value::LongArray(self.0.clone())
+    }
+}
+
+impl<T: Nbt> Nbt for NbtList<T> {
+    fn nbt(&self) -> Value {
+        self.0.nbt()
+    }
+}
+
+/// The reverse of [`Nbt`], reading a type back from a [`Value`].
+///
+/// Numbers are read from any numeric tag and cast, the way the game reads them.
+/// Can be derived with `#[derive(FromNbt)]` from flux-macros.
+pub trait FromNbt: Sized {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{kind}{}", At(.path))]
+pub struct FromNbtError {
+    /// Location of the offending value, starting at the root
+    pub path: Vec<PathSegment>,
+    pub kind: FromNbtErrorKind,
+}
+
+impl FromNbtError {
+    /// Prepends a segment to the path, used when an error bubbles up from a nested value
+    pub fn within(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    pub fn mismatch(expected: &'static str, found: &Value) -> Self {
+        FromNbtErrorKind::Mismatch {
+            expected,
+            found: tag::name(found.id()),
+        }
+        .into()
+    }
+}
+
+impl From<FromNbtErrorKind> for FromNbtError {
+    fn from(kind: FromNbtErrorKind) -> Self {
+        Self {
+            path: Vec::new(),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FromNbtErrorKind {
+    #[error("Expected {expected}, found {found}")]
+    Mismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("Missing field `{0}`")]
+    MissingField(&'static str),
+    #[error("Unknown variant `{variant}` of {ty}")]
+    UnknownVariant { ty: &'static str, variant: String },
+    #[error("Expected a variant of {0} as a string or a compound with a single key")]
+    NoMatchingVariant(&'static str),
+    #[error("{0}")]
+    Custom(String),
+}
+
+fn as_long(value: &Value) -> Option<i64> {
+    match value {
+        Value::Byte(v) => Some(*v as i64),
+        Value::Short(v) => Some(*v as i64),
+        Value::Int(v) => Some(*v as i64),
+        Value::Long(v) => Some(*v),
+        Value::Float(v) => Some(*v as i64),
+        Value::Double(v) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+fn as_double(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(v) => Some(*v as f64),
+        Value::Double(v) => Some(*v),
+        other => as_long(other).map(|v| v as f64),
+    }
+}
+
+macro_rules! from_number {
+    ($($ty:ty: $convert:ident => $expected:literal),* $(,)?) => {
+        $(
+            impl FromNbt for $ty {
+                fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+                    $convert(value)
+                        .map(|v| v as $ty)
+                        .ok_or_else(|| FromNbtError::mismatch($expected, value))
+                }
+            }
+        )*
+    };
+}
+
+from_number! {
+    i8: as_long => "TAG_Byte",
+    u8: as_long => "TAG_Byte",
+    i16: as_long => "TAG_Short",
+    u16: as_long => "TAG_Short",
+    i32: as_long => "TAG_Int",
+    u32: as_long => "TAG_Int",
+    i64: as_long => "TAG_Long",
+    u64: as_long => "TAG_Long",
+    f32: as_double => "TAG_Float",
+    f64: as_double => "TAG_Double",
+}
+
+impl FromNbt for bool {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        as_long(value)
+            .map(|v| v != 0)
+            .ok_or_else(|| FromNbtError::mismatch("TAG_Byte", value))
+    }
+}
+
+impl FromNbt for String {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        match value {
+            Value::String(st) => Ok(st.clone()),
+            other => Err(FromNbtError::mismatch("TAG_String", other)),
+        }
+    }
+}
+
+/// Reads both the string form written by [`Nbt`] and the int array form the game uses
+impl FromNbt for Uuid {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        match value {
+            Value::String(st) => {
+                Uuid::parse_str(st).map_err(|err| FromNbtErrorKind::Custom(err.to_string()).into())
+            }
+            Value::IntArray(ints) if ints.len() == 4 => {
+                let bits = ints
+                    .iter()
+                    .fold(0u128, |bits, int| bits << 32 | *int as u32 as u128);
+                Ok(Uuid::from_u128(bits))
+            }
+            other => Err(FromNbtError::mismatch("TAG_String", other)),
+        }
+    }
+}
+
+impl FromNbt for Value {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromNbt> FromNbt for Box<T> {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        T::from_nbt(value).map(Box::new)
+    }
+}
+
+impl<T: FromNbt> FromNbt for Option<T> {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        T::from_nbt(value).map(Some)
+    }
+}
+
+/// Reads lists as well as arrays
+impl<T: FromNbt> FromNbt for Vec<T> {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        fn collect<T: FromNbt, V>(
+            values: impl Iterator<Item = V>,
+            convert: impl Fn(V) -> Result<T, FromNbtError>,
+        ) -> Result<Vec<T>, FromNbtError> {
+            values
+                .enumerate()
+                .map(|(index, v)| convert(v).map_err(|err| err.within(PathSegment::Index(index))))
+                .collect()
+        }
+
+        match value {
+            Value::List(list) => collect(list.iter(), T::from_nbt),
+            Value::ByteArray(bytes) => collect(bytes.iter(), |v| T::from_nbt(&Value::Byte(*v))),
+            Value::IntArray(ints) => collect(ints.iter(), |v| T::from_nbt(&Value::Int(*v))),
+            Value::LongArray(longs) => collect(longs.iter(), |v| T::from_nbt(&Value::Long(*v))),
+            other => Err(FromNbtError::mismatch("TAG_List", other)),
+        }
+    }
+}
+
+impl<T: FromNbt, const N: usize> FromNbt for [T; N] {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        Vec::from_nbt(value)?
+            .try_into()
+            .map_err(|elements: Vec<T>| {
+                FromNbtErrorKind::Custom(format!(
+                    "Expected a list of {N} elements, found {}",
+                    elements.len()
+                ))
+                .into()
+            })
+    }
+}
+
+macro_rules! from_compound {
+    ($($map:ident),*) => {
+        $(
+            impl<T: FromNbt> FromNbt for $map<String, T> {
+                fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+                    __private::compound(value)?
+                        .iter()
+                        .map(|(key, v)| {
+                            T::from_nbt(v)
+                                .map(|v| (key.clone(), v))
+                                .map_err(|err| err.within(PathSegment::Field(key.clone())))
+                        })
+                        .collect()
+                }
+            }
+        )*
+    };
+}
+
+from_compound!(Map, HashMap, BTreeMap);
+
+macro_rules! from_wrapper {
+    ($($wrapper:ident$(<$gen:ident>)?),*) => {
+        $(
+            impl$(<$gen: FromNbt>)? FromNbt for $wrapper$(<$gen>)? {
+                fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+                    Vec::from_nbt(value).map(Self)
+                }
+            }
+        )*
+    };
+}
+
+from_wrapper!(ByteArray, IntArray, LongArray, NbtList<T>);
+
+/// Support functions for the code generated by the derives of flux-macros
+#[doc(hidden)]
+pub mod __private {
+    use nbt::{Map, Value};
+
+    use super::{FromNbt, FromNbtError, FromNbtErrorKind, PathSegment};
+
+    pub fn byte_array<'a, T: Copy + Into<i64> + 'a>(
+        values: impl IntoIterator<Item = &'a T>,
+    ) -> Value {
+        Value::ByteArray(values.into_iter().map(|v| (*v).into() as i8).collect())
+    }
+
+    pub fn int_array<'a, T: Copy + Into<i64> + 'a>(
+        values: impl IntoIterator<Item = &'a T>,
+    ) -> Value {
+        Value::IntArray(values.into_iter().map(|v| (*v).into() as i32).collect())
+    }
+
+    pub fn long_array<'a, T: Copy + Into<i64> + 'a>(
+        values: impl IntoIterator<Item = &'a T>,
+    ) -> Value {
+        Value::LongArray(values.into_iter().map(|v| (*v).into()).collect())
+    }
+
+    /// Merges a flattened value into the parent compound, values other than compounds are skipped
+    pub fn flatten(into: &mut Map<String, Value>, value: Value) {
+        if let Value::Compound(cmp) = value {
+            into.extend(cmp);
+        }
+    }
+
+    pub fn compound(value: &Value) -> Result<&Map<String, Value>, FromNbtError> {
+        match value {
+            Value::Compound(cmp) => Ok(cmp),
+            other => Err(FromNbtError::mismatch("TAG_Compound", other)),
+        }
+    }
+
+    /// Compound of the keys not read by other fields, which flattened fields are read from
+    pub fn rest(cmp: &Map<String, Value>, read: &[&str]) -> Value {
+        Value::Compound(
+            cmp.iter()
+                .filter(|(key, _)| !read.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn list(value: &Value, len: usize) -> Result<&[Value], FromNbtError> {
+        match value {
+            Value::List(list) if list.len() == len => Ok(list),
+            Value::List(list) => Err(FromNbtErrorKind::Custom(format!(
+                "Expected a list of {len} elements, found {}",
+                list.len()
+            ))
+            .into()),
+            other => Err(FromNbtError::mismatch("TAG_List", other)),
+        }
+    }
+
+    pub fn field<T: FromNbt>(
+        cmp: &Map<String, Value>,
+        key: &'static str,
+    ) -> Result<T, FromNbtError> {
+        match cmp.get(key) {
+            Some(value) => {
+                T::from_nbt(value).map_err(|err| err.within(PathSegment::Field(key.to_owned())))
+            }
+            None => Err(FromNbtErrorKind::MissingField(key).into()),
+        }
+    }
+
+    /// Reads a field that falls back to its default when missing, like optional fields do
+    pub fn default_field<T: FromNbt + Default>(
+        cmp: &Map<String, Value>,
+        key: &'static str,
+    ) -> Result<T, FromNbtError> {
+        match cmp.get(key) {
+            Some(value) => {
+                T::from_nbt(value).map_err(|err| err.within(PathSegment::Field(key.to_owned())))
+            }
+            None => Ok(T::default()),
+        }
+    }
+
+    pub fn element<T: FromNbt>(list: &[Value], index: usize) -> Result<T, FromNbtError> {
+        T::from_nbt(&list[index]).map_err(|err| err.within(PathSegment::Index(index)))
+    }
+
+    pub fn unknown_variant(ty: &'static str, variant: &str) -> FromNbtError {
+        FromNbtErrorKind::UnknownVariant {
+            ty,
+            variant: variant.to_owned(),
+        }
+        .into()
+    }
+
+    pub fn no_matching_variant(ty: &'static str) -> FromNbtError {
+        FromNbtErrorKind::NoMatchingVariant(ty).into()
+    }
+
+    /// Wraps the value of a variant with data as `{Variant: value}`
+    pub fn tagged(variant: &str, value: Value) -> Value {
+        Value::Compound(Map::from([(variant.to_owned(), value)]))
+    }
+
+    /// Name of a variant along with its value, which unit variants do not have
+    pub fn variant(value: &Value) -> Option<(&str, Option<&Value>)> {
+        match value {
+            Value::String(variant) => Some((variant, None)),
+            Value::Compound(cmp) if cmp.len() == 1 => cmp
+                .iter()
+                .next()
+                .map(|(variant, value)| (variant.as_str(), Some(value))),
+            _ => None,
+        }
+    }
+
+    pub fn within_variant<T>(
+        result: Result<T, FromNbtError>,
+        variant: &str,
+    ) -> Result<T, FromNbtError> {
+        result.map_err(|err| err.within(PathSegment::Field(variant.to_owned())))
+    }
+
+    /// Copies every entry of a spread compound, values other than compounds are skipped
+    pub fn spread(into: &mut Map<String, Value>, value: Value) {
+        flatten(into, value)
+    }
+
+    /// A literal of the `nbt!` macro, classified at compile time from its source text
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Literal {
+        Byte(i8),
+        Short(i16),
+        Int(i32),
+        Long(i64),
+        /// Floats are validated at compile time, but parsed at runtime
+        Float,
+        Double,
+        String,
+    }
+
+    impl Literal {
+        /// Classifies a literal, panicking at compile time when it is invalid
+        pub const fn parse(lit: &str) -> Literal {
+            let bytes = lit.as_bytes();
+            match bytes {
+                [b'"', ..] | [b'r', b'"' | b'#', ..] => return Literal::String,
+                b"true" => return Literal::Byte(1),
+                b"false" => return Literal::Byte(0),
+                [b'-' | b'0'..=b'9', ..] => {}
+                _ => panic!("nbt! only supports number, string and bool literals"),
+            }
+            let negative = bytes[0] == b'-';
+            let mut i = negative as usize;
+            let mut radix = 10;
+            if i + 2 < bytes.len() && bytes[i] == b'0' {
+                let prefixed = match bytes[i + 1] {
+                    b'x' => 16,
+                    b'o' => 8,
+                    b'b' => 2,
+                    _ => 10,
+                };
+                if prefixed != 10 && digit(bytes[i + 2], prefixed).is_some() {
+                    radix = prefixed;
+                    i += 2;
+                }
+            }
+            let mut value: u128 = 0;
+            let mut digits = 0;
+            while i < bytes.len() {
+                if bytes[i] != b'_' {
+                    match digit(bytes[i], radix) {
+                        Some(d) => {
+                            value = value
+                                .saturating_mul(radix as u128)
+                                .saturating_add(d as u128);
+                            digits += 1;
+                        }
+                        None => break,
+                    }
+                }
+                i += 1;
+            }
+            if digits == 0 {
+                panic!("nbt! number literal without digits");
+            }
+            let mut float = false;
+            if radix == 10 && i < bytes.len() && bytes[i] == b'.' {
+                float = true;
+                i += 1;
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+                    i += 1;
+                }
+            }
+            if radix == 10 && i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                float = true;
+                i += 1;
+                if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+                    i += 1;
+                }
+                let exponent_start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                if i == exponent_start {
+                    panic!("nbt! float literal without exponent digits");
+                }
+            }
+            let suffix = bytes.split_at(i).1;
+            if float {
+                return match suffix {
+                    b"" | b"d" | b"D" | b"f64" => Literal::Double,
+                    b"f" | b"F" | b"f32" => Literal::Float,
+                    _ => panic!("nbt! float literals take the suffixes `f` or `d`"),
+                };
+            }
+            let signed = if negative {
+                -(value as i128)
+            } else {
+                value as i128
+            };
+            match suffix {
+                b"" | b"i32" => {
+                    Literal::Int(ranged(signed, i32::MIN as i128, i32::MAX as i128) as i32)
+                }
+                b"b" | b"B" | b"i8" => {
+                    Literal::Byte(ranged(signed, i8::MIN as i128, i8::MAX as i128) as i8)
+                }
+                b"s" | b"S" | b"i16" => {
+                    Literal::Short(ranged(signed, i16::MIN as i128, i16::MAX as i128) as i16)
+                }
+                b"l" | b"L" | b"i64" => {
+                    Literal::Long(ranged(signed, i64::MIN as i128, i64::MAX as i128) as i64)
+                }
+                // unsigned values are stored in the signed tag of the same width
+                b"u8" => Literal::Byte(ranged(signed, 0, u8::MAX as i128) as u8 as i8),
+                b"u16" => Literal::Short(ranged(signed, 0, u16::MAX as i128) as u16 as i16),
+                b"u32" => Literal::Int(ranged(signed, 0, u32::MAX as i128) as u32 as i32),
+                b"u64" => Literal::Long(ranged(signed, 0, u64::MAX as i128) as u64 as i64),
+                b"f" | b"F" | b"f32" if radix == 10 => Literal::Float,
+                b"d" | b"D" | b"f64" if radix == 10 => Literal::Double,
+                _ => panic!("nbt! integer literals take the suffixes `b`, `s`, `L`, `f` or `d`"),
+            }
+        }
+
+        pub fn value(self, lit: &str) -> Value {
+            match self {
+                Literal::Byte(v) => Value::Byte(v),
+                Literal::Short(v) => Value::Short(v),
+                Literal::Int(v) => Value::Int(v),
+                Literal::Long(v) => Value::Long(v),
+                Literal::Float => Value::Float(float_text(lit).parse().unwrap()),
+                Literal::Double => Value::Double(float_text(lit).parse().unwrap()),
+                Literal::String => Value::String(unescape(lit)),
+            }
+        }
+    }
+
+    const fn digit(byte: u8, radix: u32) -> Option<u32> {
+        let value = match byte {
+            b'0'..=b'9' => byte - b'0',
+            b'a'..=b'f' => byte - b'a' + 10,
+            b'A'..=b'F' => byte - b'A' + 10,
+            _ => return None,
+        } as u32;
+        if value < radix {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    const fn ranged(value: i128, min: i128, max: i128) -> i128 {
+        if value < min || value > max {
+            panic!("nbt! number literal out of range for its type");
+        }
+        value
+    }
+
+    /// Strips the underscores and the suffix of a validated float literal
+    fn float_text(lit: &str) -> String {
+        let text = lit.replace('_', "");
+        let len = ["f32", "f64", "f", "F", "d", "D"]
+            .iter()
+            .find_map(|suffix| text.strip_suffix(suffix))
+            .map_or(text.len(), str::len);
+        text[..len].to_owned()
+    }
+
+    /// Unescapes the source text of a Rust string literal
+    fn unescape(lit: &str) -> String {
+        if let Some(raw) = lit.strip_prefix('r') {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            return raw[hashes + 1..raw.len() - hashes - 1].to_owned();
+        }
+        let inner = &lit[1..lit.len() - 1];
+        if !inner.contains('\\') {
+            return inner.to_owned();
+        }
+        let mut out = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('0') => out.push('\0'),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    out.push(u8::from_str_radix(&hex, 16).unwrap() as char);
+                }
+                Some('u') => {
+                    let hex: String = chars
+                        .by_ref()
+                        .skip(1)
+                        .take_while(|c| *c != '}')
+                        .filter(|c| *c != '_')
+                        .collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                }
+                // a line continuation skips the line break and any leading whitespace
+                Some('\n') => {
+                    let rest = chars.as_str().trim_start();
+                    chars = rest.chars();
+                }
+                Some(other) => out.push(other),
+                None => {}
+            }
+        }
+        out
+    }
+}
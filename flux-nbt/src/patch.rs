@@ -0,0 +1,273 @@
+//! Merging, diffing and patching of NBT trees.
+//!
+//! [`diff`] produces a [`NbtPatch`] that turns one value into another, which can be
+//! reviewed, stored in any serde format and applied to a value later on:
+//!
+//! ```
+//! # use flux_nbt::{nbt, patch, Value};
+//! let base = nbt! { id: "minecraft:diamond_sword", tag: { Damage: 0 } };
+//! let mut item = base.clone();
+//! patch::merge(&mut item, &nbt! { tag: { Unbreakable: true } });
+//!
+//! let patch = patch::diff(&base, &item);
+//! assert_eq!(patch.to_string(), "+ tag.Unbreakable: 1b\n");
+//!
+//! let mut applied = base.clone();
+//! patch.apply(&mut applied).unwrap();
+//! assert_eq!(applied, item);
+//! ```
+
+use std::fmt::Display;
+
+use nbt::Value;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    path::{NbtPath, Node},
+    snbt,
+};
+
+/// Merges `source` into `target` the way `/data merge` does.
+///
+/// Compounds are merged key by key recursively, any other value in `source`,
+/// including lists, replaces the value in `target`.
+pub fn merge(target: &mut Value, source: &Value) {
+    match (target, source) {
+        (Value::Compound(target), Value::Compound(source)) => {
+            for (key, value) in source {
+                match target.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, source) => *target = source.clone(),
+    }
+}
+
+/// Computes the changes that turn `old` into `new`.
+///
+/// Compounds are compared key by key and lists element by element, as long as their
+/// element types match. Arrays and lists of differing types are changed as a whole.
+pub fn diff(old: &Value, new: &Value) -> NbtPatch {
+    let mut patch = NbtPatch::default();
+    diff_into(NbtPath::root(), old, new, &mut patch.entries);
+    patch
+}
+
+fn diff_into(path: NbtPath, old: &Value, new: &Value, entries: &mut Vec<PatchEntry>) {
+    match (old, new) {
+        (Value::Compound(old), Value::Compound(new)) => {
+            for (key, old_value) in old {
+                if !new.contains_key(key) {
+                    entries.push(PatchEntry::Remove {
+                        path: path.clone().key(key),
+                        value: old_value.clone(),
+                    });
+                }
+            }
+            for (key, new_value) in new {
+                match old.get(key) {
+                    Some(old_value) => {
+                        diff_into(path.clone().key(key), old_value, new_value, entries)
+                    }
+                    None => entries.push(PatchEntry::Add {
+                        path: path.clone().key(key),
+                        value: new_value.clone(),
+                    }),
+                }
+            }
+        }
+        (Value::List(old), Value::List(new)) if same_element_type(old, new) => {
+            let common = old.len().min(new.len());
+            for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                diff_into(path.clone().index(index as i32), old, new, entries);
+            }
+            // removed from the back, so the indices stay valid while applying
+            for index in (common..old.len()).rev() {
+                entries.push(PatchEntry::Remove {
+                    path: path.clone().index(index as i32),
+                    value: old[index].clone(),
+                });
+            }
+            for (index, value) in new.iter().enumerate().skip(common) {
+                entries.push(PatchEntry::Add {
+                    path: path.clone().index(index as i32),
+                    value: value.clone(),
+                });
+            }
+        }
+        (old, new) if old != new => entries.push(PatchEntry::Change {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn same_element_type(old: &[Value], new: &[Value]) -> bool {
+    match (old.first(), new.first()) {
+        (Some(old), Some(new)) => old.id() == new.id(),
+        _ => true,
+    }
+}
+
+/// A list of changes to an NBT tree, produced by [`diff`].
+///
+/// Every entry remembers the value it replaces, so applying a patch to a value that
+/// has diverged from the one it was created from fails instead of losing data.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NbtPatch {
+    pub entries: Vec<PatchEntry>,
+}
+
+/// A single change of a [`NbtPatch`], values are serialized as SNBT strings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PatchEntry {
+    /// Adds a key to a compound, or inserts an element into a list
+    Add {
+        path: NbtPath,
+        #[serde(with = "snbt_value")]
+        value: Value,
+    },
+    /// Removes a key from a compound, or an element from a list
+    Remove {
+        path: NbtPath,
+        #[serde(with = "snbt_value")]
+        value: Value,
+    },
+    /// Replaces a value
+    Change {
+        path: NbtPath,
+        #[serde(with = "snbt_value")]
+        old: Value,
+        #[serde(with = "snbt_value")]
+        new: Value,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("Patch conflicts with the target value at `{path}`")]
+pub struct PatchError {
+    /// Path of the first entry that could not be applied
+    pub path: NbtPath,
+}
+
+impl NbtPatch {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Applies every entry in order, stopping at the first one that does not match the target.
+    ///
+    /// The target is left partially patched if an error is returned.
+    pub fn apply(&self, target: &mut Value) -> Result<(), PatchError> {
+        for entry in &self.entries {
+            if !entry.apply(target) {
+                return Err(PatchError {
+                    path: entry.path().clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PatchEntry {
+    pub fn path(&self) -> &NbtPath {
+        match self {
+            PatchEntry::Add { path, .. }
+            | PatchEntry::Remove { path, .. }
+            | PatchEntry::Change { path, .. } => path,
+        }
+    }
+
+    fn apply(&self, target: &mut Value) -> bool {
+        match self {
+            PatchEntry::Add { path, value } => {
+                let Some((last, parent)) = split_last(path) else {
+                    return false;
+                };
+                let fits = match (last, parent.get(target).as_deref()) {
+                    (Node::Key(key), Ok(Value::Compound(cmp))) => !cmp.contains_key(key),
+                    (Node::Index(index), Ok(Value::List(list))) => *index as usize == list.len(),
+                    _ => false,
+                };
+                match last {
+                    Node::Index(index) if fits => parent.insert(target, *index, value.clone()) == 1,
+                    _ => fits && path.set(target, value.clone()) == 1,
+                }
+            }
+            PatchEntry::Remove { path, value } => {
+                matches!(path.get(target).as_deref(), Ok(current) if current == value)
+                    && path.remove(target) == 1
+            }
+            PatchEntry::Change { path, old, new } => {
+                if !matches!(path.get(target).as_deref(), Ok(current) if current == old) {
+                    return false;
+                }
+                if path.is_root() {
+                    *target = new.clone();
+                    return true;
+                }
+                path.set(target, new.clone()) == 1
+            }
+        }
+    }
+}
+
+fn split_last(path: &NbtPath) -> Option<(&Node, NbtPath)> {
+    let (last, parent) = path.nodes.split_last()?;
+    Some((
+        last,
+        NbtPath {
+            nodes: parent.to_vec(),
+        },
+    ))
+}
+
+/// Writes one line per entry, prefixed with `+`, `-` or `~`
+impl Display for NbtPatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            match entry {
+                PatchEntry::Add { path, value } => {
+                    writeln!(f, "+ {path}: {}", snbt::to_string(value))?
+                }
+                PatchEntry::Remove { path, value } => {
+                    writeln!(f, "- {path}: {}", snbt::to_string(value))?
+                }
+                PatchEntry::Change { path, old, new } => writeln!(
+                    f,
+                    "~ {path}: {} -> {}",
+                    snbt::to_string(old),
+                    snbt::to_string(new)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stores values as SNBT strings, which keeps their exact tag types in any format
+mod snbt_value {
+    use nbt::Value;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::snbt;
+
+    pub fn serialize<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&snbt::to_string(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        snbt::from_str(&text).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,683 @@
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+
+use nbt::{Map, Value};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::snbt::{self, SnbtError};
+
+/// A path into an NBT tree, using the syntax of the `/data` command,
+/// e.g. `Inventory[{Slot:0b}].tag.display.Name`.
+///
+/// The supported nodes are:
+/// - `key`, `"quoted key"` selects a key of a compound
+/// - `key{filter}` selects a key of a compound if its value matches the filter
+/// - `{filter}` matches the root compound, only allowed as the first node
+/// - `[n]`, `[-n]` selects an element of a list or an array, counting from the end if negative
+/// - `[]` selects all elements of a list or an array
+/// - `[{filter}]` selects all compounds of a list that match the filter
+///
+/// Filters match if every key they contain is present with a matching value,
+/// lists in a filter match if each of their elements matches any element of the target list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NbtPath {
+    pub(crate) nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    MatchRoot(Map<String, Value>),
+    Key(String),
+    MatchKey(String, Map<String, Value>),
+    Index(i32),
+    All,
+    MatchElement(Map<String, Value>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PathError {
+    #[error("Expected {expected} at column {column} of the NBT path")]
+    Expected {
+        expected: &'static str,
+        column: usize,
+    },
+    #[error("Invalid filter at column {column} of the NBT path: {source}")]
+    Filter { column: usize, source: SnbtError },
+    #[error("Found no elements matching {0}")]
+    NotFound(String),
+    #[error("Found {0} elements matching the path, expected exactly one")]
+    MultipleMatches(usize),
+}
+
+impl NbtPath {
+    pub fn parse(path: &str) -> Result<Self, PathError> {
+        PathParser { src: path, pos: 0 }.parse()
+    }
+
+    /// The empty path, pointing at the root value itself.
+    ///
+    /// It has no textual form in the game and is written as an empty string.
+    pub fn root() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Appends a compound key to the path
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.nodes.push(Node::Key(key.into()));
+        self
+    }
+
+    /// Appends a list or array index to the path
+    pub fn index(mut self, index: i32) -> Self {
+        self.nodes.push(Node::Index(index));
+        self
+    }
+
+    /// Returns every value the path points to.
+    ///
+    /// Elements of arrays are returned as owned values, anything else is borrowed.
+    pub fn get_all<'a>(&self, root: &'a Value) -> Vec<Cow<'a, Value>> {
+        let mut current = vec![Cow::Borrowed(root)];
+        for node in &self.nodes {
+            let mut next = Vec::new();
+            for value in &current {
+                // owned values are array elements, which have no children
+                if let Cow::Borrowed(value) = value {
+                    node.get(value, &mut next);
+                }
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Returns the single value the path points to, like `/data get` does
+    pub fn get<'a>(&self, root: &'a Value) -> Result<Cow<'a, Value>, PathError> {
+        let mut all = self.get_all(root);
+        match all.len() {
+            0 => Err(PathError::NotFound(self.to_string())),
+            1 => Ok(all.remove(0)),
+            len => Err(PathError::MultipleMatches(len)),
+        }
+    }
+
+    /// Sets every value the path points to, creating missing compounds and lists on the way.
+    ///
+    /// Returns the amount of values that changed, the game reports a failure if it is zero.
+    pub fn set(&self, root: &mut Value, value: Value) -> usize {
+        let Some((last, parents)) = self.nodes.split_last() else {
+            return 0;
+        };
+        for_each_parent(
+            parents,
+            root,
+            Some(last.preferred_parent()),
+            &mut |parent| last.set(parent, &value),
+        )
+    }
+
+    /// Removes every value the path points to, returning how many were removed
+    pub fn remove(&self, root: &mut Value) -> usize {
+        let Some((last, parents)) = self.nodes.split_last() else {
+            return 0;
+        };
+        for_each_parent(parents, root, None, &mut |parent| last.remove(parent))
+    }
+
+    /// Inserts the value at `index` into every list or array the path points to, creating
+    /// the path if it does not exist. Negative indices count from the end, `-1` appends.
+    ///
+    /// Returns the amount of collections the value was inserted into.
+    pub fn insert(&self, root: &mut Value, index: i32, value: Value) -> usize {
+        for_each_parent(
+            &self.nodes,
+            root,
+            Some(Value::List(Vec::new())),
+            &mut |target| {
+                let len = collection_len(target).unwrap_or(0) as i64;
+                let index = if index < 0 {
+                    len + index as i64 + 1
+                } else {
+                    index as i64
+                };
+                if !(0..=len).contains(&index) {
+                    return 0;
+                }
+                insert_element(target, index as usize, value.clone()) as usize
+            },
+        )
+    }
+}
+
+impl FromStr for NbtPath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Serialize for NbtPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NbtPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        if path.is_empty() {
+            return Ok(Self::root());
+        }
+        Self::parse(&path).map_err(D::Error::custom)
+    }
+}
+
+impl Display for NbtPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, node) in self.nodes.iter().enumerate() {
+            if index > 0 && matches!(node, Node::Key(_) | Node::MatchKey(..)) {
+                f.write_str(".")?;
+            }
+            match node {
+                Node::MatchRoot(filter) => write_filter(f, filter)?,
+                Node::Key(key) => write_key(f, key)?,
+                Node::MatchKey(key, filter) => {
+                    write_key(f, key)?;
+                    write_filter(f, filter)?
+                }
+                Node::Index(index) => f.write_fmt(format_args!("[{index}]"))?,
+                Node::All => f.write_str("[]")?,
+                Node::MatchElement(filter) => {
+                    f.write_str("[")?;
+                    write_filter(f, filter)?;
+                    f.write_str("]")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_key(f: &mut std::fmt::Formatter<'_>, key: &str) -> std::fmt::Result {
+    if !key.is_empty() && key.chars().all(is_unquoted_char) {
+        f.write_str(key)
+    } else {
+        f.write_fmt(format_args!(
+            "\"{}\"",
+            key.replace('\\', "\\\\").replace('"', "\\\"")
+        ))
+    }
+}
+
+fn write_filter(f: &mut std::fmt::Formatter<'_>, filter: &Map<String, Value>) -> std::fmt::Result {
+    f.write_str(&snbt::to_string(&Value::Compound(filter.clone())))
+}
+
+/// Walks the `nodes`, calling `f` on every value they lead to.
+///
+/// Missing compound keys and empty lists are filled in when `leaf` is given,
+/// with `leaf` being the value created for the last node.
+fn for_each_parent(
+    nodes: &[Node],
+    value: &mut Value,
+    leaf: Option<Value>,
+    f: &mut dyn FnMut(&mut Value) -> usize,
+) -> usize {
+    let Some((node, rest)) = nodes.split_first() else {
+        return f(value);
+    };
+    let create = leaf.as_ref().map(|leaf| match rest.first() {
+        Some(next) => next.preferred_parent(),
+        None => leaf.clone(),
+    });
+    node.get_mut(value, create)
+        .into_iter()
+        .map(|child| for_each_parent(rest, child, leaf.clone(), f))
+        .sum()
+}
+
+impl Node {
+    /// The empty value created when this node has to be applied to a missing value
+    fn preferred_parent(&self) -> Value {
+        match self {
+            Node::MatchRoot(_) | Node::Key(_) | Node::MatchKey(..) => Value::Compound(Map::new()),
+            Node::Index(_) | Node::All | Node::MatchElement(_) => Value::List(Vec::new()),
+        }
+    }
+
+    fn get<'a>(&self, value: &'a Value, out: &mut Vec<Cow<'a, Value>>) {
+        match (self, value) {
+            (Node::MatchRoot(filter), value) if matches_filter(filter, value) => {
+                out.push(Cow::Borrowed(value))
+            }
+            (Node::Key(key), Value::Compound(cmp)) => out.extend(cmp.get(key).map(Cow::Borrowed)),
+            (Node::MatchKey(key, filter), Value::Compound(cmp)) => out.extend(
+                cmp.get(key)
+                    .filter(|value| matches_filter(filter, value))
+                    .map(Cow::Borrowed),
+            ),
+            (Node::Index(index), value) => {
+                let element = collection_len(value)
+                    .and_then(|len| resolve_index(*index, len))
+                    .and_then(|index| get_element(value, index));
+                out.extend(element)
+            }
+            (Node::All, value) => {
+                let len = collection_len(value).unwrap_or(0);
+                out.extend((0..len).filter_map(|index| get_element(value, index)))
+            }
+            (Node::MatchElement(filter), Value::List(list)) => out.extend(
+                list.iter()
+                    .filter(|value| matches_filter(filter, value))
+                    .map(Cow::Borrowed),
+            ),
+            _ => {}
+        }
+    }
+
+    /// Returns the children of a value this node selects, which can only be list elements
+    /// or compound values, as array elements have no children
+    fn get_mut<'a>(&self, value: &'a mut Value, create: Option<Value>) -> Vec<&'a mut Value> {
+        match (self, value) {
+            (Node::MatchRoot(filter), value) => {
+                if matches_filter(filter, value) {
+                    vec![value]
+                } else {
+                    vec![]
+                }
+            }
+            (Node::Key(key), Value::Compound(cmp)) => {
+                if let Some(create) = create {
+                    cmp.entry(key.clone()).or_insert(create);
+                }
+                cmp.get_mut(key).into_iter().collect()
+            }
+            (Node::MatchKey(key, filter), Value::Compound(cmp)) => {
+                if create.is_some() {
+                    cmp.entry(key.clone())
+                        .or_insert_with(|| Value::Compound(filter.clone()));
+                }
+                cmp.get_mut(key)
+                    .filter(|value| matches_filter(filter, value))
+                    .into_iter()
+                    .collect()
+            }
+            (Node::Index(index), Value::List(list)) => resolve_index(*index, list.len())
+                .map(|index| vec![&mut list[index]])
+                .unwrap_or_default(),
+            (Node::All, Value::List(list)) => {
+                if let Some(create) = create.filter(|_| list.is_empty()) {
+                    list.push(create);
+                }
+                list.iter_mut().collect()
+            }
+            (Node::MatchElement(filter), Value::List(list)) => {
+                let compounds = list
+                    .first()
+                    .is_none_or(|first| matches!(first, Value::Compound(_)));
+                if create.is_some()
+                    && compounds
+                    && !list.iter().any(|value| matches_filter(filter, value))
+                {
+                    list.push(Value::Compound(filter.clone()));
+                }
+                list.iter_mut()
+                    .filter(|value| matches_filter(filter, value))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn set(&self, parent: &mut Value, value: &Value) -> usize {
+        match (self, parent) {
+            (Node::Key(key), Value::Compound(cmp)) => {
+                if cmp.get(key) == Some(value) {
+                    return 0;
+                }
+                cmp.insert(key.clone(), value.clone());
+                1
+            }
+            (Node::MatchKey(key, filter), Value::Compound(cmp)) => match cmp.get_mut(key) {
+                Some(old) if matches_filter(filter, old) && old != value => {
+                    *old = value.clone();
+                    1
+                }
+                _ => 0,
+            },
+            (Node::Index(index), parent) => {
+                let Some(index) = collection_len(parent).and_then(|len| resolve_index(*index, len))
+                else {
+                    return 0;
+                };
+                if get_element(parent, index).as_deref() == Some(value) {
+                    return 0;
+                }
+                set_element(parent, index, value) as usize
+            }
+            (Node::All, parent) => {
+                let Some(len) = collection_len(parent) else {
+                    return 0;
+                };
+                if len == 0 {
+                    return insert_element(parent, 0, value.clone()) as usize;
+                }
+                (0..len)
+                    .filter(|index| {
+                        get_element(parent, *index).as_deref() != Some(value)
+                            && set_element(parent, *index, value)
+                    })
+                    .count()
+            }
+            (Node::MatchElement(filter), Value::List(list)) => {
+                let mut changed = 0;
+                for element in list.iter_mut() {
+                    if matches_filter(filter, element) && element != value {
+                        *element = value.clone();
+                        changed += 1;
+                    }
+                }
+                changed
+            }
+            _ => 0,
+        }
+    }
+
+    fn remove(&self, parent: &mut Value) -> usize {
+        match (self, parent) {
+            (Node::Key(key), Value::Compound(cmp)) => cmp.shift_remove(key).is_some() as usize,
+            (Node::MatchKey(key, filter), Value::Compound(cmp)) => match cmp.get(key) {
+                Some(value) if matches_filter(filter, value) => {
+                    cmp.shift_remove(key);
+                    1
+                }
+                _ => 0,
+            },
+            (Node::Index(index), parent) => {
+                match collection_len(parent).and_then(|len| resolve_index(*index, len)) {
+                    Some(index) => {
+                        remove_element(parent, index);
+                        1
+                    }
+                    None => 0,
+                }
+            }
+            (Node::All, parent) => {
+                let len = collection_len(parent).unwrap_or(0);
+                clear(parent);
+                len
+            }
+            (Node::MatchElement(filter), Value::List(list)) => {
+                let len = list.len();
+                list.retain(|value| !matches_filter(filter, value));
+                len - list.len()
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Checks whether the `target` contains everything in the `pattern`, the way the game compares filters
+pub fn matches_pattern(pattern: &Value, target: &Value) -> bool {
+    match (pattern, target) {
+        (Value::Compound(pattern), target) => matches_filter(pattern, target),
+        (Value::List(pattern), Value::List(target)) if pattern.is_empty() => target.is_empty(),
+        (Value::List(pattern), Value::List(target)) => pattern
+            .iter()
+            .all(|pattern| target.iter().any(|value| matches_pattern(pattern, value))),
+        (pattern, target) => pattern == target,
+    }
+}
+
+fn matches_filter(filter: &Map<String, Value>, target: &Value) -> bool {
+    match target {
+        Value::Compound(cmp) => filter.iter().all(|(key, pattern)| {
+            cmp.get(key)
+                .is_some_and(|value| matches_pattern(pattern, value))
+        }),
+        _ => false,
+    }
+}
+
+fn resolve_index(index: i32, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        len as i64 + index as i64
+    } else {
+        index as i64
+    };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+fn collection_len(value: &Value) -> Option<usize> {
+    match value {
+        Value::List(list) => Some(list.len()),
+        Value::ByteArray(bytes) => Some(bytes.len()),
+        Value::IntArray(ints) => Some(ints.len()),
+        Value::LongArray(longs) => Some(longs.len()),
+        _ => None,
+    }
+}
+
+fn get_element(value: &Value, index: usize) -> Option<Cow<'_, Value>> {
+    match value {
+        Value::List(list) => list.get(index).map(Cow::Borrowed),
+        Value::ByteArray(bytes) => bytes.get(index).map(|v| Cow::Owned(Value::Byte(*v))),
+        Value::IntArray(ints) => ints.get(index).map(|v| Cow::Owned(Value::Int(*v))),
+        Value::LongArray(longs) => longs.get(index).map(|v| Cow::Owned(Value::Long(*v))),
+        _ => None,
+    }
+}
+
+/// Numbers stored into arrays are converted to the element type, like the game does
+fn as_long(value: &Value) -> Option<i64> {
+    match value {
+        Value::Byte(v) => Some(*v as i64),
+        Value::Short(v) => Some(*v as i64),
+        Value::Int(v) => Some(*v as i64),
+        Value::Long(v) => Some(*v),
+        Value::Float(v) => Some(v.floor() as i64),
+        Value::Double(v) => Some(v.floor() as i64),
+        _ => None,
+    }
+}
+
+/// Lists only accept elements of the same type as their existing elements
+fn accepts(list: &[Value], value: &Value) -> bool {
+    list.first().is_none_or(|first| first.id() == value.id())
+}
+
+fn set_element(collection: &mut Value, index: usize, value: &Value) -> bool {
+    match collection {
+        Value::List(list) if accepts(list, value) => list[index] = value.clone(),
+        Value::ByteArray(bytes) => match as_long(value) {
+            Some(v) => bytes[index] = v as i8,
+            None => return false,
+        },
+        Value::IntArray(ints) => match as_long(value) {
+            Some(v) => ints[index] = v as i32,
+            None => return false,
+        },
+        Value::LongArray(longs) => match as_long(value) {
+            Some(v) => longs[index] = v,
+            None => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+fn insert_element(collection: &mut Value, index: usize, value: Value) -> bool {
+    match collection {
+        Value::List(list) if accepts(list, &value) => list.insert(index, value),
+        Value::ByteArray(bytes) => match as_long(&value) {
+            Some(v) => bytes.insert(index, v as i8),
+            None => return false,
+        },
+        Value::IntArray(ints) => match as_long(&value) {
+            Some(v) => ints.insert(index, v as i32),
+            None => return false,
+        },
+        Value::LongArray(longs) => match as_long(&value) {
+            Some(v) => longs.insert(index, v),
+            None => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+fn remove_element(collection: &mut Value, index: usize) {
+    match collection {
+        Value::List(list) => drop(list.remove(index)),
+        Value::ByteArray(bytes) => drop(bytes.remove(index)),
+        Value::IntArray(ints) => drop(ints.remove(index)),
+        Value::LongArray(longs) => drop(longs.remove(index)),
+        _ => {}
+    }
+}
+
+fn clear(collection: &mut Value) {
+    match collection {
+        Value::List(list) => list.clear(),
+        Value::ByteArray(bytes) => bytes.clear(),
+        Value::IntArray(ints) => ints.clear(),
+        Value::LongArray(longs) => longs.clear(),
+        _ => {}
+    }
+}
+
+/// Characters allowed in unquoted keys of a path, which are more than in SNBT keys
+fn is_unquoted_char(c: char) -> bool {
+    !matches!(c, ' ' | '"' | '\'' | '[' | ']' | '.' | '{' | '}')
+}
+
+struct PathParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> PathParser<'a> {
+    fn parse(mut self) -> Result<NbtPath, PathError> {
+        let mut nodes = vec![self.parse_node(true)?];
+        while let Some(c) = self.peek() {
+            if c != '[' && c != '{' {
+                self.expect('.', "`.`")?;
+            }
+            nodes.push(self.parse_node(false)?);
+        }
+        Ok(NbtPath { nodes })
+    }
+
+    fn column(&self) -> usize {
+        self.src[..self.pos].chars().count() + 1
+    }
+
+    fn expected(&self, expected: &'static str) -> PathError {
+        PathError::Expected {
+            expected,
+            column: self.column(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.pos += next.len_utf8();
+        Some(next)
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), PathError> {
+        if self.peek() == Some(c) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.expected(expected))
+        }
+    }
+
+    fn parse_node(&mut self, first: bool) -> Result<Node, PathError> {
+        match self.peek() {
+            Some('{') if first => Ok(Node::MatchRoot(self.parse_filter()?)),
+            Some('{') => Err(self.expected("a key or `[`")),
+            Some('[') => {
+                self.next();
+                let node = match self.peek() {
+                    Some('{') => Node::MatchElement(self.parse_filter()?),
+                    Some(']') => Node::All,
+                    _ => Node::Index(self.parse_index()?),
+                };
+                self.expect(']', "`]`")?;
+                Ok(node)
+            }
+            _ => {
+                let key = self.parse_key()?;
+                if self.peek() == Some('{') {
+                    Ok(Node::MatchKey(key, self.parse_filter()?))
+                } else {
+                    Ok(Node::Key(key))
+                }
+            }
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<String, PathError> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.next();
+                let mut key = String::new();
+                loop {
+                    match self.next() {
+                        Some('\\') => match self.next() {
+                            Some(c @ '\\') => key.push(c),
+                            Some(c) if c == quote => key.push(c),
+                            _ => return Err(self.expected("`\\` or a quote after `\\`")),
+                        },
+                        Some(c) if c == quote => return Ok(key),
+                        Some(c) => key.push(c),
+                        None => return Err(self.expected("closing quote")),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if is_unquoted_char(c)) {
+                    self.next();
+                }
+                if start == self.pos {
+                    return Err(self.expected("a key"));
+                }
+                Ok(self.src[start..self.pos].to_owned())
+            }
+        }
+    }
+
+    fn parse_index(&mut self) -> Result<i32, PathError> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.next();
+        }
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.next();
+        }
+        self.src[start..self.pos].parse().map_err(|_| {
+            self.pos = start;
+            self.expected("an index, `{` or `]`")
+        })
+    }
+
+    fn parse_filter(&mut self) -> Result<Map<String, Value>, PathError> {
+        let column = self.column();
+        let (filter, len) = snbt::compound_prefix(&self.src[self.pos..])
+            .map_err(|source| PathError::Filter { column, source })?;
+        self.pos += len;
+        Ok(filter)
+    }
+}
@@ -1,6 +1,6 @@
-use std::sync::{Arc, Mutex};
+use std::fmt::Display;
 
-use nbt::Value;
+use nbt::{Map, Value};
 use serde::{
     ser::{
         SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
@@ -10,39 +10,81 @@ use serde::{
 };
 use thiserror::Error;
 
-use crate::snbt::Snbt;
+use crate::{array, snbt::Snbt, stream::tag};
 
-pub fn to_nbt<T: Serialize>(obj: &T) -> Option<Value> {
-    let mut ser = Obj2CmpSerializer::new();
-    ser.scopes.lock().unwrap().push(ScopeTy::None);
-    ser.push_anonymous_node();
-    ser.serialize(obj)
+/// Serializes a `T` into an NBT value.
+///
+/// Sequences become `TAG_List` unless forced otherwise through [`array`]. [`Value`]
+/// fields need [`array::value`] to keep their array tags, since the derived implementation
+/// writes arrays as plain sequences.
+pub fn to_nbt<T: Serialize>(obj: &T) -> Result<Value, SerError> {
+    Obj2CmpSerializer::new().serialize(obj)
 }
 
-pub fn to_snbt<T: Serialize>(obj: &T) -> Option<String> {
+pub fn to_snbt<T: Serialize>(obj: &T) -> Result<String, SerError> {
     let mut out = String::new();
     to_nbt(obj)?.snbt_fmt(&mut out);
-    Some(out)
+    Ok(out)
 }
 
-#[derive(Debug, Clone)]
-enum CompoundNode {
-    Vacant(String),
-    Occupied(String, Value),
-    Unnamed(Value),
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{kind}{}", At(.path))]
+pub struct SerError {
+    /// Location of the offending value, starting at the root
+    pub path: Vec<PathSegment>,
+    pub kind: SerErrorKind,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum ScopeTy {
-    None,
-    Compound(usize),
-    Array(usize),
+impl SerError {
+    /// Renders the path the way it would be written in Rust, e.g. `tag.display.Lore[2]`
+    pub fn path_string(&self) -> String {
+        Path(&self.path).to_string()
+    }
+
+    pub(crate) fn within(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+}
+
+impl From<SerErrorKind> for SerError {
+    fn from(kind: SerErrorKind) -> Self {
+        Self {
+            path: Vec::new(),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
 }
 
-#[derive(Debug, Error)]
-pub enum SerError {
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SerErrorKind {
     #[error("Can only serialize keys of String type")]
     InvalidKey,
+    #[error("Can not insert {found} into a list of {expected}")]
+    HeterogeneousList {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("Can not insert {found} into {array}")]
+    InvalidArrayElement {
+        array: &'static str,
+        found: &'static str,
+    },
+    #[error("Can not serialize {found} as {array}")]
+    InvalidArray {
+        array: &'static str,
+        found: &'static str,
+    },
+    #[error("The serialized value is absent")]
+    AbsentRoot,
+    #[error("Can not serialize an absent value inside of a list")]
+    AbsentElement,
     #[error("A serde error has occurred: {0}")]
     Custom(String),
 }
@@ -52,215 +94,239 @@ impl serde::ser::Error for SerError {
     where
         T: std::fmt::Display,
     {
-        Self::Custom(msg.to_string())
+        SerErrorKind::Custom(msg.to_string()).into()
+    }
+}
+
+struct Path<'a>(&'a [PathSegment]);
+
+impl<'a> Display for Path<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Field(name) if index == 0 => f.write_str(name)?,
+                PathSegment::Field(name) => f.write_fmt(format_args!(".{name}"))?,
+                PathSegment::Index(index) => f.write_fmt(format_args!("[{index}]"))?,
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Obj2CmpSerializer {
-    nodes: Arc<Mutex<Vec<CompoundNode>>>,
-    scopes: Arc<Mutex<Vec<ScopeTy>>>,
-    output: Arc<Mutex<std::collections::HashMap<String, Value>>>,
+pub(crate) struct At<'a>(pub(crate) &'a [PathSegment]);
+
+impl<'a> Display for At<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            [] => Ok(()),
+            path => f.write_fmt(format_args!(" at `{}`", Path(path))),
+        }
+    }
 }
 
-pub struct Seq<'a> {
-    ser: &'a mut Obj2CmpSerializer,
+/// Serializes objects into [`Value`]s.
+///
+/// Every value is built bottom-up by a recursive call, an `Ok(None)` marks an absent value
+/// which is skipped by the enclosing compound or sequence.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Obj2CmpSerializer;
+
+pub struct Seq {
+    elements: Vec<Value>,
+    index: usize,
+    variant: Option<&'static str>,
 }
 
-pub struct Struct<'a> {
-    ser: &'a mut Obj2CmpSerializer,
+pub struct Struct {
+    map: Map<String, Value>,
+    key: Option<String>,
+    variant: Option<&'static str>,
 }
 
 impl Obj2CmpSerializer {
     pub fn new() -> Self {
-        let scopes = Arc::new(Mutex::new(Vec::with_capacity(8)));
-        let nodes = Arc::new(Mutex::new(Vec::with_capacity(64)));
-        let output = Arc::new(Mutex::new(std::collections::HashMap::new()));
+        Self
+    }
+
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, obj: &T) -> Result<Value, SerError> {
+        obj.serialize(*self)?
+            .ok_or_else(|| SerErrorKind::AbsentRoot.into())
+    }
+}
+
+/// Wraps the value of an enum variant with data as `{Variant: value}`
+fn variant_of(variant: &'static str, value: Value) -> Option<Value> {
+    let mut map = Map::with_capacity(1);
+    map.insert(variant.to_owned(), value);
+    Some(Value::Compound(map))
+}
 
+/// Prefixes the path of an error with the variant it occurred in, if any
+fn within_variant(err: SerError, variant: Option<&'static str>) -> SerError {
+    match variant {
+        Some(variant) => err.within(PathSegment::Field(variant.to_owned())),
+        None => err,
+    }
+}
+
+impl Seq {
+    fn new(len: Option<usize>) -> Self {
         Self {
-            nodes,
-            scopes,
-            output,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+            index: 0,
+            variant: None,
         }
     }
 
-    pub fn serialize<T: Serialize>(&mut self, obj: &T) -> Option<Value> {
-        let mut copy = Obj2CmpSerializer {
-            nodes: self.nodes.clone(),
-            scopes: self.scopes.clone(),
-            output: self.output.clone(),
-        };
-        copy.push_cmp();
-        obj.serialize(&mut copy).unwrap();
-        match self.pop_node() {
-            CompoundNode::Unnamed(value) => Some(value),
-            _ => None,
-        }
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let index = self.index;
+        self.index += 1;
+        let value = value
+            .serialize(Obj2CmpSerializer)
+            .and_then(|value| value.ok_or_else(|| SerErrorKind::AbsentElement.into()))
+            .map_err(|err| within_variant(err.within(PathSegment::Index(index)), self.variant))?;
+        self.elements.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>, SerError> {
+        let list = list_of(self.elements).map_err(|err| within_variant(err, self.variant))?;
+        Ok(match self.variant {
+            Some(variant) => variant_of(variant, list),
+            None => Some(list),
+        })
     }
+}
 
-    pub fn finish(&mut self) -> Option<Value> {
-        match self.pop_node() {
-            CompoundNode::Unnamed(value) => Some(value),
-            _ => None,
+impl Struct {
+    fn new(len: Option<usize>) -> Self {
+        Self {
+            map: Map::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
         }
     }
 
-    fn push_node(&mut self, name: String) {
-        self.nodes.lock().unwrap().push(CompoundNode::Vacant(name))
-    }
-
-    fn push_anonymous_node(&mut self) {
-        self.nodes
-            .lock()
-            .unwrap()
-            .push(CompoundNode::Unnamed(Value::Byte(0)))
-    }
-
-    fn mutate_node(&mut self, value: Value) {
-        let mut nodes = self.nodes.lock().unwrap();
-        let last = nodes.last_mut();
-        match last {
-            Some(last) => match last {
-                CompoundNode::Vacant(name) => {
-                    *last = CompoundNode::Occupied(name.to_owned(), value)
-                }
-                CompoundNode::Occupied(_, old_value) => *old_value = value,
-                CompoundNode::Unnamed(old_value) => *old_value = value,
-            },
-            None => {
-                // we are probably in array
-                self.nodes
-                    .lock()
-                    .unwrap()
-                    .push(CompoundNode::Unnamed(value))
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), SerError> {
+        match value.serialize(Obj2CmpSerializer) {
+            Ok(Some(value)) => {
+                self.map.insert(key, value);
+                Ok(())
             }
+            Ok(None) => Ok(()),
+            Err(err) => Err(within_variant(
+                err.within(PathSegment::Field(key)),
+                self.variant,
+            )),
         }
     }
 
-    fn pop_node(&mut self) -> CompoundNode {
-        self.nodes.lock().unwrap().pop().unwrap()
-    }
-
-    fn push_array(&mut self) {
-        self.scopes
-            .lock()
-            .unwrap()
-            .push(ScopeTy::Array(self.nodes.lock().unwrap().len()));
-    }
-
-    fn push_cmp(&mut self) {
-        self.scopes
-            .lock()
-            .unwrap()
-            .push(ScopeTy::Compound(self.nodes.lock().unwrap().len()));
-    }
-
-    fn pop_scope(&mut self) {
-        let last = self.scopes.lock().unwrap().pop().unwrap();
-        match last {
-            ScopeTy::None => unreachable!(),
-            ScopeTy::Compound(cmp) => {
-                let map = std::collections::HashMap::<String, Value>::from_iter(
-                    self.nodes
-                        .lock()
-                        .unwrap()
-                        .drain(cmp..)
-                        .map(|node| match node {
-                            CompoundNode::Occupied(name, value) => (name, value),
-                            _ => unreachable!(),
-                        }),
-                );
-                self.mutate_node(Value::Compound(map))
-            }
-            ScopeTy::Array(arr) => {
-                let arr: Vec<Value> = self
-                    .nodes
-                    .lock()
-                    .unwrap()
-                    .drain(arr..)
-                    .map(|node| match node {
-                        CompoundNode::Unnamed(value) => value,
-                        other => panic!("Did not expect the {other:?} node"),
-                    })
-                    .collect();
-                macro_rules! unwrap {
-                    ($value:ident as $pattern:ident) => {
-                        match $value {
-                            Value::$pattern(value) => value,
-                            _ => panic!("Heterogenous array encountered!"),
-                        }
-                    };
-                }
-                let val = match arr.first().unwrap() {
-                    Value::Byte(_) => {
-                        // a byte array
-                        Value::ByteArray(
-                            arr.into_iter().map(|each| unwrap!(each as Byte)).collect(),
-                        )
-                    }
-                    Value::Int(_) => {
-                        Value::IntArray(arr.into_iter().map(|each| unwrap!(each as Int)).collect())
-                    }
-                    Value::Long(_) => Value::LongArray(
-                        arr.into_iter().map(|each| unwrap!(each as Long)).collect(),
-                    ),
-                    _ => Value::List(arr),
-                };
-                self.mutate_node(val)
-            }
+    fn finish(self) -> Result<Option<Value>, SerError> {
+        let cmp = Value::Compound(self.map);
+        Ok(match self.variant {
+            Some(variant) => variant_of(variant, cmp),
+            None => Some(cmp),
+        })
+    }
+}
+
+/// Builds a list, making sure that all of its elements have the same tag
+fn list_of(elements: Vec<Value>) -> Result<Value, SerError> {
+    if let Some(first) = elements.first() {
+        let id = first.id();
+        if let Some((index, other)) = elements
+            .iter()
+            .enumerate()
+            .find(|(_, each)| each.id() != id)
+        {
+            return Err(SerError::from(SerErrorKind::HeterogeneousList {
+                expected: tag::name(id),
+                found: tag::name(other.id()),
+            })
+            .within(PathSegment::Index(index)));
         }
     }
+    Ok(Value::List(elements))
 }
 
-impl Default for Obj2CmpSerializer {
-    fn default() -> Self {
-        Self::new()
+/// Converts a serialized sequence into the tag forced by one of the [`array`] wrappers
+fn force_array(array: u8, value: Value) -> Result<Value, SerError> {
+    let found = tag::name(value.id());
+    let elements = match value {
+        Value::List(list) => list,
+        Value::ByteArray(bytes) => bytes.into_iter().map(Value::Byte).collect(),
+        Value::IntArray(ints) => ints.into_iter().map(Value::Int).collect(),
+        Value::LongArray(longs) => longs.into_iter().map(Value::Long).collect(),
+        _ => {
+            return Err(SerErrorKind::InvalidArray {
+                array: tag::name(array),
+                found,
+            }
+            .into())
+        }
+    };
+    macro_rules! collect {
+        ($pattern:ident) => {
+            elements
+                .into_iter()
+                .enumerate()
+                .map(|(index, each)| match each {
+                    Value::$pattern(value) => Ok(value),
+                    other => Err(SerError::from(SerErrorKind::InvalidArrayElement {
+                        array: tag::name(array),
+                        found: tag::name(other.id()),
+                    })
+                    .within(PathSegment::Index(index))),
+                })
+                .collect::<Result<_, _>>()?
+        };
     }
+    Ok(match array {
+        tag::BYTE_ARRAY => Value::ByteArray(collect!(Byte)),
+        tag::INT_ARRAY => Value::IntArray(collect!(Int)),
+        tag::LONG_ARRAY => Value::LongArray(collect!(Long)),
+        _ => list_of(elements)?,
+    })
 }
 
 #[allow(unused_variables)]
-impl<'a> Serializer for &'a mut Obj2CmpSerializer {
-    type Ok = ();
+impl Serializer for Obj2CmpSerializer {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    type SerializeSeq = Seq<'a>;
+    type SerializeSeq = Seq;
 
-    type SerializeTuple = Seq<'a>;
+    type SerializeTuple = Seq;
 
-    type SerializeTupleStruct = Seq<'a>;
+    type SerializeTupleStruct = Seq;
 
-    type SerializeTupleVariant = Seq<'a>;
+    type SerializeTupleVariant = Seq;
 
-    type SerializeMap = Struct<'a>;
+    type SerializeMap = Struct;
 
-    type SerializeStruct = Struct<'a>;
+    type SerializeStruct = Struct;
 
-    type SerializeStructVariant = Struct<'a>;
+    type SerializeStructVariant = Struct;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Byte(v as i8));
-        Ok(())
+        Ok(Some(Value::Byte(v as i8)))
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Byte(v));
-        Ok(())
+        Ok(Some(Value::Byte(v)))
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Short(v));
-        Ok(())
+        Ok(Some(Value::Short(v)))
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Int(v));
-        Ok(())
+        Ok(Some(Value::Int(v)))
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Long(v));
-        Ok(())
+        Ok(Some(Value::Long(v)))
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
@@ -280,37 +346,34 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Float(v));
-        Ok(())
+        Ok(Some(Value::Float(v)))
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::Double(v));
-        Ok(())
+        Ok(Some(Value::Double(v)))
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        self.serialize_str(&v.to_string())
+        Ok(Some(Value::String(v.to_string())))
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::String(v.to_owned()));
-        Ok(())
+        Ok(Some(Value::String(v.to_owned())))
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        self.mutate_node(Value::ByteArray(v.iter().map(|num| *num as i8).collect()));
-        Ok(())
+        Ok(Some(Value::ByteArray(
+            v.iter().map(|num| *num as i8).collect(),
+        )))
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        self.pop_node();
-        Ok(())
+        Ok(None)
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(self)
     }
@@ -332,18 +395,24 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
         name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(self)
+        match array::marker_tag(name) {
+            Some(array) => value
+                .serialize(self)?
+                .map(|value| force_array(array, value))
+                .transpose(),
+            None => value.serialize(self),
+        }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         name: &'static str,
         variant_index: u32,
@@ -351,19 +420,24 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(self)
+        let value = value
+            .serialize(self)
+            .map_err(|err| within_variant(err, Some(variant)))?;
+        match value {
+            Some(value) => Ok(variant_of(variant, value)),
+            // written like a unit variant, which `from_nbt` reads back as an absent value
+            None => self.serialize_unit_variant(name, variant_index, variant),
+        }
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        self.push_array();
-        Ok(Seq { ser: self })
+        Ok(Seq::new(len))
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        self.push_array();
-        Ok(Seq { ser: self })
+        Ok(Seq::new(Some(len)))
     }
 
     fn serialize_tuple_struct(
@@ -371,8 +445,7 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        self.push_array();
-        Ok(Seq { ser: self })
+        Ok(Seq::new(Some(len)))
     }
 
     fn serialize_tuple_variant(
@@ -382,13 +455,13 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        self.push_array();
-        Ok(Seq { ser: self })
+        let mut seq = Seq::new(Some(len));
+        seq.variant = Some(variant);
+        Ok(seq)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        self.push_cmp();
-        Ok(Struct { ser: self })
+        Ok(Struct::new(len))
     }
 
     fn serialize_struct(
@@ -396,8 +469,7 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.push_cmp();
-        Ok(Struct { ser: self })
+        Ok(Struct::new(Some(len)))
     }
 
     fn serialize_struct_variant(
@@ -407,161 +479,141 @@ impl<'a> Serializer for &'a mut Obj2CmpSerializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        self.push_cmp();
-        Ok(Struct { ser: self })
+        let mut cmp = Struct::new(Some(len));
+        cmp.variant = Some(variant);
+        Ok(cmp)
     }
 }
 
-impl<'a> SerializeSeq for Seq<'a> {
-    type Ok = ();
+impl SerializeSeq for Seq {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_anonymous_node();
-        value.serialize(&mut self.ser.clone())
+        self.push(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a> SerializeTuple for Seq<'a> {
-    type Ok = ();
+impl SerializeTuple for Seq {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_anonymous_node();
-        value.serialize(&mut self.ser.clone())
+        self.push(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a> SerializeTupleVariant for Seq<'a> {
-    type Ok = ();
+impl SerializeTupleVariant for Seq {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_anonymous_node();
-        value.serialize(&mut self.ser.clone())
+        self.push(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a> SerializeTupleStruct for Seq<'a> {
-    type Ok = ();
+impl SerializeTupleStruct for Seq {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_anonymous_node();
-        value.serialize(&mut self.ser.clone())
+        self.push(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a> SerializeMap for Struct<'a> {
-    type Ok = ();
+impl SerializeMap for Struct {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_anonymous_node();
-        key.to_owned().serialize(&mut self.ser.clone())?;
-        let name = match self.ser.pop_node() {
-            CompoundNode::Unnamed(Value::String(str)) => str,
-            _ => return Err(SerError::InvalidKey),
-        };
-        self.ser.push_node(name);
-        Ok(())
+        match key.serialize(Obj2CmpSerializer)? {
+            Some(Value::String(str)) => {
+                self.key = Some(str);
+                Ok(())
+            }
+            _ => Err(SerErrorKind::InvalidKey.into()),
+        }
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut self.ser.clone())
+        let key = self.key.take().ok_or(SerErrorKind::InvalidKey)?;
+        self.insert(key, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a> SerializeStruct for Struct<'a> {
-    type Ok = ();
+impl SerializeStruct for Struct {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_node(key.to_owned());
-        value.serialize(&mut self.ser.clone())
+        self.insert(key.to_owned(), value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a> SerializeStructVariant for Struct<'a> {
-    type Ok = ();
+impl SerializeStructVariant for Struct {
+    type Ok = Option<Value>;
 
     type Error = SerError;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.ser.push_node(key.to_owned());
-        value.serialize(&mut self.ser.clone())
+        self.insert(key.to_owned(), value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pop_scope();
-        Ok(())
+        self.finish()
     }
 }
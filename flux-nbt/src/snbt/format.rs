@@ -0,0 +1,544 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, LowerExp, Write},
+    ops::Neg,
+};
+
+use nbt::{Map, Value};
+
+use super::parse::is_unquoted_char;
+
+/// Quotes used around string values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteStyle {
+    #[default]
+    Single,
+    Double,
+}
+
+impl QuoteStyle {
+    fn char(self) -> char {
+        match self {
+            QuoteStyle::Single => '\'',
+            QuoteStyle::Double => '"',
+        }
+    }
+}
+
+/// Game version SNBT is written for, see [`SnbtFormatter::with_target_version`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SnbtVersion {
+    /// Before 1.21.5, lists hold a single element type and text components are JSON strings
+    Legacy,
+    /// 1.21.5 and later, lists may mix element types and text components are stored as NBT
+    V1_21_5,
+}
+
+impl SnbtVersion {
+    /// Picks the SNBT version of the game version with the given `DataVersion`
+    pub fn from_data_version(data_version: i32) -> Self {
+        if data_version >= 4325 {
+            SnbtVersion::V1_21_5
+        } else {
+            SnbtVersion::Legacy
+        }
+    }
+}
+
+/// Kind of compound a value sits in, so text components are only converted where the game reads them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    /// Entities, block entities and anything else without a more specific location
+    Any,
+    /// Item components, from 1.20.5
+    Components,
+    /// The `display` compound of legacy items
+    Display,
+    /// `front_text` and `back_text` of signs
+    SignText,
+    /// Custom data and everything below it, which is never converted
+    CustomData,
+}
+
+impl Location {
+    /// Location of the value of an entry with the given key
+    fn child(self, key: &str) -> Location {
+        match (self, key.strip_prefix("minecraft:").unwrap_or(key)) {
+            (Location::CustomData, _) | (Location::Components, "custom_data") => {
+                Location::CustomData
+            }
+            (Location::Any, "components") => Location::Components,
+            (Location::Any, "display") => Location::Display,
+            (Location::Any, "front_text" | "back_text") => Location::SignText,
+            _ => Location::Any,
+        }
+    }
+
+    /// Whether the value of an entry with the given key is a text component, or a list of them
+    fn text_component(self, key: &str) -> Option<TextComponent> {
+        match (self, key.strip_prefix("minecraft:").unwrap_or(key)) {
+            (Location::Any, "CustomName")
+            | (Location::Components, "custom_name" | "item_name")
+            | (Location::Display, "Name") => Some(TextComponent::Single),
+            (Location::Components, "lore")
+            | (Location::Display, "Lore")
+            | (Location::SignText, "messages" | "filtered_messages") => Some(TextComponent::List),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextComponent {
+    Single,
+    List,
+}
+
+/// Configurable SNBT writer.
+///
+/// The default formatter writes everything on a single line without any whitespace,
+/// the same way [`to_string`](super::to_string) does. Setting an indent makes compounds
+/// span multiple lines, while lists and arrays stay inline until they exceed the max inline width.
+///
+/// SNBT has no literals for NaN and infinity, so they are written as `0` and the largest
+/// finite value of their sign, like casting them to an integer would.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnbtFormatter {
+    indent: usize,
+    sort_keys: bool,
+    max_inline_width: usize,
+    quote: QuoteStyle,
+    target: Option<SnbtVersion>,
+}
+
+impl Default for SnbtFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnbtFormatter {
+    /// Creates a formatter that writes minified SNBT
+    pub fn new() -> Self {
+        Self {
+            indent: 0,
+            sort_keys: false,
+            max_inline_width: 80,
+            quote: QuoteStyle::Single,
+            target: None,
+        }
+    }
+
+    /// Creates a formatter that writes multi-line SNBT indented with 4 spaces and sorted keys
+    pub fn pretty() -> Self {
+        Self::new().with_indent(4).with_sorted_keys(true)
+    }
+
+    /// Sets the amount of spaces per nesting level, `0` writes everything on a single line
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    pub fn with_sorted_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Sets the width in characters, including indentation, above which lists and arrays
+    /// are wrapped to one element per line
+    pub fn with_max_inline_width(mut self, width: usize) -> Self {
+        self.max_inline_width = width;
+        self
+    }
+
+    pub fn with_quote(mut self, quote: QuoteStyle) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Adapts the output to the SNBT of a game version, by default values are written as they are.
+    ///
+    /// - Lists mixing element types are written in the form 1.21.5 stores them in, with every
+    ///   element wrapped in a compound under the empty key, for legacy versions, and unwrapped
+    ///   again for 1.21.5
+    /// - Control characters in strings are escaped for 1.21.5, older versions can not read the escapes
+    /// - Text components are converted between JSON strings and NBT, values can hold either form.
+    ///   This only applies where the game reads text components: `CustomName`, the names and lore
+    ///   of item components and legacy `display` compounds, and sign messages. Nothing below
+    ///   `minecraft:custom_data` is converted
+    ///
+    /// Bytes are written as `1b` in every version, 1.21.5 only added `true` and `false` to the parser.
+    pub fn with_target_version(mut self, version: SnbtVersion) -> Self {
+        self.target = Some(version);
+        self
+    }
+
+    pub fn format(&self, value: &Value) -> String {
+        let mut out = String::new();
+        self.format_into(value, &mut out);
+        out
+    }
+
+    pub fn format_into(&self, value: &Value, into: &mut String) {
+        self.write(value, Location::Any, 0, self.indent == 0, into)
+            .expect("writing into a String never fails")
+    }
+
+    fn is_pretty(&self) -> bool {
+        self.indent > 0
+    }
+
+    fn separators(&self) -> (&'static str, &'static str) {
+        if self.is_pretty() {
+            (", ", ": ")
+        } else {
+            (",", ":")
+        }
+    }
+
+    fn newline(&self, depth: usize, into: &mut String) {
+        into.push('\n');
+        into.extend(std::iter::repeat_n(' ', depth * self.indent));
+    }
+
+    fn write(
+        &self,
+        value: &Value,
+        location: Location,
+        depth: usize,
+        inline: bool,
+        into: &mut String,
+    ) -> fmt::Result {
+        match value {
+            Value::Byte(v) => write!(into, "{v}b"),
+            Value::Short(v) => write!(into, "{v}s"),
+            Value::Int(v) => write!(into, "{v}"),
+            Value::Long(v) => write!(into, "{v}L"),
+            Value::Float(v) => write_decimal(*v, f32::MAX, 'f', into),
+            Value::Double(v) => write_decimal(*v, f64::MAX, 'd', into),
+            Value::String(st) => self.write_string(st, into),
+            Value::ByteArray(bytes) => {
+                self.write_seq("B;", bytes, depth, inline, into, &|v, _, into| {
+                    write!(into, "{v}b")
+                })
+            }
+            Value::IntArray(ints) => {
+                self.write_seq("I;", ints, depth, inline, into, &|v, _, into| {
+                    write!(into, "{v}")
+                })
+            }
+            Value::LongArray(longs) => {
+                self.write_seq("L;", longs, depth, inline, into, &|v, _, into| {
+                    write!(into, "{v}L")
+                })
+            }
+            Value::List(list) => {
+                let list = self.adapt_list(list);
+                self.write_seq("", &list, depth, inline, into, &|element, inline, into| {
+                    self.write(element, location, depth + 1, inline, into)
+                })
+            }
+            Value::Compound(cmp) => {
+                let mut entries: Vec<(&String, &Value)> = cmp.iter().collect();
+                if self.sort_keys {
+                    entries.sort_by_key(|(key, _)| *key);
+                }
+                let (comma, colon) = self.separators();
+                into.push('{');
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if inline {
+                        if index > 0 {
+                            into.push_str(comma);
+                        }
+                    } else {
+                        if index > 0 {
+                            into.push(',');
+                        }
+                        self.newline(depth + 1, into);
+                    }
+                    self.write_key(key, into)?;
+                    into.push_str(colon);
+                    let value = match (self.target, location.text_component(key)) {
+                        (Some(target), Some(kind)) => text_components(kind, value, target),
+                        _ => Cow::Borrowed(*value),
+                    };
+                    self.write(&value, location.child(key), depth + 1, inline, into)?;
+                }
+                if !inline && !entries.is_empty() {
+                    self.newline(depth, into);
+                }
+                into.push('}');
+                Ok(())
+            }
+        }
+    }
+
+    /// Quotes and escapes a string, switching to the other quote style if that avoids escaping
+    fn write_string(&self, st: &str, into: &mut String) -> fmt::Result {
+        let preferred = self.quote.char();
+        let other = match self.quote {
+            QuoteStyle::Single => '"',
+            QuoteStyle::Double => '\'',
+        };
+        let quote = if st.contains(preferred) && !st.contains(other) {
+            other
+        } else {
+            preferred
+        };
+        let escape_controls = self.target >= Some(SnbtVersion::V1_21_5);
+        into.push(quote);
+        for c in st.chars() {
+            match c {
+                '\\' => into.push_str("\\\\"),
+                c if c == quote => {
+                    into.push('\\');
+                    into.push(c);
+                }
+                '\u{8}' if escape_controls => into.push_str("\\b"),
+                '\u{c}' if escape_controls => into.push_str("\\f"),
+                '\n' if escape_controls => into.push_str("\\n"),
+                '\r' if escape_controls => into.push_str("\\r"),
+                '\t' if escape_controls => into.push_str("\\t"),
+                c if escape_controls && c.is_control() && (c as u32) < 0x100 => {
+                    write!(into, "\\x{:02x}", c as u32)?
+                }
+                c if escape_controls && c.is_control() => write!(into, "\\u{:04x}", c as u32)?,
+                c => into.push(c),
+            }
+        }
+        into.push(quote);
+        Ok(())
+    }
+
+    /// Wraps the elements of lists mixing element types for legacy versions, and unwraps them for 1.21.5
+    fn adapt_list<'v>(&self, list: &'v [Value]) -> Cow<'v, [Value]> {
+        match self.target {
+            Some(SnbtVersion::Legacy)
+                if list.iter().any(|element| element.id() != list[0].id()) =>
+            {
+                Cow::Owned(list.iter().map(wrap_element).collect())
+            }
+            Some(SnbtVersion::V1_21_5)
+                if list.iter().any(|element| unwrapped(element).is_some()) =>
+            {
+                Cow::Owned(
+                    list.iter()
+                        .map(|element| unwrapped(element).unwrap_or(element).clone())
+                        .collect(),
+                )
+            }
+            _ => Cow::Borrowed(list),
+        }
+    }
+
+    /// Writes keys unquoted when they only consist of characters allowed in unquoted SNBT
+    fn write_key(&self, key: &str, into: &mut String) -> fmt::Result {
+        if !key.is_empty() && key.chars().all(is_unquoted_char) {
+            into.push_str(key);
+            Ok(())
+        } else {
+            self.write_string(key, into)
+        }
+    }
+
+    /// Writes a list or an array inline if it fits into the max inline width, otherwise
+    /// one element per line
+    fn write_seq<T>(
+        &self,
+        prefix: &str,
+        elements: &[T],
+        depth: usize,
+        inline: bool,
+        into: &mut String,
+        write_element: &dyn Fn(&T, bool, &mut String) -> fmt::Result,
+    ) -> fmt::Result {
+        if !inline && !elements.is_empty() {
+            let start = into.len();
+            self.write_seq(prefix, elements, depth, true, into, write_element)?;
+            if self.fits(depth, &into[start..]) {
+                return Ok(());
+            }
+            into.truncate(start);
+            into.push('[');
+            into.push_str(prefix);
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    into.push(',');
+                }
+                self.newline(depth + 1, into);
+                write_element(element, false, into)?;
+            }
+            self.newline(depth, into);
+            into.push(']');
+            return Ok(());
+        }
+        let (comma, _) = self.separators();
+        into.push('[');
+        into.push_str(prefix);
+        for (index, element) in elements.iter().enumerate() {
+            if index > 0 {
+                into.push_str(comma);
+            } else if !prefix.is_empty() && self.is_pretty() {
+                into.push(' ');
+            }
+            write_element(element, true, into)?;
+        }
+        into.push(']');
+        Ok(())
+    }
+
+    fn fits(&self, depth: usize, inline_form: &str) -> bool {
+        depth * self.indent + inline_form.chars().count() <= self.max_inline_width
+    }
+}
+
+/// The value of a compound with the empty key as its only entry, which 1.21.5 wraps list elements in
+/// Writes a finite decimal in exponent form outside of `1e-3..1e7`, like Java's `toString` does
+fn write_decimal<F>(v: F, max: F, suffix: char, into: &mut String) -> fmt::Result
+where
+    F: Copy + Default + PartialOrd + Neg<Output = F> + Into<f64> + Display + LowerExp,
+{
+    let v = match v {
+        v if v.into().is_nan() => F::default(),
+        v if v > max => max,
+        v if v < -max => -max,
+        v => v,
+    };
+    let abs = v.into().abs();
+    if abs != 0.0 && !(1e-3..1e7).contains(&abs) {
+        write!(into, "{v:e}{suffix}")
+    } else {
+        write!(into, "{v}{suffix}")
+    }
+}
+
+fn unwrapped(element: &Value) -> Option<&Value> {
+    match element {
+        Value::Compound(cmp) if cmp.len() == 1 => cmp.get(""),
+        _ => None,
+    }
+}
+
+pub(super) fn wrap_element(element: &Value) -> Value {
+    match element {
+        Value::Compound(_) if unwrapped(element).is_none() => element.clone(),
+        _ => Value::Compound(Map::from([(String::new(), element.clone())])),
+    }
+}
+
+/// Converts a text component, or a list of them, to the form of the target version
+fn text_components(kind: TextComponent, value: &Value, target: SnbtVersion) -> Cow<'_, Value> {
+    match (kind, value) {
+        (TextComponent::Single, _) => text_component(value, target),
+        (TextComponent::List, Value::List(list)) => Cow::Owned(Value::List(
+            list.iter()
+                .map(|element| text_component(element, target).into_owned())
+                .collect(),
+        )),
+        (TextComponent::List, _) => Cow::Borrowed(value),
+    }
+}
+
+fn text_component(value: &Value, target: SnbtVersion) -> Cow<'_, Value> {
+    let json = match value {
+        Value::String(st) => legacy_json(st, target == SnbtVersion::Legacy),
+        _ => None,
+    };
+    match (target, json) {
+        (SnbtVersion::Legacy, Some(_)) => Cow::Borrowed(value),
+        (SnbtVersion::Legacy, None) => {
+            let mut out = String::new();
+            write_json(value, &mut out).expect("writing into a String never fails");
+            Cow::Owned(Value::String(out))
+        }
+        (SnbtVersion::V1_21_5, Some(json)) => Cow::Owned(json_to_nbt(json)),
+        (SnbtVersion::V1_21_5, None) => Cow::Borrowed(value),
+    }
+}
+
+/// Parses a string holding a text component in the JSON form used before 1.21.5.
+///
+/// Only objects, arrays and, if `strings` is set, strings count, plain text like `123` is a
+/// component on its own. JSON strings are left out when converting to 1.21.5, where text like
+/// `"Quoted Name"` is more likely a plain string component than JSON.
+fn legacy_json(st: &str, strings: bool) -> Option<serde_json::Value> {
+    let json = st.starts_with(['{', '[']) || (strings && st.starts_with('"'));
+    if !json {
+        return None;
+    }
+    serde_json::from_str(st)
+        .ok()
+        .filter(|json: &serde_json::Value| !json.is_number() && !json.is_boolean())
+}
+
+fn json_to_nbt(json: serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::String(String::new()),
+        serde_json::Value::Bool(flag) => Value::Byte(flag as i8),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(int) => i32::try_from(int).map_or(Value::Long(int), Value::Int),
+            None => Value::Double(number.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(st) => Value::String(st),
+        serde_json::Value::Array(array) => {
+            Value::List(array.into_iter().map(json_to_nbt).collect())
+        }
+        serde_json::Value::Object(object) => Value::Compound(
+            object
+                .into_iter()
+                .map(|(key, value)| (key, json_to_nbt(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Writes a text component as JSON, bytes are the booleans of text components
+fn write_json(value: &Value, into: &mut String) -> fmt::Result {
+    fn write_all<T>(
+        elements: &[T],
+        into: &mut String,
+        write: impl Fn(&T, &mut String) -> fmt::Result,
+    ) -> fmt::Result {
+        into.push('[');
+        for (index, element) in elements.iter().enumerate() {
+            if index > 0 {
+                into.push(',');
+            }
+            write(element, into)?;
+        }
+        into.push(']');
+        Ok(())
+    }
+
+    match value {
+        Value::Byte(0) => write!(into, "false"),
+        Value::Byte(1) => write!(into, "true"),
+        Value::Byte(v) => write!(into, "{v}"),
+        Value::Short(v) => write!(into, "{v}"),
+        Value::Int(v) => write!(into, "{v}"),
+        Value::Long(v) => write!(into, "{v}"),
+        Value::Float(v) if v.is_finite() => write!(into, "{v}"),
+        Value::Double(v) if v.is_finite() => write!(into, "{v}"),
+        Value::Float(_) | Value::Double(_) => write!(into, "0"),
+        Value::String(st) => write!(into, "{}", serde_json::Value::from(st.as_str())),
+        Value::ByteArray(bytes) => write_all(bytes, into, |v, into| write!(into, "{v}")),
+        Value::IntArray(ints) => write_all(ints, into, |v, into| write!(into, "{v}")),
+        Value::LongArray(longs) => write_all(longs, into, |v, into| write!(into, "{v}")),
+        Value::List(list) => write_all(list, into, |element, into| {
+            write_json(unwrapped(element).unwrap_or(element), into)
+        }),
+        Value::Compound(cmp) => {
+            into.push('{');
+            for (index, (key, value)) in cmp.iter().enumerate() {
+                if index > 0 {
+                    into.push(',');
+                }
+                write!(into, "{}:", serde_json::Value::from(key.as_str()))?;
+                write_json(value, into)?;
+            }
+            into.push('}');
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::fmt::Display;
+
+use nbt::{Map, Value};
+use thiserror::Error;
+
+use super::format::wrap_element;
+use crate::stream::{tag, ReadLimits};
+
+/// Most compounds and lists that may be open at once, the same as the game and
+/// the default [`ReadLimits`]
+pub const MAX_DEPTH: usize = 512;
+
+/// Parses a single SNBT value, e.g. `{CustomModelData:5,display:{Name:'"Hello"'}}`.
+///
+/// Trailing whitespace is allowed, anything else after the value is an error. Nesting is limited
+/// to the default [`ReadLimits`], see [`from_str_with_limits`] for untrusted input.
+pub fn from_str(snbt: &str) -> Result<Value, SnbtError> {
+    from_str_with_limits(snbt, ReadLimits::default())
+}
+
+/// Parses a single SNBT value within limits, the same ones binary NBT is read with.
+///
+/// The size limit applies to the length of the SNBT in bytes, and the length limit to the
+/// elements of every list and array.
+pub fn from_str_with_limits(snbt: &str, limits: ReadLimits) -> Result<Value, SnbtError> {
+    let mut parser = SnbtParser::new(snbt, limits);
+    if snbt.len() as u64 > limits.max_bytes() {
+        return Err(parser.error(SnbtErrorKind::SizeLimitExceeded(limits.max_bytes())));
+    }
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(value),
+        Some(found) => Err(parser.error(SnbtErrorKind::TrailingData(found))),
+    }
+}
+
+/// Parses the SNBT compound at the start of `src`, returning it along with the amount of bytes it spans
+pub(crate) fn compound_prefix(src: &str) -> Result<(Map<String, Value>, usize), SnbtError> {
+    let mut parser = SnbtParser::new(src, ReadLimits::default());
+    let cmp = parser.parse_compound()?;
+    Ok((cmp, parser.pos))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{kind} at line {line}, column {column}")]
+pub struct SnbtError {
+    /// 1-based line of the offending character
+    pub line: usize,
+    /// 1-based column of the offending character, counted in chars
+    pub column: usize,
+    pub kind: SnbtErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SnbtErrorKind {
+    #[error("Expected {expected}, found {}", Found(.found))]
+    Expected {
+        expected: &'static str,
+        found: Option<char>,
+    },
+    #[error("Invalid escape sequence `\\{0}`")]
+    InvalidEscape(char),
+    #[error("Invalid unicode escape `{0}`")]
+    InvalidUnicode(String),
+    #[error("Can not insert {found} into {array}")]
+    InvalidArrayElement {
+        array: &'static str,
+        found: &'static str,
+    },
+    #[error("Unexpected trailing data starting with `{0}`")]
+    TrailingData(char),
+    #[error("Nesting is deeper than the limit of {0}")]
+    DepthLimitExceeded(usize),
+    #[error("SNBT is larger than the limit of {0} bytes")]
+    SizeLimitExceeded(u64),
+    #[error("Length is larger than the limit of {0}")]
+    LengthLimitExceeded(usize),
+}
+
+struct Found<'a>(&'a Option<char>);
+
+impl<'a> Display for Found<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(c) => f.write_fmt(format_args!("`{c}`")),
+            None => f.write_str("end of input"),
+        }
+    }
+}
+
+struct SnbtParser<'a> {
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+    limits: ReadLimits,
+}
+
+impl<'a> SnbtParser<'a> {
+    fn new(src: &'a str, limits: ReadLimits) -> Self {
+        Self {
+            src,
+            pos: 0,
+            depth: 0,
+            limits,
+        }
+    }
+
+    fn error(&self, kind: SnbtErrorKind) -> SnbtError {
+        self.error_at(self.pos, kind)
+    }
+
+    fn error_at(&self, pos: usize, kind: SnbtErrorKind) -> SnbtError {
+        let before = &self.src[..pos];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+        SnbtError { line, column, kind }
+    }
+
+    fn expected(&self, expected: &'static str) -> SnbtError {
+        self.error(SnbtErrorKind::Expected {
+            expected,
+            found: self.peek(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.src[self.pos..].chars().nth(n)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.pos += next.len_utf8();
+        Some(next)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.expected(expected))
+        }
+    }
+
+    /// Consumes a `,` separator if present, returning whether one was found
+    fn separator(&mut self) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(',') {
+            self.next();
+            self.skip_whitespace();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Enters a compound or a list, failing before the recursion gets deep enough to overflow the stack
+    fn descend(&mut self) -> Result<(), SnbtError> {
+        let limit = self.limits.max_depth();
+        if self.depth >= limit {
+            return Err(self.error(SnbtErrorKind::DepthLimitExceeded(limit)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Fails before a list or array with `len` elements gets another one
+    fn check_len(&self, start: usize, len: usize) -> Result<(), SnbtError> {
+        let limit = self.limits.max_len();
+        if len >= limit {
+            return Err(self.error_at(start, SnbtErrorKind::LengthLimitExceeded(limit)));
+        }
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Value, SnbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_compound().map(Value::Compound),
+            Some('[') => self.parse_list_or_array(),
+            Some('"' | '\'') => self.parse_quoted().map(Value::String),
+            Some(c) if is_unquoted_char(c) => {
+                let raw = self.parse_unquoted();
+                Ok(type_unquoted(raw))
+            }
+            _ => Err(self.expected("value")),
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<String, SnbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"' | '\'') => self.parse_quoted(),
+            Some(c) if is_unquoted_char(c) => Ok(self.parse_unquoted().to_owned()),
+            _ => Err(self.expected("key")),
+        }
+    }
+
+    fn parse_unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if is_unquoted_char(c)) {
+            self.next();
+        }
+        &self.src[start..self.pos]
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, SnbtError> {
+        let quote = self.next().unwrap();
+        let mut out = String::new();
+        loop {
+            let escape_start = self.pos;
+            match self.next() {
+                None => return Err(self.expected("closing quote")),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some(c @ ('\\' | '\'' | '"')) => c,
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('s') => ' ',
+                        Some('t') => '\t',
+                        Some('x') => self.parse_unicode_escape(escape_start, 2)?,
+                        Some('u') => self.parse_unicode_escape(escape_start, 4)?,
+                        Some('U') => self.parse_unicode_escape(escape_start, 8)?,
+                        Some(other) => {
+                            return Err(
+                                self.error_at(escape_start, SnbtErrorKind::InvalidEscape(other))
+                            )
+                        }
+                        None => return Err(self.expected("escaped character")),
+                    };
+                    out.push(escaped)
+                }
+                Some(c) if c == quote => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self, start: usize, digits: usize) -> Result<char, SnbtError> {
+        for _ in 0..digits {
+            match self.peek() {
+                Some(c) if c.is_ascii_hexdigit() => {
+                    self.next();
+                }
+                _ => break,
+            }
+        }
+        let escape = &self.src[start..self.pos];
+        u32::from_str_radix(&escape[2..], 16)
+            .ok()
+            .filter(|_| escape.len() == digits + 2)
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error_at(start, SnbtErrorKind::InvalidUnicode(escape.to_owned())))
+    }
+
+    fn parse_compound(&mut self) -> Result<Map<String, Value>, SnbtError> {
+        self.expect('{', "`{`")?;
+        self.descend()?;
+        let mut cmp = Map::new();
+        self.skip_whitespace();
+        while self.peek() != Some('}') {
+            let key = self.parse_key()?;
+            self.expect(':', "`:`")?;
+            let value = self.parse_value()?;
+            cmp.insert(key, value);
+            if !self.separator() {
+                break;
+            }
+        }
+        self.expect('}', "`,` or `}`")?;
+        self.depth -= 1;
+        Ok(cmp)
+    }
+
+    fn parse_list_or_array(&mut self) -> Result<Value, SnbtError> {
+        self.expect('[', "`[`")?;
+        let array = match (self.peek(), self.peek_nth(1)) {
+            (Some(ty @ ('B' | 'I' | 'L')), Some(';')) => ty,
+            _ => return self.parse_list(),
+        };
+        self.next();
+        self.next();
+        self.skip_whitespace();
+        let (array_tag, range) = match array {
+            'B' => (tag::BYTE_ARRAY, i8::MIN as i64..=i8::MAX as i64),
+            'I' => (tag::INT_ARRAY, i32::MIN as i64..=i32::MAX as i64),
+            _ => (tag::LONG_ARRAY, i64::MIN..=i64::MAX),
+        };
+        let mut elements = Vec::new();
+        while self.peek() != Some(']') {
+            let start = self.pos;
+            self.check_len(start, elements.len())?;
+            let element = self.parse_value()?;
+            let number = match element {
+                Value::Byte(v) => Some(v as i64),
+                Value::Short(v) => Some(v as i64),
+                Value::Int(v) => Some(v as i64),
+                Value::Long(v) => Some(v),
+                _ => None,
+            };
+            match number.filter(|number| range.contains(number)) {
+                Some(number) => elements.push(number),
+                None => {
+                    return Err(self.error_at(
+                        start,
+                        SnbtErrorKind::InvalidArrayElement {
+                            array: tag::name(array_tag),
+                            found: tag::name(element.id()),
+                        },
+                    ))
+                }
+            }
+            if !self.separator() {
+                break;
+            }
+        }
+        self.expect(']', "`,` or `]`")?;
+        Ok(match array {
+            'B' => Value::ByteArray(elements.into_iter().map(|v| v as i8).collect()),
+            'I' => Value::IntArray(elements.into_iter().map(|v| v as i32).collect()),
+            _ => Value::LongArray(elements),
+        })
+    }
+
+    fn parse_list(&mut self) -> Result<Value, SnbtError> {
+        self.descend()?;
+        let mut elements: Vec<Value> = Vec::new();
+        self.skip_whitespace();
+        while self.peek() != Some(']') {
+            let start = self.pos;
+            self.check_len(start, elements.len())?;
+            elements.push(self.parse_value()?);
+            if !self.separator() {
+                break;
+            }
+        }
+        self.expect(']', "`,` or `]`")?;
+        self.depth -= 1;
+        // mixed lists hold compounds, with other elements wrapped in `{'': element}` like the game does
+        if elements
+            .iter()
+            .any(|element| element.id() != elements[0].id())
+        {
+            elements = elements.iter().map(wrap_element).collect();
+        }
+        Ok(Value::List(elements))
+    }
+}
+
+pub(super) fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+/// Matches `[-+]?(?:0|[1-9][0-9]*)`
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    match digits.as_bytes() {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// Matches `[-+]?(?:[0-9]+[.]?|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?`, requiring
+/// a dot when `require_fraction` is set
+fn is_decimal(s: &str, require_fraction: bool) -> bool {
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+        None => (s, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    let mantissa_ok = digits(int)
+        && frac.map(digits).unwrap_or(true)
+        && !(int.is_empty() && frac.map(str::is_empty).unwrap_or(true));
+    let exponent_ok = match exponent {
+        Some(exp) => {
+            let exp = exp.strip_prefix(['-', '+']).unwrap_or(exp);
+            !exp.is_empty() && digits(exp)
+        }
+        None => true,
+    };
+    mantissa_ok && exponent_ok && (!require_fraction || frac.is_some())
+}
+
+/// Guesses the type of an unquoted token the same way the game does, falling back
+/// to a string if the token is not a valid number
+fn type_unquoted(raw: &str) -> Value {
+    if raw.eq_ignore_ascii_case("true") {
+        return Value::Byte(1);
+    } else if raw.eq_ignore_ascii_case("false") {
+        return Value::Byte(0);
+    }
+    let typed = match raw.char_indices().last() {
+        Some((idx, suffix)) if idx > 0 => {
+            let body = &raw[..idx];
+            match suffix.to_ascii_lowercase() {
+                'b' if is_integer(body) => body.parse().ok().map(Value::Byte),
+                's' if is_integer(body) => body.parse().ok().map(Value::Short),
+                'l' if is_integer(body) => body.parse().ok().map(Value::Long),
+                'f' if is_decimal(body, false) => body.parse().ok().map(Value::Float),
+                'd' if is_decimal(body, false) => body.parse().ok().map(Value::Double),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    typed
+        .or_else(|| {
+            if is_integer(raw) {
+                raw.parse().ok().map(Value::Int)
+            } else if is_decimal(raw, true) {
+                raw.parse().ok().map(Value::Double)
+            } else {
+                None
+            }
+        })
+        .unwrap_or_else(|| Value::String(raw.to_owned()))
+}
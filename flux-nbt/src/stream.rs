@@ -0,0 +1,273 @@
+use std::io::{BufReader, Read, Write};
+
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+use nbt::Value;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::{
+    de::{from_nbt, DeError},
+    ser::{PathSegment, SerError, SerErrorKind},
+};
+
+mod encoding;
+mod pull;
+mod ser;
+
+pub use encoding::Encoding;
+pub use pull::{Event, NbtReader, ReadLimits};
+pub use ser::Serializer;
+
+use ser::write_root;
+
+pub(crate) mod tag {
+    pub const END: u8 = 0;
+    pub const BYTE: u8 = 1;
+    pub const SHORT: u8 = 2;
+    pub const INT: u8 = 3;
+    pub const LONG: u8 = 4;
+    pub const FLOAT: u8 = 5;
+    pub const DOUBLE: u8 = 6;
+    pub const BYTE_ARRAY: u8 = 7;
+    pub const STRING: u8 = 8;
+    pub const LIST: u8 = 9;
+    pub const COMPOUND: u8 = 10;
+    pub const INT_ARRAY: u8 = 11;
+    pub const LONG_ARRAY: u8 = 12;
+
+    pub fn name(tag: u8) -> &'static str {
+        match tag {
+            END => "TAG_End",
+            BYTE => "TAG_Byte",
+            SHORT => "TAG_Short",
+            INT => "TAG_Int",
+            LONG => "TAG_Long",
+            FLOAT => "TAG_Float",
+            DOUBLE => "TAG_Double",
+            BYTE_ARRAY => "TAG_Byte_Array",
+            STRING => "TAG_String",
+            LIST => "TAG_List",
+            COMPOUND => "TAG_Compound",
+            INT_ARRAY => "TAG_Int_Array",
+            LONG_ARRAY => "TAG_Long_Array",
+            _ => "an unknown tag",
+        }
+    }
+}
+
+/// Serializes an object as binary NBT straight into the `writer`, without building a [`Value`] first
+pub fn to_writer<W: Write, T: Serialize>(
+    writer: W,
+    obj: &T,
+    options: &NbtOptions,
+) -> Result<(), StreamError> {
+    options.frame_writer(writer, |writer| {
+        obj.serialize(
+            &mut Serializer::new(writer)
+                .with_root_name(&options.root_name)
+                .with_encoding(options.encoding),
+        )
+    })
+}
+
+/// Serializes an object as binary NBT into a new buffer
+pub fn to_bytes<T: Serialize>(obj: &T, options: &NbtOptions) -> Result<Vec<u8>, StreamError> {
+    let mut out = Vec::with_capacity(128);
+    to_writer(&mut out, obj, options)?;
+    Ok(out)
+}
+
+/// Writes an already built [`Value`] as binary NBT into the `writer`
+pub fn write_value<W: Write>(
+    writer: W,
+    value: &Value,
+    options: &NbtOptions,
+) -> Result<(), StreamError> {
+    options.frame_writer(writer, |writer| {
+        write_root(writer, options.encoding, &options.root_name, value)
+    })
+}
+
+/// Reads a binary NBT value from the `reader`, returning the root name alongside it.
+///
+/// The root name is empty for encodings without one.
+pub fn read_value<R: Read>(
+    reader: R,
+    options: &NbtOptions,
+) -> Result<(String, Value), StreamError> {
+    let (name, value) = pull_reader(reader, options)
+        .read_value()?
+        .ok_or(StreamError::InvalidRoot(tag::name(tag::END)))?;
+    Ok((name.unwrap_or_default(), value))
+}
+
+/// Creates a buffered [`NbtReader`] that decompresses the `reader` according to the options
+pub fn pull_reader<'r, R: Read + 'r>(
+    reader: R,
+    options: &NbtOptions,
+) -> NbtReader<Box<dyn Read + 'r>> {
+    let reader: Box<dyn Read + 'r> = match options.compression {
+        Compression::None => Box::new(BufReader::new(reader)),
+        Compression::Gzip => Box::new(BufReader::new(GzDecoder::new(reader))),
+        Compression::Zlib => Box::new(BufReader::new(ZlibDecoder::new(reader))),
+    };
+    NbtReader::new(reader, options.encoding).with_limits(options.limits)
+}
+
+/// Reads binary NBT from the `reader` and deserializes it into a `T`.
+///
+/// The whole tree is read into a [`Value`] first and deserialized with
+/// [`from_nbt`](crate::de::from_nbt), so it is held in memory twice at its peak.
+/// Use [`pull_reader`] to process large files event by event instead.
+pub fn from_reader_buffered<R: Read, T: DeserializeOwned>(
+    reader: R,
+    options: &NbtOptions,
+) -> Result<T, StreamError> {
+    let (_, value) = read_value(reader, options)?;
+    Ok(from_nbt(&value)?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zlib,
+}
+
+/// Framing and encoding of binary NBT data
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NbtOptions {
+    root_name: String,
+    compression: Compression,
+    encoding: Encoding,
+    limits: ReadLimits,
+}
+
+impl NbtOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_root_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.root_name = name.into();
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Limits applied when reading, only nesting is limited by default
+    pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn root_name(&self) -> &str {
+        &self.root_name
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn limits(&self) -> ReadLimits {
+        self.limits
+    }
+
+    fn frame_writer<W: Write, F>(&self, writer: W, write: F) -> Result<(), StreamError>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<(), StreamError>,
+    {
+        match self.compression {
+            Compression::None => {
+                let mut writer = std::io::BufWriter::new(writer);
+                write(&mut writer)?;
+                writer.flush()?;
+            }
+            Compression::Gzip => {
+                let mut writer = GzEncoder::new(writer, flate2::Compression::default());
+                write(&mut writer)?;
+                writer.finish()?;
+            }
+            Compression::Zlib => {
+                let mut writer = ZlibEncoder::new(writer, flate2::Compression::default());
+                write(&mut writer)?;
+                writer.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("An IO error has occurred: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The root of an NBT file can not be {0}")]
+    InvalidRoot(&'static str),
+    /// The value can not be represented as NBT, the same way [`to_nbt`](crate::ser::to_nbt) fails
+    #[error(transparent)]
+    Ser(#[from] SerError),
+    #[error("A sequence declared {declared} elements, but {actual} were serialized")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error("String of {0} encoded bytes is longer than the limit of 65535 bytes")]
+    StringTooLong(usize),
+    #[error("Length {0} does not fit into a length prefix")]
+    TooLong(usize),
+    #[error("Encountered a varint longer than {0} bytes")]
+    VarIntTooLong(usize),
+    #[error("Encountered an unknown tag id {0}")]
+    UnknownTag(u8),
+    #[error("Encountered a negative length {0}")]
+    NegativeLength(i32),
+    #[error("NBT is nested deeper than the limit of {0}")]
+    DepthLimitExceeded(usize),
+    #[error("NBT is larger than the limit of {0} bytes")]
+    SizeLimitExceeded(u64),
+    #[error("Length {len} is larger than the limit of {limit}")]
+    LengthLimitExceeded { len: usize, limit: usize },
+    #[error("Encountered a string that is not valid (modified) UTF-8")]
+    InvalidString,
+    #[error("Failed to deserialize the value: {0}")]
+    De(#[from] DeError),
+}
+
+impl StreamError {
+    /// Prefixes the path of a serialization error, any other error has no path
+    fn within(self, segment: PathSegment) -> Self {
+        match self {
+            StreamError::Ser(err) => StreamError::Ser(err.within(segment)),
+            err => err,
+        }
+    }
+}
+
+impl From<SerErrorKind> for StreamError {
+    fn from(kind: SerErrorKind) -> Self {
+        StreamError::Ser(kind.into())
+    }
+}
+
+impl serde::ser::Error for StreamError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        SerErrorKind::Custom(msg.to_string()).into()
+    }
+}
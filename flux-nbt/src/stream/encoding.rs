@@ -0,0 +1,317 @@
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+};
+
+use super::{tag, StreamError};
+
+/// Binary layout of the NBT primitives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Big-endian NBT used by Java Edition files and protocol
+    #[default]
+    Java,
+    /// Big-endian NBT without a root name, which the Java Edition protocol uses since 1.20.2.
+    ///
+    /// The root may be a tag of any type, e.g. a plain string for text components.
+    JavaNetwork,
+    /// Little-endian NBT used by Bedrock Edition files, e.g. `.mcstructure` and `level.dat`
+    BedrockLittleEndian,
+    /// Little-endian NBT with zigzag varint ints, longs and lengths, used by the Bedrock Edition protocol
+    BedrockNetwork,
+}
+
+macro_rules! fixed_width {
+    ($($write:ident, $read:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub(crate) fn $write<W: Write + ?Sized>(self, writer: &mut W, v: $ty) -> Result<(), StreamError> {
+                match self {
+                    Encoding::Java | Encoding::JavaNetwork => writer.write_all(&v.to_be_bytes())?,
+                    _ => writer.write_all(&v.to_le_bytes())?,
+                }
+                Ok(())
+            }
+
+            pub(crate) fn $read<R: Read + ?Sized>(self, reader: &mut R) -> Result<$ty, StreamError> {
+                let mut buf = [0u8; std::mem::size_of::<$ty>()];
+                reader.read_exact(&mut buf)?;
+                Ok(match self {
+                    Encoding::Java | Encoding::JavaNetwork => <$ty>::from_be_bytes(buf),
+                    _ => <$ty>::from_le_bytes(buf),
+                })
+            }
+        )*
+    };
+}
+
+impl Encoding {
+    pub fn has_root_name(self) -> bool {
+        !matches!(self, Encoding::JavaNetwork)
+    }
+
+    fixed_width! {
+        write_short, read_short: i16,
+        write_float, read_float: f32,
+        write_double, read_double: f64,
+    }
+
+    /// Size of a payload in bytes, if it is the same for every value of the tag
+    pub(crate) fn fixed_size(self, tag: u8) -> Option<usize> {
+        match tag {
+            tag::BYTE => Some(1),
+            tag::SHORT => Some(2),
+            tag::FLOAT => Some(4),
+            tag::DOUBLE => Some(8),
+            tag::INT if self != Encoding::BedrockNetwork => Some(4),
+            tag::LONG if self != Encoding::BedrockNetwork => Some(8),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn write_byte<W: Write + ?Sized>(
+        self,
+        writer: &mut W,
+        v: i8,
+    ) -> Result<(), StreamError> {
+        writer.write_all(&[v as u8])?;
+        Ok(())
+    }
+
+    pub(crate) fn read_byte<R: Read + ?Sized>(self, reader: &mut R) -> Result<i8, StreamError> {
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf)?;
+        Ok(buf[0] as i8)
+    }
+
+    pub(crate) fn write_int<W: Write + ?Sized>(
+        self,
+        writer: &mut W,
+        v: i32,
+    ) -> Result<(), StreamError> {
+        match self {
+            Encoding::Java | Encoding::JavaNetwork => writer.write_all(&v.to_be_bytes())?,
+            Encoding::BedrockLittleEndian => writer.write_all(&v.to_le_bytes())?,
+            Encoding::BedrockNetwork => write_varint(writer, ((v << 1) ^ (v >> 31)) as u32 as u64)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_int<R: Read + ?Sized>(self, reader: &mut R) -> Result<i32, StreamError> {
+        let mut buf = [0u8; 4];
+        match self {
+            Encoding::Java | Encoding::JavaNetwork => {
+                reader.read_exact(&mut buf)?;
+                Ok(i32::from_be_bytes(buf))
+            }
+            Encoding::BedrockLittleEndian => {
+                reader.read_exact(&mut buf)?;
+                Ok(i32::from_le_bytes(buf))
+            }
+            Encoding::BedrockNetwork => {
+                let raw = read_varint(reader, 5)? as u32;
+                Ok((raw >> 1) as i32 ^ -((raw & 1) as i32))
+            }
+        }
+    }
+
+    pub(crate) fn write_long<W: Write + ?Sized>(
+        self,
+        writer: &mut W,
+        v: i64,
+    ) -> Result<(), StreamError> {
+        match self {
+            Encoding::Java | Encoding::JavaNetwork => writer.write_all(&v.to_be_bytes())?,
+            Encoding::BedrockLittleEndian => writer.write_all(&v.to_le_bytes())?,
+            Encoding::BedrockNetwork => write_varint(writer, ((v << 1) ^ (v >> 63)) as u64)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_long<R: Read + ?Sized>(self, reader: &mut R) -> Result<i64, StreamError> {
+        let mut buf = [0u8; 8];
+        match self {
+            Encoding::Java | Encoding::JavaNetwork => {
+                reader.read_exact(&mut buf)?;
+                Ok(i64::from_be_bytes(buf))
+            }
+            Encoding::BedrockLittleEndian => {
+                reader.read_exact(&mut buf)?;
+                Ok(i64::from_le_bytes(buf))
+            }
+            Encoding::BedrockNetwork => {
+                let raw = read_varint(reader, 10)?;
+                Ok((raw >> 1) as i64 ^ -((raw & 1) as i64))
+            }
+        }
+    }
+
+    /// Writes the length prefix of a list or an array, which is encoded like an int
+    pub(crate) fn write_len<W: Write + ?Sized>(
+        self,
+        writer: &mut W,
+        len: usize,
+    ) -> Result<(), StreamError> {
+        let len = i32::try_from(len).map_err(|_| StreamError::TooLong(len))?;
+        self.write_int(writer, len)
+    }
+
+    pub(crate) fn read_len<R: Read + ?Sized>(self, reader: &mut R) -> Result<usize, StreamError> {
+        let len = self.read_int(reader)?;
+        usize::try_from(len).map_err(|_| StreamError::NegativeLength(len))
+    }
+
+    /// Writes a string, in modified UTF-8 for Java Edition and UTF-8 for Bedrock Edition
+    pub(crate) fn write_string<W: Write + ?Sized>(
+        self,
+        writer: &mut W,
+        str: &str,
+    ) -> Result<(), StreamError> {
+        let bytes = match self {
+            Encoding::Java | Encoding::JavaNetwork => encode_mutf8(str),
+            _ => Cow::Borrowed(str.as_bytes()),
+        };
+        match self {
+            Encoding::BedrockNetwork => {
+                let len =
+                    u32::try_from(bytes.len()).map_err(|_| StreamError::TooLong(bytes.len()))?;
+                write_varint(writer, len as u64)?;
+            }
+            _ => {
+                let len = u16::try_from(bytes.len())
+                    .map_err(|_| StreamError::StringTooLong(bytes.len()))?;
+                self.write_short(writer, len as i16)?;
+            }
+        }
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Decodes the bytes of a string read after its length
+    pub(crate) fn decode_string(self, bytes: Vec<u8>) -> Result<String, StreamError> {
+        match self {
+            Encoding::Java | Encoding::JavaNetwork => decode_mutf8(bytes),
+            _ => String::from_utf8(bytes).map_err(|_| StreamError::InvalidString),
+        }
+    }
+
+    pub(crate) fn read_string_len<R: Read + ?Sized>(
+        self,
+        reader: &mut R,
+    ) -> Result<usize, StreamError> {
+        Ok(match self {
+            Encoding::BedrockNetwork => read_varint(reader, 5)? as u32 as usize,
+            _ => self.read_short(reader)? as u16 as usize,
+        })
+    }
+}
+
+/// Reads exactly `len` bytes without trusting `len` for the initial allocation
+pub(crate) fn read_exact_vec<R: Read + ?Sized>(
+    reader: &mut R,
+    len: usize,
+) -> Result<Vec<u8>, StreamError> {
+    let mut bytes = Vec::with_capacity(len.min(4096));
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(bytes)
+}
+
+fn write_varint<W: Write + ?Sized>(writer: &mut W, mut v: u64) -> Result<(), StreamError> {
+    let mut buf = [0u8; 10];
+    let mut len = 0;
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    writer.write_all(&buf[..len])?;
+    Ok(())
+}
+
+fn read_varint<R: Read + ?Sized>(reader: &mut R, max_bytes: usize) -> Result<u64, StreamError> {
+    let mut value = 0u64;
+    for index in 0..max_bytes {
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf)?;
+        value |= ((buf[0] & 0x7f) as u64) << (7 * index);
+        if buf[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(StreamError::VarIntTooLong(max_bytes))
+}
+
+/// Encodes a string in Java's modified UTF-8, which writes NUL as two bytes and characters
+/// outside of the BMP as two three-byte surrogates
+fn encode_mutf8(str: &str) -> Cow<'_, [u8]> {
+    // Both only differ from UTF-8 for NUL and four-byte characters
+    if !str.bytes().any(|byte| byte == 0 || byte >= 0xf0) {
+        return Cow::Borrowed(str.as_bytes());
+    }
+    let mut bytes = Vec::with_capacity(str.len() + 4);
+    let mut units = [0; 2];
+    for ch in str.chars() {
+        match ch as u32 {
+            0 => bytes.extend_from_slice(&[0xc0, 0x80]),
+            0x10000.. => {
+                for unit in ch.encode_utf16(&mut units).iter() {
+                    bytes.extend_from_slice(&[
+                        0xe0 | (unit >> 12) as u8,
+                        0x80 | (unit >> 6 & 0x3f) as u8,
+                        0x80 | (unit & 0x3f) as u8,
+                    ]);
+                }
+            }
+            _ => bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Cow::Owned(bytes)
+}
+
+/// Decodes modified UTF-8, also accepting the NUL and four-byte characters of plain UTF-8
+fn decode_mutf8(bytes: Vec<u8>) -> Result<String, StreamError> {
+    // Only encoded NULs start with 0xc0 and only surrogates with 0xed
+    if !bytes.iter().any(|byte| *byte == 0xc0 || *byte == 0xed) {
+        return String::from_utf8(bytes).map_err(|_| StreamError::InvalidString);
+    }
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.into_iter();
+    while let Some(byte) = iter.next() {
+        let code = match byte {
+            0x00..=0x7f => byte as u32,
+            0xc0..=0xdf => (byte as u32 & 0x1f) << 6 | continuation(&mut iter)?,
+            0xe0..=0xef => {
+                (byte as u32 & 0x0f) << 12
+                    | continuation(&mut iter)? << 6
+                    | continuation(&mut iter)?
+            }
+            0xf0..=0xf7 => {
+                let code = (byte as u32 & 0x07) << 18
+                    | continuation(&mut iter)? << 12
+                    | continuation(&mut iter)? << 6
+                    | continuation(&mut iter)?;
+                let ch = char::from_u32(code).ok_or(StreamError::InvalidString)?;
+                units.extend_from_slice(ch.encode_utf16(&mut [0; 2]));
+                continue;
+            }
+            _ => return Err(StreamError::InvalidString),
+        };
+        units.push(code as u16);
+    }
+    String::from_utf16(&units).map_err(|_| StreamError::InvalidString)
+}
+
+fn continuation<I: Iterator<Item = u8>>(iter: &mut I) -> Result<u32, StreamError> {
+    match iter.next() {
+        Some(byte) if byte & 0xc0 == 0x80 => Ok((byte & 0x3f) as u32),
+        _ => Err(StreamError::InvalidString),
+    }
+}
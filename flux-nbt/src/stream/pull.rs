@@ -0,0 +1,442 @@
+use std::io::{self, Read};
+
+use nbt::{Map, Value};
+
+use super::{encoding::read_exact_vec, tag, Encoding, StreamError};
+use crate::snbt::MAX_DEPTH;
+
+/// A single step of a binary NBT document.
+///
+/// The name is `None` for list elements and for the root of encodings without a root name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Byte(Option<String>, i8),
+    Short(Option<String>, i16),
+    Int(Option<String>, i32),
+    Long(Option<String>, i64),
+    Float(Option<String>, f32),
+    Double(Option<String>, f64),
+    ByteArray(Option<String>, Vec<i8>),
+    String(Option<String>, String),
+    IntArray(Option<String>, Vec<i32>),
+    LongArray(Option<String>, Vec<i64>),
+    StartCompound(Option<String>),
+    /// A list along with the tag of its elements and its length
+    StartList(Option<String>, u8, usize),
+    /// Closes the innermost compound or list
+    End,
+}
+
+/// Limits for reading untrusted NBT, exceeding them fails with a [`StreamError`] before
+/// anything is allocated for the offending value.
+///
+/// Bytes are accounted by the nominal size of every tag, name and payload as it is read, so
+/// decompression bombs stop at the limit too. SNBT is read within the same limits by
+/// [`snbt::from_str_with_limits`](crate::snbt::from_str_with_limits).
+///
+/// ```
+/// # use flux_nbt::stream::{NbtOptions, ReadLimits};
+/// let uploads = NbtOptions::new().with_limits(
+///     ReadLimits::new()
+///         .with_max_bytes(2 * 1024 * 1024)
+///         .with_max_len(65536),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimits {
+    max_depth: usize,
+    max_bytes: u64,
+    max_len: usize,
+}
+
+impl ReadLimits {
+    /// Limits nesting to a depth of 512 like the game, without limiting sizes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No limits at all, reading deeply nested values may overflow the stack
+    pub fn unlimited() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_bytes: u64::MAX,
+            max_len: usize::MAX,
+        }
+    }
+
+    /// Most compounds and lists that may be open at once
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Most bytes that may be read in total
+    pub fn with_max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Longest list or array that may be read
+    pub fn with_max_len(mut self, len: usize) -> Self {
+        self.max_len = len;
+        self
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            ..Self::unlimited()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Frame {
+    Compound,
+    List { element: u8, remaining: usize },
+}
+
+/// A pull parser over binary NBT, producing one [`Event`] at a time.
+///
+/// Only the path to the current tag is kept in memory, and subtrees that are not of
+/// interest can be skipped without decoding them:
+///
+/// ```
+/// # use flux_nbt::{nbt, stream::{self, Encoding, Event, NbtOptions, NbtReader}};
+/// let mut bytes = Vec::new();
+/// stream::write_value(&mut bytes, &nbt! { big: { a: 1 }, wanted: 2 }, &NbtOptions::new()).unwrap();
+///
+/// let mut reader = NbtReader::new(bytes.as_slice(), Encoding::Java);
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::StartCompound(Some(String::new()))));
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::StartCompound(Some("big".to_owned()))));
+/// reader.skip().unwrap();
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::Int(Some("wanted".to_owned()), 2)));
+/// ```
+pub struct NbtReader<R> {
+    reader: R,
+    encoding: Encoding,
+    stack: Vec<Frame>,
+    started: bool,
+    limits: ReadLimits,
+    accounted: u64,
+}
+
+impl<R: Read> NbtReader<R> {
+    pub fn new(reader: R, encoding: Encoding) -> Self {
+        Self {
+            reader,
+            encoding,
+            stack: Vec::with_capacity(16),
+            started: false,
+            limits: ReadLimits::default(),
+            accounted: 0,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Bytes read so far, counted towards [`ReadLimits::with_max_bytes`]
+    pub fn accounted(&self) -> u64 {
+        self.accounted
+    }
+
+    /// Amount of compounds and lists that are currently open
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next event, returning `None` once the root value has been read completely
+    pub fn next_event(&mut self) -> Result<Option<Event>, StreamError> {
+        if !self.started {
+            self.started = true;
+            let tag = self.read_tag()?;
+            if tag == tag::END {
+                return Err(StreamError::InvalidRoot(tag::name(tag)));
+            }
+            let name = if self.encoding.has_root_name() {
+                Some(self.read_string()?)
+            } else {
+                None
+            };
+            return self.start(tag, name).map(Some);
+        }
+        match self.stack.last_mut() {
+            None => Ok(None),
+            Some(Frame::Compound) => {
+                let tag = self.read_tag()?;
+                if tag == tag::END {
+                    self.stack.pop();
+                    return Ok(Some(Event::End));
+                }
+                let name = self.read_string()?;
+                self.start(tag, Some(name)).map(Some)
+            }
+            Some(Frame::List { remaining: 0, .. }) => {
+                self.stack.pop();
+                Ok(Some(Event::End))
+            }
+            Some(Frame::List { element, remaining }) => {
+                *remaining -= 1;
+                let element = *element;
+                self.start(element, None).map(Some)
+            }
+        }
+    }
+
+    /// Skips the rest of the innermost open compound or list, including its [`Event::End`]
+    pub fn skip(&mut self) -> Result<(), StreamError> {
+        let depth = self.stack.len();
+        match self.stack.pop() {
+            None => Ok(()),
+            Some(Frame::Compound) => self.skip_compound(depth),
+            Some(Frame::List { element, remaining }) => self.skip_many(element, remaining, depth),
+        }
+    }
+
+    /// Reads the next complete value, decoding a whole subtree if the next event starts one.
+    ///
+    /// Returns `None` when the innermost compound or list has ended instead, or when
+    /// the root value has been read completely.
+    pub fn read_value(&mut self) -> Result<Option<(Option<String>, Value)>, StreamError> {
+        let Some(event) = self.next_event()? else {
+            return Ok(None);
+        };
+        Ok(Some(match event {
+            Event::Byte(name, v) => (name, Value::Byte(v)),
+            Event::Short(name, v) => (name, Value::Short(v)),
+            Event::Int(name, v) => (name, Value::Int(v)),
+            Event::Long(name, v) => (name, Value::Long(v)),
+            Event::Float(name, v) => (name, Value::Float(v)),
+            Event::Double(name, v) => (name, Value::Double(v)),
+            Event::ByteArray(name, v) => (name, Value::ByteArray(v)),
+            Event::String(name, v) => (name, Value::String(v)),
+            Event::IntArray(name, v) => (name, Value::IntArray(v)),
+            Event::LongArray(name, v) => (name, Value::LongArray(v)),
+            Event::StartCompound(name) => {
+                let mut cmp = Map::new();
+                while let Some((key, value)) = self.read_value()? {
+                    cmp.insert(key.unwrap_or_default(), value);
+                }
+                (name, Value::Compound(cmp))
+            }
+            Event::StartList(name, _, len) => {
+                let mut list = Vec::with_capacity(len.min(1024));
+                while let Some((_, value)) = self.read_value()? {
+                    list.push(value);
+                }
+                (name, Value::List(list))
+            }
+            Event::End => return Ok(None),
+        }))
+    }
+
+    fn read_tag(&mut self) -> Result<u8, StreamError> {
+        self.account(1)?;
+        Ok(self.encoding.read_byte(&mut self.reader)? as u8)
+    }
+
+    fn read_string(&mut self) -> Result<String, StreamError> {
+        let len = self.encoding.read_string_len(&mut self.reader)?;
+        self.account(2 + len as u64)?;
+        let bytes = read_exact_vec(&mut self.reader, len)?;
+        self.encoding.decode_string(bytes)
+    }
+
+    /// Reads the length of a list or an array, accounting its elements of `size` bytes
+    fn read_len(&mut self, size: u64) -> Result<usize, StreamError> {
+        let len = self.encoding.read_len(&mut self.reader)?;
+        if len > self.limits.max_len {
+            return Err(StreamError::LengthLimitExceeded {
+                len,
+                limit: self.limits.max_len,
+            });
+        }
+        self.account(4 + size.saturating_mul(len as u64))?;
+        Ok(len)
+    }
+
+    fn account(&mut self, bytes: u64) -> Result<(), StreamError> {
+        self.accounted = self.accounted.saturating_add(bytes);
+        if self.accounted > self.limits.max_bytes {
+            return Err(StreamError::SizeLimitExceeded(self.limits.max_bytes));
+        }
+        Ok(())
+    }
+
+    /// Checks the depth of a compound or list about to be opened
+    fn enter(&self, depth: usize) -> Result<(), StreamError> {
+        if depth > self.limits.max_depth {
+            return Err(StreamError::DepthLimitExceeded(self.limits.max_depth));
+        }
+        Ok(())
+    }
+
+    /// Reads the payload of a scalar or an array, or opens a compound or a list
+    fn start(&mut self, tag: u8, name: Option<String>) -> Result<Event, StreamError> {
+        if let Some(size) = scalar_size(tag) {
+            self.account(size)?;
+        }
+        let encoding = self.encoding;
+        Ok(match tag {
+            tag::BYTE => Event::Byte(name, encoding.read_byte(&mut self.reader)?),
+            tag::SHORT => Event::Short(name, encoding.read_short(&mut self.reader)?),
+            tag::INT => Event::Int(name, encoding.read_int(&mut self.reader)?),
+            tag::LONG => Event::Long(name, encoding.read_long(&mut self.reader)?),
+            tag::FLOAT => Event::Float(name, encoding.read_float(&mut self.reader)?),
+            tag::DOUBLE => Event::Double(name, encoding.read_double(&mut self.reader)?),
+            tag::BYTE_ARRAY => {
+                let len = self.read_len(1)?;
+                let bytes = read_exact_vec(&mut self.reader, len)?;
+                Event::ByteArray(name, bytes.into_iter().map(|byte| byte as i8).collect())
+            }
+            tag::STRING => Event::String(name, self.read_string()?),
+            tag::LIST => {
+                self.enter(self.stack.len() + 1)?;
+                let element = self.read_tag()?;
+                // Elements are accounted as they are read
+                let len = self.read_len(0)?;
+                self.stack.push(Frame::List {
+                    element,
+                    remaining: len,
+                });
+                Event::StartList(name, element, len)
+            }
+            tag::COMPOUND => {
+                self.enter(self.stack.len() + 1)?;
+                self.stack.push(Frame::Compound);
+                Event::StartCompound(name)
+            }
+            tag::INT_ARRAY => {
+                let len = self.read_len(4)?;
+                let mut ints = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    ints.push(encoding.read_int(&mut self.reader)?);
+                }
+                Event::IntArray(name, ints)
+            }
+            tag::LONG_ARRAY => {
+                let len = self.read_len(8)?;
+                let mut longs = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    longs.push(encoding.read_long(&mut self.reader)?);
+                }
+                Event::LongArray(name, longs)
+            }
+            other => return Err(StreamError::UnknownTag(other)),
+        })
+    }
+
+    /// Skips the rest of a compound at `depth`
+    fn skip_compound(&mut self, depth: usize) -> Result<(), StreamError> {
+        loop {
+            let tag = self.read_tag()?;
+            if tag == tag::END {
+                return Ok(());
+            }
+            self.skip_string()?;
+            self.skip_payload(tag, depth + 1)?;
+        }
+    }
+
+    /// Skips `count` payloads of a list at `depth`
+    fn skip_many(&mut self, tag: u8, count: usize, depth: usize) -> Result<(), StreamError> {
+        match self.encoding.fixed_size(tag) {
+            Some(size) => self.skip_bytes(size.saturating_mul(count)),
+            None => {
+                for _ in 0..count {
+                    self.skip_payload(tag, depth + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Skips a payload, opening a compound or list at `depth`
+    fn skip_payload(&mut self, tag: u8, depth: usize) -> Result<(), StreamError> {
+        match tag {
+            tag::BYTE_ARRAY => {
+                let len = self.read_len(0)?;
+                self.skip_bytes(len)
+            }
+            tag::STRING => self.skip_string(),
+            tag::LIST => {
+                self.enter(depth)?;
+                let element = self.read_tag()?;
+                let len = self.read_len(0)?;
+                self.skip_many(element, len, depth)
+            }
+            tag::COMPOUND => {
+                self.enter(depth)?;
+                self.skip_compound(depth)
+            }
+            tag::INT_ARRAY => {
+                let len = self.read_len(0)?;
+                self.skip_many(tag::INT, len, depth)
+            }
+            tag::LONG_ARRAY => {
+                let len = self.read_len(0)?;
+                self.skip_many(tag::LONG, len, depth)
+            }
+            other => match self.encoding.fixed_size(other) {
+                Some(size) => self.skip_bytes(size),
+                // ints and longs are varints in some encodings
+                None if other == tag::INT => {
+                    self.account(4)?;
+                    self.encoding.read_int(&mut self.reader).map(drop)
+                }
+                None if other == tag::LONG => {
+                    self.account(8)?;
+                    self.encoding.read_long(&mut self.reader).map(drop)
+                }
+                None => Err(StreamError::UnknownTag(other)),
+            },
+        }
+    }
+
+    fn skip_string(&mut self) -> Result<(), StreamError> {
+        let len = self.encoding.read_string_len(&mut self.reader)?;
+        self.account(2)?;
+        self.skip_bytes(len)
+    }
+
+    fn skip_bytes(&mut self, len: usize) -> Result<(), StreamError> {
+        self.account(len as u64)?;
+        let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+        if skipped != len as u64 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+}
+
+/// Nominal size of a scalar payload, the same for every encoding
+fn scalar_size(tag: u8) -> Option<u64> {
+    match tag {
+        tag::BYTE => Some(1),
+        tag::SHORT => Some(2),
+        tag::INT | tag::FLOAT => Some(4),
+        tag::LONG | tag::DOUBLE => Some(8),
+        _ => None,
+    }
+}
@@ -0,0 +1,1299 @@
+use std::io::Write;
+
+use nbt::Value;
+use serde::{
+    ser::{
+        Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Serialize,
+};
+
+use super::{tag, Encoding, StreamError};
+use crate::{
+    array,
+    ser::{PathSegment, SerErrorKind},
+};
+
+/// A serde serializer that writes binary NBT directly into the underlying writer.
+///
+/// Every compound field and list element is first probed for its tag type, so that
+/// the tag headers can be written before the payload without any intermediate buffers.
+/// Only sequences of unknown length have to be buffered, as their length prefix has
+/// to be known upfront.
+pub struct Serializer<W> {
+    writer: W,
+    root_name: String,
+    encoding: Encoding,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            root_name: String::new(),
+            encoding: Encoding::default(),
+        }
+    }
+
+    pub fn with_root_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.root_name = name.into();
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn root(&mut self, tag: u8) -> Result<Payload<'_, W>, StreamError> {
+        write_root_header(&mut self.writer, self.encoding, tag, &self.root_name)?;
+        Ok(Payload::new(&mut self.writer, self.encoding))
+    }
+}
+
+pub(crate) fn write_tag<W: Write + ?Sized>(writer: &mut W, tag: u8) -> Result<(), StreamError> {
+    writer.write_all(&[tag])?;
+    Ok(())
+}
+
+/// Writes the tag of the root value, followed by its name if the encoding has one
+fn write_root_header<W: Write + ?Sized>(
+    writer: &mut W,
+    encoding: Encoding,
+    tag: u8,
+    name: &str,
+) -> Result<(), StreamError> {
+    write_tag(writer, tag)?;
+    if encoding.has_root_name() {
+        encoding.write_string(writer, name)?;
+    }
+    Ok(())
+}
+
+/// Writes the root value of an NBT file
+pub(crate) fn write_root<W: Write + ?Sized>(
+    writer: &mut W,
+    encoding: Encoding,
+    name: &str,
+    value: &Value,
+) -> Result<(), StreamError> {
+    write_root_header(writer, encoding, value.id(), name)?;
+    write_payload(writer, encoding, value)
+}
+
+/// Writes a named tag inside of a compound
+fn write_named<W: Write + ?Sized>(
+    writer: &mut W,
+    encoding: Encoding,
+    name: &str,
+    value: &Value,
+) -> Result<(), StreamError> {
+    write_tag(writer, value.id())?;
+    encoding.write_string(writer, name)?;
+    write_payload(writer, encoding, value)
+}
+
+pub(crate) fn write_payload<W: Write + ?Sized>(
+    writer: &mut W,
+    encoding: Encoding,
+    value: &Value,
+) -> Result<(), StreamError> {
+    match value {
+        Value::Byte(v) => encoding.write_byte(writer, *v)?,
+        Value::Short(v) => encoding.write_short(writer, *v)?,
+        Value::Int(v) => encoding.write_int(writer, *v)?,
+        Value::Long(v) => encoding.write_long(writer, *v)?,
+        Value::Float(v) => encoding.write_float(writer, *v)?,
+        Value::Double(v) => encoding.write_double(writer, *v)?,
+        Value::ByteArray(bytes) => {
+            encoding.write_len(writer, bytes.len())?;
+            for byte in bytes {
+                encoding.write_byte(writer, *byte)?;
+            }
+        }
+        Value::String(str) => encoding.write_string(writer, str)?,
+        Value::List(list) => {
+            let element = list.first().map(Value::id).unwrap_or(tag::END);
+            write_tag(writer, element)?;
+            encoding.write_len(writer, list.len())?;
+            for (index, each) in list.iter().enumerate() {
+                if each.id() != element {
+                    return Err(StreamError::from(SerErrorKind::HeterogeneousList {
+                        expected: tag::name(element),
+                        found: tag::name(each.id()),
+                    })
+                    .within(PathSegment::Index(index)));
+                }
+                write_payload(writer, encoding, each)
+                    .map_err(|err| err.within(PathSegment::Index(index)))?;
+            }
+        }
+        Value::Compound(cmp) => {
+            for (name, each) in cmp {
+                write_named(writer, encoding, name, each)
+                    .map_err(|err| err.within(PathSegment::Field(name.clone())))?;
+            }
+            write_tag(writer, tag::END)?;
+        }
+        Value::IntArray(ints) => {
+            encoding.write_len(writer, ints.len())?;
+            for int in ints {
+                encoding.write_int(writer, *int)?;
+            }
+        }
+        Value::LongArray(longs) => {
+            encoding.write_len(writer, longs.len())?;
+            for long in longs {
+                encoding.write_long(writer, *long)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<'a, W: Write> serde::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+
+    type Error = StreamError;
+
+    type SerializeSeq = Seq<'a, W>;
+
+    type SerializeTuple = Seq<'a, W>;
+
+    type SerializeTupleStruct = Seq<'a, W>;
+
+    type SerializeTupleVariant = Seq<'a, W>;
+
+    type SerializeMap = Compound<'a, W>;
+
+    type SerializeStruct = Compound<'a, W>;
+
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::BYTE)?.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::BYTE)?.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::SHORT)?.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::INT)?.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::LONG)?.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i8(v as i8)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i16(v as i16)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::FLOAT)?.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::DOUBLE)?.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::STRING)?.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.root(tag::BYTE_ARRAY)?.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(StreamError::InvalidRoot("an absent value"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match array::marker_tag(name) {
+            Some(array) => {
+                let tag = TagProbe
+                    .serialize_newtype_struct(name, value)?
+                    .ok_or(StreamError::InvalidRoot("an absent value"))?;
+                let mut payload = self.root(tag)?;
+                payload.array = Some(array);
+                value.serialize(payload)
+            }
+            None => value.serialize(self),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if value.serialize(TagProbe)?.is_none() {
+            return self.serialize_unit_variant(name, variant_index, variant);
+        }
+        self.root(tag::COMPOUND)?
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(Seq::new(
+            &mut self.writer,
+            self.encoding,
+            tag::LIST,
+            len,
+            Some(&self.root_name),
+        ))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.root(tag::COMPOUND)?
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        let root = self.root(tag::COMPOUND)?;
+        Ok(Compound::new(root.writer, root.encoding))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.root(tag::COMPOUND)?
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+}
+
+/// Determines which tag a value will be serialized as, without writing anything.
+///
+/// Absent values (`None` and `()`) have no tag and are skipped by compounds.
+struct TagProbe;
+
+impl serde::Serializer for TagProbe {
+    type Ok = Option<u8>;
+
+    type Error = StreamError;
+
+    type SerializeSeq = SeqProbe;
+
+    type SerializeTuple = SeqProbe;
+
+    type SerializeTupleStruct = SeqProbe;
+
+    type SerializeTupleVariant = CompoundProbe;
+
+    type SerializeMap = CompoundProbe;
+
+    type SerializeStruct = CompoundProbe;
+
+    type SerializeStructVariant = CompoundProbe;
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::BYTE))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::BYTE))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::SHORT))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::INT))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::LONG))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::BYTE))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::SHORT))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::INT))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::LONG))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::FLOAT))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::DOUBLE))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::STRING))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::STRING))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::BYTE_ARRAY))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::STRING))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::STRING))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let Some(array) = array::marker_tag(name) else {
+            return value.serialize(self);
+        };
+        match value.serialize(self)? {
+            None => Ok(None),
+            Some(tag::LIST | tag::BYTE_ARRAY | tag::INT_ARRAY | tag::LONG_ARRAY) => Ok(Some(array)),
+            Some(found) => Err(SerErrorKind::InvalidArray {
+                array: tag::name(array),
+                found: tag::name(found),
+            }
+            .into()),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Some(match value.serialize(TagProbe)? {
+            Some(_) => tag::COMPOUND,
+            None => tag::STRING,
+        }))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqProbe)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(CompoundProbe)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(CompoundProbe)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(CompoundProbe)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(CompoundProbe)
+    }
+}
+
+/// Sequences are lists, unless they are wrapped in one of the [`array`] markers
+struct SeqProbe;
+
+macro_rules! seq_probe {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl $trait for SeqProbe {
+                type Ok = Option<u8>;
+
+                type Error = StreamError;
+
+                fn $method<T>(&mut self, _value: &T) -> Result<(), Self::Error>
+                where
+                    T: ?Sized + Serialize,
+                {
+                    Ok(())
+                }
+
+                fn end(self) -> Result<Self::Ok, Self::Error> {
+                    Ok(Some(tag::LIST))
+                }
+            }
+        )*
+    };
+}
+
+seq_probe!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field
+);
+
+/// Compounds, including the `{Variant: value}` wrappers of enum variants with data
+struct CompoundProbe;
+
+impl SerializeTupleVariant for CompoundProbe {
+    type Ok = Option<u8>;
+
+    type Error = StreamError;
+
+    fn serialize_field<T>(&mut self, _value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::COMPOUND))
+    }
+}
+
+impl SerializeMap for CompoundProbe {
+    type Ok = Option<u8>;
+
+    type Error = StreamError;
+
+    fn serialize_key<T>(&mut self, _key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, _value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::COMPOUND))
+    }
+}
+
+impl SerializeStruct for CompoundProbe {
+    type Ok = Option<u8>;
+
+    type Error = StreamError;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::COMPOUND))
+    }
+}
+
+impl SerializeStructVariant for CompoundProbe {
+    type Ok = Option<u8>;
+
+    type Error = StreamError;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(tag::COMPOUND))
+    }
+}
+
+/// Writes the payload of a value whose tag header has already been written
+struct Payload<'a, W: ?Sized> {
+    writer: &'a mut W,
+    encoding: Encoding,
+    // the tag forced by an array marker, sequences are lists otherwise
+    array: Option<u8>,
+}
+
+impl<'a, W: Write + ?Sized> Payload<'a, W> {
+    fn new(writer: &'a mut W, encoding: Encoding) -> Self {
+        Self {
+            writer,
+            encoding,
+            array: None,
+        }
+    }
+}
+
+impl<'a, W: Write + ?Sized> serde::Serializer for Payload<'a, W> {
+    type Ok = ();
+
+    type Error = StreamError;
+
+    type SerializeSeq = Seq<'a, W>;
+
+    type SerializeTuple = Seq<'a, W>;
+
+    type SerializeTupleStruct = Seq<'a, W>;
+
+    type SerializeTupleVariant = Seq<'a, W>;
+
+    type SerializeMap = Compound<'a, W>;
+
+    type SerializeStruct = Compound<'a, W>;
+
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i8(v as i8)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_byte(self.writer, v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_short(self.writer, v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_int(self.writer, v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_long(self.writer, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i8(v as i8)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i16(v as i16)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_float(self.writer, v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_double(self.writer, v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.encoding.write_string(self.writer, v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        match self.array {
+            // a list of bytes only differs from a byte array by its element tag
+            Some(tag::LIST) => write_tag(self.writer, tag::BYTE)?,
+            Some(array) if array != tag::BYTE_ARRAY => {
+                return Err(SerErrorKind::InvalidArrayElement {
+                    array: tag::name(array),
+                    found: tag::name(tag::BYTE),
+                }
+                .into())
+            }
+            _ => {}
+        }
+        self.encoding.write_len(self.writer, v.len())?;
+        self.writer.write_all(v)?;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(array) = array::marker_tag(name) {
+            self.array = Some(array);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if value.serialize(TagProbe)?.is_none() {
+            return self.serialize_unit_variant(name, variant_index, variant);
+        }
+        let mut wrapper = Compound::new(self.writer, self.encoding);
+        wrapper.field(variant, value)?;
+        wrapper.finish()
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let container = self.array.unwrap_or(tag::LIST);
+        Ok(Seq::new(self.writer, self.encoding, container, len, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        write_tag(self.writer, tag::LIST)?;
+        self.encoding.write_string(self.writer, variant)?;
+        let mut seq = Seq::new(self.writer, self.encoding, tag::LIST, Some(len), None);
+        seq.variant = Some(variant);
+        Ok(seq)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(Compound::new(self.writer, self.encoding))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(Compound::new(self.writer, self.encoding))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        write_tag(self.writer, tag::COMPOUND)?;
+        self.encoding.write_string(self.writer, variant)?;
+        let mut cmp = Compound::new(self.writer, self.encoding);
+        cmp.variant = Some(variant);
+        Ok(cmp)
+    }
+}
+
+/// Prefixes the path of an error with the variant it occurred in, if any
+fn within_variant(err: StreamError, variant: Option<&'static str>) -> StreamError {
+    match variant {
+        Some(variant) => err.within(PathSegment::Field(variant.to_owned())),
+        None => err,
+    }
+}
+
+#[doc(hidden)]
+pub struct Seq<'a, W: ?Sized> {
+    writer: &'a mut W,
+    encoding: Encoding,
+    // either a list or one of the array tags
+    container: u8,
+    len: Option<usize>,
+    element: Option<u8>,
+    count: usize,
+    // sequences of unknown length are collected here until their length is known
+    buffer: Option<Vec<u8>>,
+    // root sequences write their own tag, as it is only known after the first element
+    root_name: Option<&'a str>,
+    // tuple variants also close the `{Variant: [..]}` compound around them
+    variant: Option<&'static str>,
+}
+
+impl<'a, W: Write + ?Sized> Seq<'a, W> {
+    fn new(
+        writer: &'a mut W,
+        encoding: Encoding,
+        container: u8,
+        len: Option<usize>,
+        root_name: Option<&'a str>,
+    ) -> Self {
+        Self {
+            writer,
+            encoding,
+            container,
+            len,
+            element: None,
+            count: 0,
+            buffer: if len.is_none() {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            root_name,
+            variant: None,
+        }
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), StreamError> {
+        let index = self.count;
+        self.write_element(value)
+            .map_err(|err| within_variant(err.within(PathSegment::Index(index)), self.variant))
+    }
+
+    fn write_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), StreamError> {
+        let tag = value
+            .serialize(TagProbe)?
+            .ok_or(SerErrorKind::AbsentElement)?;
+        match self.element {
+            None => {
+                if let Some(expected) = array::element_tag(self.container) {
+                    if tag != expected {
+                        return Err(SerErrorKind::InvalidArrayElement {
+                            array: tag::name(self.container),
+                            found: tag::name(tag),
+                        }
+                        .into());
+                    }
+                }
+                self.element = Some(tag);
+                if self.buffer.is_none() {
+                    self.write_header(tag, self.len.unwrap_or_default())?;
+                }
+            }
+            Some(expected) if expected != tag => {
+                return Err(SerErrorKind::HeterogeneousList {
+                    expected: tag::name(expected),
+                    found: tag::name(tag),
+                }
+                .into())
+            }
+            _ => {}
+        }
+        self.count += 1;
+        match &mut self.buffer {
+            Some(buffer) => value.serialize(Payload::new(buffer, self.encoding)),
+            None => value.serialize(Payload::new(&mut *self.writer, self.encoding)),
+        }
+    }
+
+    fn write_header(&mut self, element: u8, len: usize) -> Result<(), StreamError> {
+        if let Some(name) = self.root_name {
+            write_root_header(self.writer, self.encoding, self.container, name)?;
+        }
+        // arrays only store their length, as their element type is implied
+        if self.container == tag::LIST {
+            write_tag(self.writer, element)?;
+        }
+        self.encoding.write_len(self.writer, len)
+    }
+
+    fn finish(mut self) -> Result<(), StreamError> {
+        match (self.buffer.take(), self.element) {
+            (Some(buffer), Some(element)) => {
+                self.write_header(element, self.count)?;
+                self.writer.write_all(&buffer)?;
+            }
+            (_, None) => self.write_header(tag::END, 0)?,
+            (None, Some(_)) => {
+                let declared = self.len.unwrap_or_default();
+                if declared != self.count {
+                    return Err(StreamError::LengthMismatch {
+                        declared,
+                        actual: self.count,
+                    });
+                }
+            }
+        }
+        if self.variant.is_some() {
+            write_tag(self.writer, tag::END)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! seq_impl {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl<'a, W: Write + ?Sized> $trait for Seq<'a, W> {
+                type Ok = ();
+
+                type Error = StreamError;
+
+                fn $method<T>(&mut self, value: &T) -> Result<(), Self::Error>
+                where
+                    T: ?Sized + Serialize,
+                {
+                    self.element(value)
+                }
+
+                fn end(self) -> Result<Self::Ok, Self::Error> {
+                    self.finish()
+                }
+            }
+        )*
+    };
+}
+
+seq_impl!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+#[doc(hidden)]
+pub struct Compound<'a, W: ?Sized> {
+    writer: &'a mut W,
+    encoding: Encoding,
+    key: Option<String>,
+    // struct variants also close the `{Variant: {..}}` compound around them
+    variant: Option<&'static str>,
+}
+
+impl<'a, W: Write + ?Sized> Compound<'a, W> {
+    fn new(writer: &'a mut W, encoding: Encoding) -> Self {
+        Self {
+            writer,
+            encoding,
+            key: None,
+            variant: None,
+        }
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), StreamError> {
+        self.write_field(key, value).map_err(|err| {
+            within_variant(err.within(PathSegment::Field(key.to_owned())), self.variant)
+        })
+    }
+
+    fn write_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), StreamError> {
+        let tag = match value.serialize(TagProbe)? {
+            Some(tag) => tag,
+            // absent values are omitted
+            None => return Ok(()),
+        };
+        write_tag(self.writer, tag)?;
+        self.encoding.write_string(self.writer, key)?;
+        value.serialize(Payload::new(&mut *self.writer, self.encoding))
+    }
+
+    fn finish(self) -> Result<(), StreamError> {
+        write_tag(self.writer, tag::END)?;
+        if self.variant.is_some() {
+            write_tag(self.writer, tag::END)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write + ?Sized> SerializeMap for Compound<'a, W> {
+    type Ok = ();
+
+    type Error = StreamError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.key.take().ok_or(SerErrorKind::InvalidKey)?;
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write + ?Sized> SerializeStruct for Compound<'a, W> {
+    type Ok = ();
+
+    type Error = StreamError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write + ?Sized> SerializeStructVariant for Compound<'a, W> {
+    type Ok = ();
+
+    type Error = StreamError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Accepts only string-like map keys
+struct KeySerializer;
+
+macro_rules! invalid_key {
+    ($($method:ident($($arg:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+                Err(SerErrorKind::InvalidKey.into())
+            }
+        )*
+    };
+}
+
+impl serde::Serializer for KeySerializer {
+    type Ok = String;
+
+    type Error = StreamError;
+
+    type SerializeSeq = Impossible<String, StreamError>;
+
+    type SerializeTuple = Impossible<String, StreamError>;
+
+    type SerializeTupleStruct = Impossible<String, StreamError>;
+
+    type SerializeTupleVariant = Impossible<String, StreamError>;
+
+    type SerializeMap = Impossible<String, StreamError>;
+
+    type SerializeStruct = Impossible<String, StreamError>;
+
+    type SerializeStructVariant = Impossible<String, StreamError>;
+
+    invalid_key! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(SerErrorKind::InvalidKey.into())
+    }
+}
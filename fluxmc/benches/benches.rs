@@ -1,21 +1,61 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use flux_nbt::ser::to_nbt;
 use fluxmc::item::{CompoundSkullOwner, ItemStack, Material};
+use serde::Serialize;
 
-fn build_item() -> String {
-    black_box(ItemStack::new(Material::PlayerHead).with_meta(|meta| {
+fn head_item() -> ItemStack {
+    ItemStack::new(Material::PlayerHead).with_meta(|meta| {
         meta.with_meta(|data| {
             let head = data.as_head().unwrap();
             head.set_owner(CompoundSkullOwner::from_url(
                 "f815fc1cd643cb5a08aa9bdc66a6551572f646303f0caa3cfbcf3c3a25e511d4",
             ))
         })
-    }))
-    .to_string()
+    })
+}
+
+fn build_item() -> String {
+    black_box(head_item()).to_string()
 }
 
 fn benchmark_item(c: &mut Criterion) {
     c.bench_function("items", |b| b.iter(build_item));
 }
 
-criterion_group!(benches, benchmark_item);
+#[derive(Serialize)]
+struct Section {
+    name: String,
+    position: (i32, i32, i32),
+    heights: Vec<i64>,
+    flags: Vec<bool>,
+    entries: Vec<Entry>,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    id: String,
+    count: i8,
+    damage: Option<i16>,
+}
+
+fn benchmark_to_nbt(c: &mut Criterion) {
+    let sections: Vec<Section> = (0..64)
+        .map(|index| Section {
+            name: format!("section_{index}"),
+            position: (index, -index, index * 2),
+            heights: (0..16).collect(),
+            flags: vec![true, false, true],
+            entries: (0..8)
+                .map(|entry| Entry {
+                    id: format!("minecraft:entry_{entry}"),
+                    count: entry as i8,
+                    damage: (entry % 2 == 0).then_some(entry as i16),
+                })
+                .collect(),
+        })
+        .collect();
+    c.bench_function("to_nbt", |b| b.iter(|| to_nbt(black_box(&sections))));
+}
+
+criterion_group!(benches, benchmark_item, benchmark_to_nbt);
 criterion_main!(benches);
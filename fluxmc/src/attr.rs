@@ -1,12 +1,20 @@
+use std::fmt::Display;
+
+use flux_nbt::{macros::FromNbtError, nbt, FromNbt, Nbt, Value};
 use serde::Serialize;
 use uuid::Uuid;
 
-use crate::item::EquipmentSlot;
+use crate::{
+    item::EquipmentSlot,
+    util::{compound, field, optional_field},
+};
 
 #[derive(Debug, Clone, Serialize, PartialEq, PartialOrd)]
 pub struct AttributeModifier {
     #[serde(rename = "AttributeName")]
     pub attribute: Attribute,
+    #[serde(rename = "Name")]
+    pub name: Option<String>,
     #[serde(rename = "Amount")]
     pub amount: f64,
     #[serde(rename = "Operation")]
@@ -19,6 +27,7 @@ impl AttributeModifier {
     pub fn new(attribute: Attribute, operation: AttributeOperation, amount: f64) -> Self {
         Self {
             attribute,
+            name: None,
             amount,
             operation: operation.into(),
             additional_data: AdditionalAttributeData::None,
@@ -33,6 +42,7 @@ impl AttributeModifier {
     ) -> Self {
         Self {
             attribute,
+            name: None,
             amount,
             operation: operation.into(),
             additional_data: AdditionalAttributeData::Full {
@@ -51,11 +61,36 @@ impl AttributeModifier {
     ) -> Self {
         Self {
             attribute,
+            name: None,
             amount,
             operation: operation.into(),
             additional_data: AdditionalAttributeData::Full { slot, uuid },
         }
     }
+
+    pub fn slot(&self) -> Option<EquipmentSlot> {
+        match self.additional_data {
+            AdditionalAttributeData::Full { slot, .. } => Some(slot),
+            _ => None,
+        }
+    }
+
+    pub fn uuid(&self) -> Option<Uuid> {
+        match self.additional_data {
+            AdditionalAttributeData::Full { uuid, .. } | AdditionalAttributeData::Uuid { uuid } => {
+                Some(uuid)
+            }
+            AdditionalAttributeData::None => None,
+        }
+    }
+
+    /// Sets the UUID, keeping the slot if there is one
+    pub fn set_uuid(&mut self, uuid: Uuid) {
+        self.additional_data = match self.slot() {
+            Some(slot) => AdditionalAttributeData::Full { slot, uuid },
+            None => AdditionalAttributeData::Uuid { uuid },
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default, Serialize)]
@@ -69,6 +104,11 @@ enum AdditionalAttributeData {
         #[serde(rename = "UUID")]
         uuid: Uuid,
     },
+    /// The modifiers of entities have no slot
+    Uuid {
+        #[serde(rename = "UUID")]
+        uuid: Uuid,
+    },
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Serialize)]
@@ -99,9 +139,9 @@ pub enum Attribute {
     Custom(String),
 }
 
-impl ToString for Attribute {
-    fn to_string(&self) -> String {
-        match self {
+impl Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
             Attribute::MaxHealth => "generic.max_health",
             Attribute::FollowRange => "generic.follow_range",
             Attribute::KnockbackResistance => "generic.knockback_resistance",
@@ -116,8 +156,30 @@ impl ToString for Attribute {
             Attribute::FlyingSpeed => "generic.flying_speed",
             Attribute::ZombieSpawnReinforcements => "zombie.spawn_reinforcements",
             Attribute::Custom(id) => id,
+        })
+    }
+}
+
+/// Reads the names written by [`Display`], with or without the `minecraft` namespace
+impl From<String> for Attribute {
+    fn from(name: String) -> Self {
+        let unprefixed = name.strip_prefix("minecraft:").unwrap_or(&name);
+        match unprefixed {
+            "generic.max_health" => Attribute::MaxHealth,
+            "generic.follow_range" => Attribute::FollowRange,
+            "generic.knockback_resistance" => Attribute::KnockbackResistance,
+            "generic.movement_speed" => Attribute::MovementSpeed,
+            "generic.attack_damage" => Attribute::AttackDamage,
+            "generic.armor" => Attribute::Armor,
+            "generic.armor_toughness" => Attribute::ArmorToughness,
+            "generic.attack_knockback" => Attribute::AttackKnockback,
+            "generic.attack_speed" => Attribute::AttackSpeed,
+            "generic.luck" => Attribute::Luck,
+            "horse.jump_strength" => Attribute::HorseJumpStrength,
+            "generic.flying_speed" => Attribute::FlyingSpeed,
+            "zombie.spawn_reinforcements" => Attribute::ZombieSpawnReinforcements,
+            _ => Attribute::Custom(name),
         }
-        .to_string()
     }
 }
 
@@ -142,3 +204,47 @@ impl From<AttributeOperation> for i32 {
         }
     }
 }
+
+impl Nbt for AttributeModifier {
+    fn nbt(&self) -> Value {
+        nbt! {
+            AttributeName: (self.attribute.to_string()),
+            Name?: (self.name),
+            Amount: (self.amount),
+            Operation: (self.operation),
+            Slot?: (self.slot()),
+            UUID?: (self.uuid().map(uuid_ints)),
+        }
+    }
+}
+
+impl FromNbt for AttributeModifier {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let mut modifier = Self {
+            attribute: Attribute::from(field::<String>(cmp, "AttributeName")?),
+            name: optional_field(cmp, "Name")?,
+            amount: field(cmp, "Amount")?,
+            operation: field(cmp, "Operation")?,
+            additional_data: AdditionalAttributeData::None,
+        };
+        if let Some(uuid) = optional_field(cmp, "UUID")? {
+            modifier.set_uuid(uuid);
+        }
+        if let (Some(slot), Some(uuid)) = (optional_field(cmp, "Slot")?, modifier.uuid()) {
+            modifier.additional_data = AdditionalAttributeData::Full { slot, uuid };
+        }
+        Ok(modifier)
+    }
+}
+
+/// The int array form of UUIDs stored by the game
+pub(crate) fn uuid_ints(uuid: Uuid) -> Value {
+    let bits = uuid.as_u128();
+    Value::IntArray(
+        (0..4)
+            .rev()
+            .map(|i| (bits >> (i * 32)) as u32 as i32)
+            .collect(),
+    )
+}
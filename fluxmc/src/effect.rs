@@ -1,9 +1,16 @@
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display};
 
 use convert_case::{Case, Casing};
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    nbt, FromNbt, Map, Nbt, Value,
+};
 use serde::Serialize;
 
-use crate::id::Identifier;
+use crate::{
+    id::Identifier,
+    util::{compound, field, optional_field, unknown_fields},
+};
 
 #[derive(Debug, Clone, Serialize, PartialEq, PartialOrd)]
 #[serde(rename_all = "PascalCase")]
@@ -47,8 +54,17 @@ impl PotionContainerMeta {
     }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, PartialOrd)]
-#[serde(rename_all = "PascalCase")]
+/// Keys of the typed fields of [`CustomPotionEffect`]
+const EFFECT_KEYS: [&str; 6] = [
+    "Id",
+    "Amplifier",
+    "Duration",
+    "Ambient",
+    "ShowParticles",
+    "ShowIcon",
+];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CustomPotionEffect {
     id: EffectKind,
     amplifier: i8,
@@ -56,6 +72,7 @@ pub struct CustomPotionEffect {
     ambient: Option<bool>,
     show_particles: Option<bool>,
     show_icon: Option<bool>,
+    extra: Map<String, Value>,
 }
 
 impl CustomPotionEffect {
@@ -67,6 +84,7 @@ impl CustomPotionEffect {
             ambient: None,
             show_particles: None,
             show_icon: None,
+            extra: Map::new(),
         }
     }
 
@@ -94,6 +112,15 @@ impl CustomPotionEffect {
         self.show_icon
     }
 
+    /// Every other entry, like `HiddenEffect` and `FactorCalculationData`
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    pub fn extra_mut(&mut self) -> &mut Map<String, Value> {
+        &mut self.extra
+    }
+
     pub fn set_duration(&mut self, ticks: u32) {
         self.duration = Some(ticks)
     }
@@ -111,6 +138,32 @@ impl CustomPotionEffect {
     }
 }
 
+/// Ordered by the typed fields, effects only differing in their other entries are unordered
+impl PartialOrd for CustomPotionEffect {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let fields = (
+            &self.id,
+            self.amplifier,
+            self.duration,
+            self.ambient,
+            self.show_particles,
+            self.show_icon,
+        );
+        let other_fields = (
+            &other.id,
+            other.amplifier,
+            other.duration,
+            other.ambient,
+            other.show_particles,
+            other.show_icon,
+        );
+        match fields.partial_cmp(&other_fields) {
+            Some(Ordering::Equal) if self.extra != other.extra => None,
+            ordering => ordering,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 #[repr(u32)]
 pub enum EffectKind {
@@ -150,6 +203,59 @@ pub enum EffectKind {
     Custom(Identifier),
 }
 
+/// Vanilla effects ordered by their numeric id, starting at 1
+const NUMBERED: [EffectKind; 33] = [
+    EffectKind::Speed,
+    EffectKind::Slowness,
+    EffectKind::Haste,
+    EffectKind::MiningFatigue,
+    EffectKind::Strength,
+    EffectKind::InstantHealth,
+    EffectKind::InstantDamage,
+    EffectKind::JumpBoost,
+    EffectKind::Nausea,
+    EffectKind::Regeneration,
+    EffectKind::Resistance,
+    EffectKind::FireResistance,
+    EffectKind::WaterBreathing,
+    EffectKind::Invisibility,
+    EffectKind::Blindness,
+    EffectKind::NightVision,
+    EffectKind::Hunger,
+    EffectKind::Weakness,
+    EffectKind::Poison,
+    EffectKind::Wither,
+    EffectKind::HealthBoost,
+    EffectKind::Absorption,
+    EffectKind::Saturation,
+    EffectKind::Glowing,
+    EffectKind::Levitation,
+    EffectKind::Luck,
+    EffectKind::Unluck,
+    EffectKind::SlowFalling,
+    EffectKind::ConduitPower,
+    EffectKind::DolphinGrace,
+    EffectKind::BadOmen,
+    EffectKind::HeroOfTheVillage,
+    EffectKind::Darkness,
+];
+
+impl EffectKind {
+    /// The vanilla effect with a numeric id
+    pub fn from_id(id: u32) -> Option<Self> {
+        NUMBERED.get((id as usize).checked_sub(1)?).cloned()
+    }
+
+    /// The vanilla effect with an identifier, or a custom effect
+    pub fn from_identifier(id: Identifier) -> Self {
+        NUMBERED
+            .iter()
+            .find(|kind| Identifier::from((*kind).clone()) == id)
+            .cloned()
+            .unwrap_or(EffectKind::Custom(id))
+    }
+}
+
 impl From<EffectKind> for Identifier {
     fn from(value: EffectKind) -> Self {
         match value {
@@ -217,3 +323,66 @@ impl Serialize for EffectKind {
         }
     }
 }
+
+impl Nbt for EffectKind {
+    fn nbt(&self) -> Value {
+        match self {
+            EffectKind::Custom(custom) => custom.nbt(),
+            _ => Value::Int(u32::from(self.clone()) as i32),
+        }
+    }
+}
+
+/// Reads numeric ids as well as identifiers
+impl FromNbt for EffectKind {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        match value {
+            Value::String(_) => Identifier::from_nbt(value).map(EffectKind::from_identifier),
+            _ => {
+                let id = u32::from_nbt(value)?;
+                EffectKind::from_id(id).ok_or_else(|| {
+                    FromNbtErrorKind::Custom(format!("Unknown effect id {id}")).into()
+                })
+            }
+        }
+    }
+}
+
+impl Nbt for CustomPotionEffect {
+    fn nbt(&self) -> Value {
+        nbt! {
+            Id: (self.id),
+            Amplifier: (self.amplifier),
+            Duration?: (self.duration),
+            Ambient?: (self.ambient),
+            ShowParticles?: (self.show_particles),
+            ShowIcon?: (self.show_icon),
+            ..self.extra
+        }
+    }
+}
+
+/// Goes through [`Nbt`], so arrays among the other entries keep their tags
+impl Serialize for CustomPotionEffect {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        flux_nbt::array::value::serialize(&self.nbt(), serializer)
+    }
+}
+
+impl FromNbt for CustomPotionEffect {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        Ok(Self {
+            id: field(cmp, "Id")?,
+            amplifier: optional_field(cmp, "Amplifier")?.unwrap_or_default(),
+            duration: optional_field(cmp, "Duration")?,
+            ambient: optional_field(cmp, "Ambient")?,
+            show_particles: optional_field(cmp, "ShowParticles")?,
+            show_icon: optional_field(cmp, "ShowIcon")?,
+            extra: unknown_fields(cmp, &EFFECT_KEYS),
+        })
+    }
+}
@@ -1,3 +1,7 @@
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    stream::StreamError,
+};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, Error)]
@@ -5,3 +9,34 @@ pub enum Error {
     #[error("Failed to parse an identifier")]
     ParsingError,
 }
+
+/// Error of loading a typed NBT file, like a structure template or player data
+#[derive(Debug, Error)]
+pub enum NbtFileError {
+    #[error(transparent)]
+    Stream(#[from] StreamError),
+    #[error("Invalid file contents: {0}")]
+    Nbt(#[from] FromNbtError),
+}
+
+/// Error of placing a block or an entity in a structure template
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum StructureError {
+    #[error("Structure positions must be between 0 and {}, found {0:?}", i32::MAX - 1)]
+    PositionOutOfRange([i32; 3]),
+    #[error("Position {pos:?} is outside of the size {size:?}")]
+    OutsideOfSize { pos: [i32; 3], size: [i32; 3] },
+    #[error("Size must not be negative, found {0:?}")]
+    NegativeSize([i32; 3]),
+    #[error("Entity positions must be finite, found {0:?}")]
+    NonFiniteEntityPosition([f64; 3]),
+    #[error("{0} data must be a compound, found {1}")]
+    NotACompound(&'static str, String),
+}
+
+/// Invalid positions in files are reported like any other invalid value
+impl From<StructureError> for FromNbtError {
+    fn from(err: StructureError) -> Self {
+        FromNbtErrorKind::Custom(err.to_string()).into()
+    }
+}
@@ -1,6 +1,9 @@
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, fmt::Display, str::FromStr};
 
-use flux_nbt::{Nbt, Value};
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    FromNbt, Nbt, Value,
+};
 use serde::Serialize;
 
 use crate::err::Error;
@@ -68,3 +71,24 @@ impl Nbt for Identifier {
         Value::String(self.to_string())
     }
 }
+
+/// Identifiers without a namespace are in the `minecraft` namespace, like in the game
+impl FromStr for Identifier {
+    type Err = Error;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        match id.split_once(':') {
+            Some((_, path)) if path.contains(':') => Err(Error::ParsingError),
+            Some((namespace, path)) => Ok(Identifier::new(namespace, path)),
+            None => Ok(Identifier::minecraft(id)),
+        }
+    }
+}
+
+impl FromNbt for Identifier {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let id = String::from_nbt(value)?;
+        id.parse()
+            .map_err(|_| FromNbtErrorKind::Custom(format!("Invalid identifier `{id}`")).into())
+    }
+}
@@ -5,6 +5,11 @@ mod meta;
 
 use std::fmt::Display;
 
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    FromNbt, Nbt, Value,
+};
+
 pub use firework::*;
 use flux_nbt::ser::to_snbt;
 pub use material::*;
@@ -46,6 +51,10 @@ impl ItemStack {
         self.count = count
     }
 
+    pub fn meta(&self) -> &ItemMetaTag {
+        &self.meta
+    }
+
     pub fn with_meta<F: FnOnce(&mut ItemMetaTag)>(mut self, modifier: F) -> Self {
         modifier(&mut self.meta);
         self
@@ -57,7 +66,7 @@ impl Display for ItemStack {
         f.write_fmt(format_args!(
             "{}{} {}",
             self.id,
-            to_snbt(&self.meta).unwrap(),
+            to_snbt(&self.meta).map_err(|_| std::fmt::Error)?,
             self.count
         ))
     }
@@ -73,3 +82,47 @@ pub enum EquipmentSlot {
     Chest,
     Head,
 }
+
+impl EquipmentSlot {
+    const ALL: [EquipmentSlot; 6] = [
+        EquipmentSlot::MainHand,
+        EquipmentSlot::OffHand,
+        EquipmentSlot::Feet,
+        EquipmentSlot::Legs,
+        EquipmentSlot::Chest,
+        EquipmentSlot::Head,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipmentSlot::MainHand => "mainhand",
+            EquipmentSlot::OffHand => "offhand",
+            EquipmentSlot::Feet => "feet",
+            EquipmentSlot::Legs => "legs",
+            EquipmentSlot::Chest => "chest",
+            EquipmentSlot::Head => "head",
+        }
+    }
+}
+
+impl Nbt for EquipmentSlot {
+    fn nbt(&self) -> Value {
+        Value::String(self.name().to_owned())
+    }
+}
+
+impl FromNbt for EquipmentSlot {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let name = String::from_nbt(value)?;
+        EquipmentSlot::ALL
+            .into_iter()
+            .find(|slot| slot.name() == name)
+            .ok_or_else(|| {
+                FromNbtErrorKind::UnknownVariant {
+                    ty: "EquipmentSlot",
+                    variant: name,
+                }
+                .into()
+            })
+    }
+}
@@ -3,7 +3,9 @@ use serde::Serialize;
 #[derive(Default, Debug, Clone, PartialEq, PartialOrd, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct FireworkExplosion {
+    #[serde(with = "flux_nbt::array::int_array")]
     colors: Vec<u32>,
+    #[serde(with = "flux_nbt::array::int_array")]
     fade_colors: Option<Vec<u32>>,
     flicker: Option<bool>,
     trail: Option<bool>,
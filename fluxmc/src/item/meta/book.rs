@@ -11,7 +11,7 @@ pub struct BookAndQuillMeta {
 impl BookAndQuillMeta {
     pub fn new(page_count: Option<usize>) -> Self {
         Self {
-            pages: Vec::with_capacity(if let Some(page) = page_count { page } else { 1 }),
+            pages: Vec::with_capacity(page_count.unwrap_or(1)),
         }
     }
 
@@ -64,11 +64,7 @@ impl WrittenBookMeta {
             generation: None,
             author: author.into(),
             title: title.into(),
-            pages: Vec::with_capacity(if let Some(pages) = page_count {
-                pages
-            } else {
-                1
-            }),
+            pages: Vec::with_capacity(page_count.unwrap_or(1)),
         }
     }
 
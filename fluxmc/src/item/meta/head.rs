@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use serde::Serialize;
 use uuid::Uuid;
 
@@ -162,9 +164,12 @@ pub struct HeadTexture {
     pub textures: ActualTexture,
 }
 
-impl ToString for HeadTexture {
-    fn to_string(&self) -> String {
-        base64::encode_config(serde_json::to_string(self).unwrap(), base64::STANDARD)
+impl Display for HeadTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&base64::encode_config(
+            serde_json::to_string(self).unwrap(),
+            base64::STANDARD,
+        ))
     }
 }
 
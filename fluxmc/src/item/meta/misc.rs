@@ -82,11 +82,7 @@ pub struct BundleMeta {
 impl BundleMeta {
     pub fn new(item_count: Option<usize>) -> Self {
         Self {
-            items: Vec::with_capacity(if let Some(count) = item_count {
-                count
-            } else {
-                1
-            }),
+            items: Vec::with_capacity(item_count.unwrap_or(1)),
         }
     }
 
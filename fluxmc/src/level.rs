@@ -0,0 +1,211 @@
+//! The gzipped `level.dat` file of a world, holding its game rules, spawn and world border.
+//!
+//! Entries without a typed field, like the world generation settings, are kept in
+//! [`LevelData::extra`] and written back unchanged:
+//!
+//! ```no_run
+//! # use fluxmc::{level::LevelData, loc::Position};
+//! let mut level = LevelData::load("world/level.dat")?;
+//! level.set_game_rule("keepInventory", true);
+//! level.spawn = Position::new(0, 64, 0);
+//! level.world_border.size = 2000.0;
+//! level.save("world/level.dat")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use flux_nbt::{
+    macros::FromNbtError,
+    nbt,
+    ser::PathSegment,
+    stream::{self, Compression, NbtOptions, StreamError},
+    FromNbt, Map, Nbt, Value,
+};
+
+use crate::{
+    err::NbtFileError,
+    loc::Position,
+    util::{compound, field, optional_field, unknown_fields},
+};
+
+const LEVEL_KEYS: [&str; 16] = [
+    "DataVersion",
+    "LevelName",
+    "GameRules",
+    "SpawnX",
+    "SpawnY",
+    "SpawnZ",
+    "SpawnAngle",
+    "BorderCenterX",
+    "BorderCenterZ",
+    "BorderSize",
+    "BorderDamagePerBlock",
+    "BorderSafeZone",
+    "BorderWarningBlocks",
+    "BorderWarningTime",
+    "BorderSizeLerpTarget",
+    "BorderSizeLerpTime",
+];
+
+/// The `Data` compound of `level.dat`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelData {
+    pub data_version: i32,
+    pub level_name: String,
+    /// Game rules by name, the game stores every value as a string
+    pub game_rules: Map<String, String>,
+    pub spawn: Position,
+    pub spawn_angle: f32,
+    pub world_border: WorldBorder,
+    /// Every other entry, like the seed, game type and time
+    pub extra: Map<String, Value>,
+}
+
+/// The world border of the overworld, stored along with the level data
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// Width of the border in blocks
+    pub size: f64,
+    pub damage_per_block: f64,
+    /// Distance outside the border players can be at without taking damage
+    pub safe_zone: f64,
+    pub warning_blocks: f64,
+    /// Seconds before a shrinking border reaches a player to warn them at
+    pub warning_time: f64,
+    /// Size the border is moving towards
+    pub size_lerp_target: f64,
+    /// Milliseconds until the border reaches its target size
+    pub size_lerp_time: i64,
+}
+
+impl LevelData {
+    pub fn new<N: Into<String>>(data_version: i32, level_name: N) -> Self {
+        Self {
+            data_version,
+            level_name: level_name.into(),
+            game_rules: Map::new(),
+            spawn: Position::new(0, 64, 0),
+            spawn_angle: 0.0,
+            world_border: WorldBorder::default(),
+            extra: Map::new(),
+        }
+    }
+
+    /// Reads a gzipped `level.dat`
+    pub fn read<R: Read>(reader: R) -> Result<Self, NbtFileError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        let (_, value) = stream::read_value(reader, &options)?;
+        let data: Value = field(compound(&value)?, "Data")?;
+        Ok(Self::from_nbt(&data)
+            .map_err(|err| err.within(PathSegment::Field("Data".to_owned())))?)
+    }
+
+    /// Writes the level data gzipped, wrapped in the `Data` compound the game expects
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), StreamError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        stream::write_value(writer, &nbt! { Data: (self.nbt()) }, &options)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NbtFileError> {
+        let file = File::open(path).map_err(StreamError::from)?;
+        Self::read(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StreamError> {
+        self.write(File::create(path)?)
+    }
+
+    pub fn game_rule(&self, name: &str) -> Option<&str> {
+        self.game_rules.get(name).map(String::as_str)
+    }
+
+    /// Sets a game rule, e.g. `set_game_rule("randomTickSpeed", 3)`
+    pub fn set_game_rule<N: Into<String>, V: ToString>(&mut self, name: N, value: V) {
+        self.game_rules.insert(name.into(), value.to_string());
+    }
+}
+
+impl Default for WorldBorder {
+    /// The border of a new world
+    fn default() -> Self {
+        Self {
+            center_x: 0.0,
+            center_z: 0.0,
+            size: 5.999997E7,
+            damage_per_block: 0.2,
+            safe_zone: 5.0,
+            warning_blocks: 5.0,
+            warning_time: 15.0,
+            size_lerp_target: 5.999997E7,
+            size_lerp_time: 0,
+        }
+    }
+}
+
+impl Nbt for LevelData {
+    fn nbt(&self) -> Value {
+        let border = &self.world_border;
+        nbt! {
+            DataVersion: (self.data_version),
+            LevelName: (self.level_name),
+            GameRules: (self.game_rules),
+            SpawnX: (self.spawn.x()),
+            SpawnY: (self.spawn.y()),
+            SpawnZ: (self.spawn.z()),
+            SpawnAngle: (self.spawn_angle),
+            BorderCenterX: (border.center_x),
+            BorderCenterZ: (border.center_z),
+            BorderSize: (border.size),
+            BorderDamagePerBlock: (border.damage_per_block),
+            BorderSafeZone: (border.safe_zone),
+            BorderWarningBlocks: (border.warning_blocks),
+            BorderWarningTime: (border.warning_time),
+            BorderSizeLerpTarget: (border.size_lerp_target),
+            BorderSizeLerpTime: (border.size_lerp_time),
+            ..self.extra
+        }
+    }
+}
+
+/// Reads the `Data` compound, missing entries take the values of a new world
+impl FromNbt for LevelData {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let border = WorldBorder::default();
+        Ok(Self {
+            data_version: field(cmp, "DataVersion")?,
+            level_name: optional_field(cmp, "LevelName")?.unwrap_or_default(),
+            game_rules: optional_field(cmp, "GameRules")?.unwrap_or_default(),
+            spawn: Position::new(
+                optional_field(cmp, "SpawnX")?.unwrap_or(0),
+                optional_field(cmp, "SpawnY")?.unwrap_or(64),
+                optional_field(cmp, "SpawnZ")?.unwrap_or(0),
+            ),
+            spawn_angle: optional_field(cmp, "SpawnAngle")?.unwrap_or_default(),
+            world_border: WorldBorder {
+                center_x: optional_field(cmp, "BorderCenterX")?.unwrap_or(border.center_x),
+                center_z: optional_field(cmp, "BorderCenterZ")?.unwrap_or(border.center_z),
+                size: optional_field(cmp, "BorderSize")?.unwrap_or(border.size),
+                damage_per_block: optional_field(cmp, "BorderDamagePerBlock")?
+                    .unwrap_or(border.damage_per_block),
+                safe_zone: optional_field(cmp, "BorderSafeZone")?.unwrap_or(border.safe_zone),
+                warning_blocks: optional_field(cmp, "BorderWarningBlocks")?
+                    .unwrap_or(border.warning_blocks),
+                warning_time: optional_field(cmp, "BorderWarningTime")?
+                    .unwrap_or(border.warning_time),
+                size_lerp_target: optional_field(cmp, "BorderSizeLerpTarget")?
+                    .unwrap_or(border.size_lerp_target),
+                size_lerp_time: optional_field(cmp, "BorderSizeLerpTime")?
+                    .unwrap_or(border.size_lerp_time),
+            },
+            extra: unknown_fields(cmp, &LEVEL_KEYS),
+        })
+    }
+}
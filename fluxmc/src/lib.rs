@@ -3,14 +3,21 @@ pub mod effect;
 pub mod err;
 pub mod id;
 pub mod item;
+pub mod level;
 pub mod loc;
+pub mod player;
+pub mod region;
+pub mod schematic;
+pub mod structure;
 pub mod text;
 pub mod util;
 
 pub use flux_macros as macros;
 pub use flux_nbt as nbt;
 
-pub use flux_nbt::Nbt;
+#[cfg(feature = "macros")]
+pub use flux_macros::{snbt, FromNbt, Nbt};
+pub use flux_nbt::{FromNbt, Nbt};
 
 extern crate self as fluxmc;
 
@@ -97,4 +104,750 @@ mod tests {
         });
         println!("{}", item)
     }
+
+    #[derive(Debug, PartialEq, crate::Nbt, crate::FromNbt)]
+    #[nbt(rename_all = "PascalCase")]
+    struct Slot {
+        #[nbt(rename = "Slot")]
+        index: i8,
+        id: crate::id::Identifier,
+        count: u8,
+        #[nbt(rename = "tag")]
+        tag: Option<SlotTag>,
+        #[nbt(skip)]
+        cached: u32,
+    }
+
+    #[derive(Debug, PartialEq, Default, crate::Nbt, crate::FromNbt)]
+    struct SlotTag {
+        #[nbt(int_array)]
+        colors: Vec<u32>,
+        #[nbt(default)]
+        unbreakable: bool,
+        #[nbt(flatten)]
+        extra: flux_nbt::Map<String, flux_nbt::Value>,
+    }
+
+    #[derive(Debug, PartialEq, crate::Nbt, crate::FromNbt)]
+    #[nbt(rename_all = "snake_case")]
+    enum Pattern {
+        None,
+        WildArmor,
+        Custom { pattern: String },
+        Id(i32),
+    }
+
+    #[derive(Debug, PartialEq, crate::Nbt, crate::FromNbt)]
+    enum Num {
+        I(i32),
+        F(f32),
+        Pair(i32, #[nbt(int_array)] Vec<i32>),
+    }
+
+    #[test]
+    fn test_derive() {
+        use flux_nbt::{macros::FromNbtError, nbt, snbt, FromNbt, Nbt, Value};
+
+        let mut extra = flux_nbt::Map::new();
+        extra.insert("Custom".to_owned(), Value::Int(5));
+        let slot = Slot {
+            index: 3,
+            id: crate::id::Identifier::minecraft("firework_star"),
+            count: 1,
+            tag: Some(SlotTag {
+                colors: vec![0xff0000],
+                unbreakable: false,
+                extra,
+            }),
+            cached: 7,
+        };
+        let value = slot.nbt();
+        assert_eq!(
+            snbt::to_string(&value),
+            "{Slot:3b,Id:'minecraft:firework_star',Count:1b,tag:{colors:[I;16711680],unbreakable:0b,Custom:5}}"
+        );
+        let read = Slot::from_nbt(&value).unwrap();
+        assert_eq!(read, Slot { cached: 0, ..slot });
+
+        let bare = nbt! { Slot: 0, Id: "stone", Count: 64 };
+        let read = Slot::from_nbt(&bare).unwrap();
+        assert_eq!(read.id.to_string(), "minecraft:stone");
+        assert_eq!(read.tag, None);
+        let err = Slot::from_nbt(&nbt! { Slot: 0, Id: 1, Count: 1 }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected TAG_String, found TAG_Int at `Id`"
+        );
+        let err: FromNbtError = Slot::from_nbt(&nbt! { Slot: 0 }).unwrap_err();
+        assert_eq!(err.to_string(), "Missing field `Id`");
+
+        for trim in [
+            Pattern::None,
+            Pattern::WildArmor,
+            Pattern::Custom {
+                pattern: "x".to_owned(),
+            },
+            Pattern::Id(4),
+        ] {
+            assert_eq!(Pattern::from_nbt(&trim.nbt()).unwrap(), trim);
+        }
+        assert_eq!(
+            Pattern::WildArmor.nbt(),
+            Value::String("wild_armor".to_owned())
+        );
+        assert_eq!(
+            Pattern::from_nbt(&Value::String("iron".to_owned()))
+                .unwrap_err()
+                .to_string(),
+            "Unknown variant `iron` of Pattern"
+        );
+        assert_eq!(snbt::to_string(&Pattern::Id(4).nbt()), "{id:4}");
+        assert_eq!(
+            Pattern::from_nbt(&nbt! { custom: { pattern: 1 } })
+                .unwrap_err()
+                .to_string(),
+            "Expected TAG_String, found TAG_Int at `custom.pattern`"
+        );
+        assert_eq!(
+            Pattern::from_nbt(&nbt! { id: 1, custom: 2 })
+                .unwrap_err()
+                .to_string(),
+            "Expected a variant of Pattern as a string or a compound with a single key"
+        );
+
+        for num in [Num::I(1), Num::F(1.5), Num::Pair(2, vec![3])] {
+            assert_eq!(Num::from_nbt(&num.nbt()).unwrap(), num);
+        }
+        assert_eq!(snbt::to_string(&Num::F(1.5).nbt()), "{F:1.5f}");
+    }
+
+    #[test]
+    fn test_snbt_macro() {
+        use flux_nbt::{nbt, Value};
+
+        let name = "Excalibur";
+        let value = crate::snbt!(
+            "{CustomModelData:5,display:{Name:#{name}, Lore:['a', \"#{not_interpolated}\"]},
+              Colors:[I;1,-2],Scale:1.5f,Count:#{1u8 + 2},Tags:[#{\"x\"}, #{name}]}"
+        );
+        let expected = nbt! {
+            CustomModelData: 5,
+            display: { Name: "Excalibur", Lore: ["a", "#{not_interpolated}"] },
+            Colors: (Value::IntArray(vec![1, -2])),
+            Scale: (Value::Float(1.5)),
+            Count: (Value::Byte(3)),
+            Tags: ["x", "Excalibur"]
+        };
+        assert_eq!(value, expected);
+        assert_eq!(crate::snbt!("{}"), nbt! {});
+        assert_eq!(
+            crate::snbt!(
+                crate = flux_nbt,
+                "{v:['__flux_snbt_a_0', \"__flux_snbt_b_0\", #{name}]}"
+            ),
+            nbt! { v: ["__flux_snbt_a_0", "__flux_snbt_b_0", "Excalibur"] }
+        );
+    }
+
+    #[test]
+    fn test_structure() {
+        use flux_nbt::{nbt, snbt, FromNbt, Nbt, Value};
+
+        use crate::{
+            err::StructureError,
+            item::Material,
+            loc::Position,
+            structure::{BlockState, StructureTemplate},
+        };
+
+        let mut template = StructureTemplate::new(3465)
+            .with_block(Position::new(0, 0, 0), Material::Stone)
+            .with_block(Position::new(1, 0, 0), Material::Stone);
+        template.set_block_entity(
+            Position::new(1, 1, 0),
+            BlockState::from(Material::Chest).with_property("facing", "west"),
+            nbt! { Lock: "key" },
+        );
+        template.add_entity([0.5, 1.0, 0.5], nbt! { id: "minecraft:pig" });
+        assert_eq!(template.size(), [2, 2, 1]);
+        assert_eq!(template.palette().len(), 2);
+        assert_eq!(
+            snbt::to_string(&template.nbt()),
+            "{DataVersion:3465,size:[2,2,1],palette:[{Name:'minecraft:stone'},\
+             {Name:'minecraft:chest',Properties:{facing:'west'}}],\
+             blocks:[{pos:[0,0,0],state:0},{pos:[1,0,0],state:0},{pos:[1,1,0],state:1,nbt:{Lock:'key'}}],\
+             entities:[{pos:[0.5d,1d,0.5d],blockPos:[0,1,0],nbt:{id:'minecraft:pig'}}]}"
+        );
+
+        let mut bytes = Vec::new();
+        template.write(&mut bytes).unwrap();
+        let read = StructureTemplate::read(bytes.as_slice()).unwrap();
+        assert_eq!(read, template);
+        assert_eq!(
+            read.block_state(Position::new(1, 1, 0)).unwrap().properties["facing"],
+            "west"
+        );
+
+        template.set_block(Position::new(1, 1, 0), Material::Air);
+        assert_eq!(template.block(Position::new(1, 1, 0)).unwrap().nbt, None);
+        assert!(template.remove_block(Position::new(0, 0, 0)).is_some());
+        assert_eq!(
+            template.block_state(Position::new(1, 0, 0)).unwrap().name,
+            crate::id::Identifier::minecraft("stone")
+        );
+
+        let err = StructureTemplate::from_nbt(&nbt! {
+            DataVersion: 3465,
+            size: [1, 1, 1],
+            palette: [],
+            blocks: [{ pos: [0, 0, 0], state: 0 }]
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Block state 0 is not in the palette of 0 states at `blocks[0].state`"
+        );
+        for (blocks, entities, message) in [
+            (
+                vec![nbt! { pos: [0, -1, 0], state: 0 }],
+                vec![],
+                "Position [0, -1, 0] is outside of the size [2, 1, 1] at `blocks[0].pos`",
+            ),
+            (
+                vec![nbt! { pos: [2, 0, 0], state: 0 }],
+                vec![],
+                "Position [2, 0, 0] is outside of the size [2, 1, 1] at `blocks[0].pos`",
+            ),
+            (
+                vec![],
+                vec![nbt! { pos: [0.5, 1.5, 0.5], blockPos: [0, 1, 0], nbt: {} }],
+                "Position [0, 1, 0] is outside of the size [2, 1, 1] at `entities[0].blockPos`",
+            ),
+        ] {
+            let err = StructureTemplate::from_nbt(&nbt! {
+                DataVersion: 3465,
+                size: [2, 1, 1],
+                palette: [{ Name: "minecraft:stone" }],
+                blocks: (Value::List(blocks)),
+                entities: (Value::List(entities))
+            })
+            .unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        let size = template.size();
+        let err = template
+            .try_set_block(Position::new(-1, 0, 0), Material::Stone)
+            .unwrap_err();
+        assert_eq!(err, StructureError::PositionOutOfRange([-1, 0, 0]));
+        assert!(template
+            .try_set_block(Position::new(0, i32::MAX, 0), Material::Stone)
+            .is_err());
+        assert_eq!(
+            template
+                .try_set_block_entity(Position::new(0, 0, 0), Material::Chest, Value::Int(1))
+                .unwrap_err()
+                .to_string(),
+            "Block entity data must be a compound, found TAG_Int"
+        );
+        assert!(matches!(
+            template.try_add_entity([f64::NAN, 0.0, 0.0], nbt! {}),
+            Err(StructureError::NonFiniteEntityPosition(_))
+        ));
+        assert_eq!(template.size(), size);
+    }
+
+    #[test]
+    fn test_region() {
+        use std::io::Cursor;
+
+        use flux_nbt::{nbt, NbtPath, Value};
+
+        use crate::{
+            loc::Position,
+            region::{Chunk, ChunkCompression, RegionError, RegionFile},
+        };
+
+        // stone at section-local (1, 2, 3), air everywhere else, with 16 blocks of 4 bits per
+        // long the block index (2 * 16 + 3) * 16 + 1 = 561 is at bit 4 of long 35
+        let mut data = vec![0i64; 256];
+        data[35] = 1 << 4;
+        let chunk = |x: i32, filler: usize| {
+            Chunk::new(nbt! {
+                DataVersion: 3953,
+                xPos: (x),
+                zPos: 0,
+                sections: [{
+                    Y: -1b,
+                    block_states: {
+                        palette: [{ Name: "minecraft:air" }, { Name: "minecraft:stone" }],
+                        data: (Value::LongArray(data.clone()))
+                    }
+                }, {
+                    Y: 0B,
+                    block_states: { palette: [{ Name: "minecraft:bedrock" }] }
+                }],
+                block_entities: [{ id: "minecraft:chest", x: (x * 16), y: 0, z: 0, Items: [] }],
+                filler: (Value::ByteArray((0..filler).map(|i| (i * 7 % 256) as i8).collect()))
+            })
+        };
+
+        let mut region = RegionFile::new(Cursor::new(Vec::new())).unwrap();
+        let compressions = [
+            ChunkCompression::Gzip,
+            ChunkCompression::Zlib,
+            ChunkCompression::None,
+            ChunkCompression::Lz4,
+        ];
+        for (x, compression) in compressions.into_iter().enumerate() {
+            region
+                .write_chunk(x as i32, 0, &chunk(x as i32, 0), compression)
+                .unwrap();
+        }
+        // larger than a single LZ4 block and the sectors of the old chunk
+        region
+            .write_chunk(3, 0, &chunk(3, 150_000), ChunkCompression::Lz4)
+            .unwrap();
+        assert!(region.remove_chunk(2, 0).unwrap());
+        assert!(!region.remove_chunk(2, 0).unwrap());
+
+        let mut region = RegionFile::new(Cursor::new(region.into_inner().into_inner())).unwrap();
+        assert_eq!(
+            region.chunk_positions().collect::<Vec<_>>(),
+            [(0, 0), (1, 0), (3, 0)]
+        );
+        assert!(region.timestamp(0, 0).unwrap() > 0);
+        assert_eq!(region.timestamp(2, 0), None);
+        for read in region.chunks() {
+            let (x, _, read) = read.unwrap();
+            let filler = if x == 3 { 150_000 } else { 0 };
+            assert_eq!(read, chunk(x, filler));
+            assert_eq!(read.position(), Some((x, 0)));
+        }
+
+        let name = |region: &mut RegionFile<_>, x, y, z| {
+            region
+                .block_state(Position::new(x, y, z))
+                .unwrap()
+                .map(|state| state.name.to_string())
+        };
+        assert_eq!(
+            name(&mut region, 17, -14, 3).as_deref(),
+            Some("minecraft:stone")
+        );
+        assert_eq!(
+            name(&mut region, 17, -14, 4).as_deref(),
+            Some("minecraft:air")
+        );
+        assert_eq!(
+            name(&mut region, 1, 15, 0).as_deref(),
+            Some("minecraft:bedrock")
+        );
+        assert_eq!(name(&mut region, 1, 16, 0), None);
+        assert_eq!(name(&mut region, 32, 0, 0), None);
+        // chunk 33 wraps around to chunk 1 of this region, but belongs to the next one
+        assert_eq!(name(&mut region, 17 + 512, -14, 3), None);
+        assert_eq!(name(&mut region, 17 - 512, -14, 3), None);
+
+        // strips the items of a chest and writes the chunk back
+        let mut read = region.read_chunk(1, 0).unwrap().unwrap();
+        let chest = read.block_entity_mut(Position::new(16, 0, 0)).unwrap();
+        chest.insert("Items".to_owned(), nbt! { list: [{ id: "minecraft:tnt" }] });
+        NbtPath::parse("list[0]")
+            .unwrap()
+            .remove(chest.get_mut("Items").unwrap());
+        // the old sectors are left untouched until the header points at the new ones
+        let before = region.into_inner().into_inner();
+        let location = |bytes: &[u8]| u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let old = (location(&before) >> 8) * 4096..(location(&before) >> 8) * 4096 + 4096;
+        let mut region = RegionFile::new(Cursor::new(before.clone())).unwrap();
+        region
+            .write_chunk(1, 0, &read, ChunkCompression::Zlib)
+            .unwrap();
+        let after = region.into_inner().into_inner();
+        assert_ne!(location(&after) >> 8, location(&before) >> 8);
+        assert_eq!(after[old.clone()], before[old]);
+        let mut region = RegionFile::new(Cursor::new(after)).unwrap();
+        let read = region.read_chunk(1, 0).unwrap().unwrap();
+        assert_eq!(
+            read.block_entity(Position::new(16, 0, 0)).unwrap()["Items"],
+            nbt! { list: [] }
+        );
+
+        let mut bytes = region.into_inner().into_inner();
+        bytes[2 * 4096 + 4] = 9;
+        let mut region = RegionFile::new(Cursor::new(bytes)).unwrap();
+        let err = region.chunks().find_map(Result::err).unwrap();
+        assert!(matches!(err, RegionError::UnknownCompression(9)));
+    }
+
+    #[test]
+    fn test_player_data() {
+        use flux_nbt::{nbt, FromNbt, Nbt, Value};
+        use uuid::Uuid;
+
+        use crate::{
+            attr::{Attribute, AttributeModifier, AttributeOperation},
+            effect::{CustomPotionEffect, EffectKind},
+            level::LevelData,
+            loc::Position,
+            player::{EntityAttribute, InventoryItem, PlayerData},
+        };
+
+        let mut player = PlayerData::from_nbt(&nbt! {
+            DataVersion: 3465,
+            Pos: [1.5d, 64d, -3.5d],
+            XpLevel: 3,
+            ActiveEffects: [{ Id: 1, Amplifier: 1b, Duration: 200 }],
+            Attributes: [{
+                Name: "minecraft:generic.movement_speed",
+                Base: 0.1d,
+                Modifiers: [{
+                    Amount: 0.2d,
+                    Name: "effect.minecraft.speed 1",
+                    Operation: 2,
+                    UUID: [I; 1, 2, 3, 4],
+                }],
+            }],
+            playerGameType: 1,
+            abilities: { flying: 1b },
+        })
+        .unwrap();
+        assert_eq!(player.health, 20.0);
+        assert_eq!(*player.effects[0].id(), EffectKind::Speed);
+        let speed = player.attribute(&Attribute::MovementSpeed).unwrap();
+        assert_eq!(speed.modifiers[0].operation, 2);
+        assert_eq!(
+            speed.modifiers[0].uuid(),
+            Some(Uuid::from_u128(0x00000001_00000002_00000003_00000004))
+        );
+        assert_eq!(player.extra["playerGameType"], Value::Int(1));
+
+        player.xp_level += 10;
+        player.set_item(
+            InventoryItem::from_stack(0, &ItemStack::new(Material::DiamondSword)).unwrap(),
+        );
+        player.ender_items.push(InventoryItem::new(
+            3,
+            crate::id::Identifier::minecraft("stone"),
+            64,
+        ));
+        let mut effect = CustomPotionEffect::new(EffectKind::Regeneration, 0);
+        effect.set_duration(100);
+        player.effects.push(effect);
+        let mut modifier =
+            AttributeModifier::new(Attribute::MaxHealth, AttributeOperation::Add, 4.0);
+        modifier.set_uuid(Uuid::from_u128(5));
+        let mut health = EntityAttribute::new(Attribute::MaxHealth, 20.0);
+        health.modifiers.push(modifier);
+        player.attributes.push(health);
+
+        let mut bytes = Vec::new();
+        player.write(&mut bytes).unwrap();
+        let read = PlayerData::read(bytes.as_slice()).unwrap();
+        assert_eq!(read, player);
+        assert_eq!(
+            read.item(0).unwrap().id.to_string(),
+            "minecraft:diamond_sword"
+        );
+        assert_eq!(read.xp_level, 13);
+        let Value::Compound(written) = player.nbt() else {
+            unreachable!()
+        };
+        assert_eq!(written["abilities"], nbt! { flying: 1b });
+        assert_eq!(
+            written["Attributes"],
+            Value::List(vec![
+                nbt! {
+                    Name: "minecraft:generic.movement_speed",
+                    Base: 0.1d,
+                    Modifiers: [{
+                        Amount: 0.2d,
+                        Name: "effect.minecraft.speed 1",
+                        Operation: 2,
+                        UUID: [I; 1, 2, 3, 4],
+                    }],
+                },
+                nbt! {
+                    Name: "minecraft:generic.max_health",
+                    Base: 20d,
+                    Modifiers: [{ Amount: 4d, Operation: 0, UUID: [I; 0, 0, 0, 5] }],
+                },
+            ])
+        );
+        assert_eq!(player.remove_item(0).unwrap().count, 1);
+
+        // entries without a typed field are kept, like the hidden effect of a stronger effect
+        let effect = nbt! {
+            Id: 10,
+            Amplifier: 1b,
+            Duration: 200,
+            HiddenEffect: { Id: 10, Amplifier: 0B, Duration: 1000 },
+            FactorCalculationData: { padding_duration: 22, factor_start: 0f },
+            Colors: [I; 1, 2],
+            Flags: [B; 1],
+            Seeds: [L; 3, 4],
+        };
+        let read = CustomPotionEffect::from_nbt(&effect).unwrap();
+        assert_eq!(read.id(), &EffectKind::Regeneration);
+        assert_eq!(read.extra().len(), 5);
+        assert_eq!(read.nbt(), effect);
+        assert_eq!(flux_nbt::ser::to_nbt(&read).unwrap(), effect);
+
+        let mut level = LevelData::new(3465, "world");
+        level.set_game_rule("keepInventory", true);
+        level.spawn = Position::new(10, 70, -10);
+        level.world_border.size = 1000.0;
+        level.extra.insert("Difficulty".to_owned(), Value::Byte(2));
+        let mut bytes = Vec::new();
+        level.write(&mut bytes).unwrap();
+        let read = LevelData::read(bytes.as_slice()).unwrap();
+        assert_eq!(read, level);
+        assert_eq!(read.game_rule("keepInventory"), Some("true"));
+        assert_eq!(read.world_border.damage_per_block, 0.2);
+
+        let err = LevelData::from_nbt(&nbt! { DataVersion: 3465, SpawnX: "0" }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected TAG_Int, found TAG_String at `SpawnX`"
+        );
+    }
+
+    #[test]
+    fn test_schematics() {
+        use flux_nbt::{nbt, FromNbt, Nbt, Value};
+
+        use crate::{
+            item::Material,
+            loc::Position,
+            schematic::{Litematic, SpongeSchematic},
+            structure::{BlockState, StructureTemplate},
+        };
+
+        let state: BlockState = "minecraft:oak_stairs[facing=north,half=bottom]"
+            .parse()
+            .unwrap();
+        assert_eq!(state.properties["half"], "bottom");
+        assert_eq!(
+            state.to_string(),
+            "minecraft:oak_stairs[facing=north,half=bottom]"
+        );
+        assert!("stone[facing]".parse::<BlockState>().is_err());
+
+        let mut template = StructureTemplate::new(3465);
+        template.fill(
+            Position::new(0, 0, 0),
+            Position::new(2, 0, 0),
+            Material::Stone,
+        );
+        template.set_block_entity(
+            Position::new(0, 1, 0),
+            BlockState::from(Material::Chest).with_property("facing", "west"),
+            nbt! { id: "minecraft:chest", Lock: "key" },
+        );
+        template.add_entity(
+            [0.5, 1.0, 0.5],
+            nbt! { id: "minecraft:pig", UUID: [I; 1, 2, 3, 4] },
+        );
+        assert_eq!(
+            template.commands(),
+            [
+                "fill ~0 ~0 ~0 ~2 ~0 ~0 minecraft:stone",
+                "setblock ~0 ~1 ~0 minecraft:chest[facing=west]{Lock:'key'}",
+                "summon minecraft:pig ~0.5 ~1 ~0.5",
+            ]
+        );
+
+        for version in [2, 3] {
+            let mut schematic = SpongeSchematic::new(template.clone());
+            schematic.version = version;
+            schematic.offset = [-1, 0, 4];
+            let mut bytes = Vec::new();
+            schematic.write(&mut bytes).unwrap();
+            let read = SpongeSchematic::read(bytes.as_slice()).unwrap();
+            assert_eq!(read.version, version);
+            assert_eq!(read.offset, [-1, 0, 4]);
+            let read = read.template;
+            assert_eq!(read.size(), [3, 2, 1]);
+            assert_eq!(
+                read.block(Position::new(0, 1, 0)).unwrap().nbt,
+                template.block(Position::new(0, 1, 0)).unwrap().nbt
+            );
+            assert_eq!(
+                read.block_state(Position::new(2, 1, 0)),
+                Some(&BlockState::from(Material::Air))
+            );
+            assert_eq!(read.entities(), template.entities());
+        }
+        let Value::Compound(v2) = SpongeSchematic {
+            version: 2,
+            ..SpongeSchematic::new(template.clone())
+        }
+        .nbt() else {
+            unreachable!()
+        };
+        assert_eq!(v2["PaletteMax"], Value::Int(3));
+        assert!(v2.contains_key("BlockData"));
+
+        // Enough states for multi-byte varints and for packed states spanning two longs
+        let mut states = StructureTemplate::new(3465);
+        for x in 0..200 {
+            states.set_block(
+                Position::new(x, 0, 0),
+                BlockState::from(Material::Stone).with_property("n", x),
+            );
+        }
+        let mut bytes = Vec::new();
+        SpongeSchematic::new(states.clone())
+            .write(&mut bytes)
+            .unwrap();
+        assert_eq!(
+            SpongeSchematic::read(bytes.as_slice()).unwrap().template,
+            states
+        );
+
+        let mut litematic = Litematic::from_template("states", states.clone());
+        litematic.author = "builder".to_owned();
+        litematic.regions.push(crate::schematic::LitematicRegion {
+            name: "chest".to_owned(),
+            position: Position::new(-2, 0, 1),
+            template: template.clone(),
+            extra: Default::default(),
+        });
+        let mut bytes = Vec::new();
+        litematic.write(&mut bytes).unwrap();
+        let read = Litematic::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.regions[0].template, states);
+        assert_eq!(read.regions[1].template.blocks(), template.blocks());
+        assert_eq!(read.author, "builder");
+        let Value::Compound(root) = litematic.nbt() else {
+            unreachable!()
+        };
+        let Value::Compound(metadata) = &root["Metadata"] else {
+            unreachable!()
+        };
+        assert_eq!(metadata["EnclosingSize"], nbt! { x: 202, y: 2, z: 2 });
+        assert_eq!(metadata["TotalBlocks"], Value::Int(204));
+        let merged = read.to_template();
+        assert_eq!(merged.size(), [202, 2, 2]);
+        assert_eq!(
+            merged
+                .block_state(Position::new(2, 0, 0))
+                .unwrap()
+                .properties["n"],
+            "0"
+        );
+        assert_eq!(
+            merged.block_state(Position::new(0, 1, 1)).unwrap().name,
+            crate::id::Identifier::minecraft("chest")
+        );
+
+        // A region selected towards negative x, two bits per entry
+        let read = Litematic::from_nbt(&nbt! {
+            Version: 6,
+            MinecraftDataVersion: 3465,
+            Metadata: { Name: "negative" },
+            Regions: {
+                main: {
+                    Position: { x: 2, y: 0, z: 0 },
+                    Size: { x: -2, y: 1, z: 1 },
+                    BlockStatePalette: [
+                        { Name: "minecraft:air" },
+                        { Name: "minecraft:stone" },
+                        { Name: "minecraft:dirt" },
+                    ],
+                    BlockStates: [L; 9],
+                    TileEntities: [],
+                    Entities: [],
+                    PendingBlockTicks: [],
+                },
+            },
+        })
+        .unwrap();
+        let region = &read.regions[0];
+        assert_eq!(region.position, Position::new(1, 0, 0));
+        assert_eq!(
+            region
+                .template
+                .block_state(Position::new(1, 0, 0))
+                .unwrap()
+                .name,
+            crate::id::Identifier::minecraft("dirt")
+        );
+        assert!(region.extra.contains_key("PendingBlockTicks"));
+
+        // invalid files are errors instead of panics or huge allocations
+        let sponge = |size: i16, palette: Value, data: Value, pos: f64| {
+            SpongeSchematic::from_nbt(&nbt! {
+                Version: 2,
+                DataVersion: 3465,
+                Width: size,
+                Height: size,
+                Length: size,
+                Palette: palette,
+                BlockData: data,
+                Entities: [{ Id: "minecraft:pig", Pos: [pos, 0d, 0d] }],
+            })
+            .map(|_| ())
+            .unwrap_err()
+            .to_string()
+        };
+        let errors = [
+            sponge(
+                1,
+                nbt! { stone: 2147483647 },
+                Value::ByteArray(vec![0]),
+                0.0,
+            ),
+            sponge(1, nbt! { stone: -1 }, Value::ByteArray(vec![0]), 0.0),
+            sponge(-1, nbt! { stone: 0 }, Value::ByteArray(vec![0]), 0.0),
+            sponge(1, nbt! { stone: 0 }, Value::ByteArray(vec![0]), -1.0),
+        ];
+        assert_eq!(
+            errors,
+            [
+                "Block id 0 is not in the palette at `BlockData`",
+                "Invalid block id -1 at `Palette.stone`",
+                "Expected at least 281462092005375 bytes of block data, found 1 at `BlockData`",
+                "Structure positions must be between 0 and 2147483646, found [-1, 0, 0] at `Entities[0].Pos`",
+            ]
+        );
+        let litematic = |size: i32, pos: f64| {
+            Litematic::from_nbt(&nbt! {
+                Version: 6,
+                MinecraftDataVersion: 3465,
+                Regions: {
+                    main: {
+                        Position: { x: 0, y: 0, z: 0 },
+                        Size: { x: size, y: size, z: size },
+                        BlockStatePalette: [{ Name: "minecraft:air" }],
+                        BlockStates: [L; 0],
+                        Entities: [{ id: "minecraft:pig", Pos: [pos, 0d, 0d] }],
+                    },
+                },
+            })
+            .map(|_| ())
+            .unwrap_err()
+            .to_string()
+        };
+        assert_eq!(
+            litematic(i32::MIN, 0.0),
+            "Region of size [-2147483648, -2147483648, -2147483648] at [0, 0, 0] is out of range \
+             at `Regions.main.Size`"
+        );
+        assert_eq!(
+            litematic(i32::MAX, 0.0),
+            "Region of size [2147483647, 2147483647, 2147483647] at [0, 0, 0] is out of range \
+             at `Regions.main.Size`"
+        );
+        assert_eq!(
+            litematic(1 << 16, 0.0),
+            "Expected 8796093022208 longs, found 1 at `Regions.main.BlockStates`"
+        );
+        assert_eq!(
+            litematic(0, -1.0),
+            "Structure positions must be between 0 and 2147483646, found [-1, 0, 0] \
+             at `Regions.main.Entities[0].Pos`"
+        );
+    }
 }
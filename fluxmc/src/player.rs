@@ -0,0 +1,344 @@
+//! Player data, the gzipped `playerdata/<uuid>.dat` files of a world, in the format of 1.20.1
+//! and earlier.
+//!
+//! Only the commonly edited parts of a player are typed, every other entry is kept in
+//! [`PlayerData::extra`] and written back unchanged:
+//!
+//! ```no_run
+//! # use fluxmc::{item::{ItemStack, Material}, player::{InventoryItem, PlayerData}};
+//! let mut player = PlayerData::load("world/playerdata/069a79f4-44e9-4726-a5be-fca90e38aaf5.dat")?;
+//! player.xp_level += 10;
+//! player.set_item(InventoryItem::from_stack(0, &ItemStack::new(Material::DiamondSword))?);
+//! player.save("world/playerdata/069a79f4-44e9-4726-a5be-fca90e38aaf5.dat")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use flux_nbt::{
+    macros::FromNbtError,
+    nbt,
+    ser::{self, SerError},
+    stream::{self, Compression, NbtOptions, StreamError},
+    FromNbt, Map, Nbt, Value,
+};
+
+use crate::{
+    attr::{uuid_ints, Attribute, AttributeModifier, AttributeOperation},
+    effect::CustomPotionEffect,
+    err::NbtFileError,
+    id::Identifier,
+    item::ItemStack,
+    util::{compound, field, optional_field, unknown_fields},
+};
+
+const PLAYER_KEYS: [&str; 14] = [
+    "DataVersion",
+    "Inventory",
+    "EnderItems",
+    "Pos",
+    "Rotation",
+    "Dimension",
+    "Health",
+    "foodLevel",
+    "XpLevel",
+    "XpP",
+    "XpTotal",
+    "ActiveEffects",
+    "Attributes",
+    "SelectedItemSlot",
+];
+
+/// The data of a player, missing entries take the values of a newly joined player
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerData {
+    pub data_version: i32,
+    /// Items by slot, `0..=8` is the hotbar, `9..=35` the main inventory, `100..=103` the armor
+    /// from boots to helmet and `-106` the offhand
+    pub inventory: Vec<InventoryItem>,
+    /// Items of the ender chest, in slots `0..=26`
+    pub ender_items: Vec<InventoryItem>,
+    pub pos: [f64; 3],
+    /// Yaw and pitch in degrees
+    pub rotation: [f32; 2],
+    pub dimension: Identifier,
+    pub health: f32,
+    pub food_level: i32,
+    pub selected_slot: i32,
+    pub xp_level: i32,
+    /// Progress towards the next level, from `0` to `1`
+    pub xp_progress: f32,
+    pub xp_total: i32,
+    pub effects: Vec<CustomPotionEffect>,
+    pub attributes: Vec<EntityAttribute>,
+    /// Every other entry, like the game mode, abilities and recipe book
+    pub extra: Map<String, Value>,
+}
+
+/// An item in an inventory slot, its tag kept as NBT
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryItem {
+    pub slot: i8,
+    pub id: Identifier,
+    pub count: i8,
+    pub tag: Option<Map<String, Value>>,
+}
+
+/// An attribute of an entity, with its base value and active modifiers
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityAttribute {
+    pub attribute: Attribute,
+    pub base: f64,
+    /// Modifiers currently applied, e.g. by effects. Their attribute matches this one and they
+    /// have no slot.
+    pub modifiers: Vec<AttributeModifier>,
+}
+
+impl PlayerData {
+    pub fn new(data_version: i32) -> Self {
+        Self {
+            data_version,
+            inventory: Vec::new(),
+            ender_items: Vec::new(),
+            pos: [0.0; 3],
+            rotation: [0.0; 2],
+            dimension: Identifier::minecraft("overworld"),
+            health: 20.0,
+            food_level: 20,
+            selected_slot: 0,
+            xp_level: 0,
+            xp_progress: 0.0,
+            xp_total: 0,
+            effects: Vec::new(),
+            attributes: Vec::new(),
+            extra: Map::new(),
+        }
+    }
+
+    /// Reads gzipped player data
+    pub fn read<R: Read>(reader: R) -> Result<Self, NbtFileError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        let (_, value) = stream::read_value(reader, &options)?;
+        Ok(Self::from_nbt(&value)?)
+    }
+
+    /// Writes the player data gzipped, the way the game saves it
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), StreamError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        stream::write_value(writer, &self.nbt(), &options)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NbtFileError> {
+        let file = File::open(path).map_err(StreamError::from)?;
+        Self::read(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StreamError> {
+        self.write(File::create(path)?)
+    }
+
+    pub fn item(&self, slot: i8) -> Option<&InventoryItem> {
+        self.inventory.iter().find(|item| item.slot == slot)
+    }
+
+    /// Puts an item into the inventory, replacing the item in its slot
+    pub fn set_item(&mut self, item: InventoryItem) -> Option<InventoryItem> {
+        match self
+            .inventory
+            .iter_mut()
+            .find(|other| other.slot == item.slot)
+        {
+            Some(other) => Some(std::mem::replace(other, item)),
+            None => {
+                self.inventory.push(item);
+                None
+            }
+        }
+    }
+
+    pub fn remove_item(&mut self, slot: i8) -> Option<InventoryItem> {
+        let index = self.inventory.iter().position(|item| item.slot == slot)?;
+        Some(self.inventory.remove(index))
+    }
+
+    pub fn attribute(&self, attribute: &Attribute) -> Option<&EntityAttribute> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.attribute == *attribute)
+    }
+}
+
+impl InventoryItem {
+    pub fn new(slot: i8, id: Identifier, count: i8) -> Self {
+        Self {
+            slot,
+            id,
+            count,
+            tag: None,
+        }
+    }
+
+    /// Serializes the tag of an item stack into an item for a slot
+    pub fn from_stack(slot: i8, stack: &ItemStack) -> Result<Self, SerError> {
+        let tag = match ser::to_nbt(stack.meta())? {
+            Value::Compound(tag) if !tag.is_empty() => Some(tag),
+            _ => None,
+        };
+        Ok(Self {
+            slot,
+            id: stack.id().clone(),
+            count: stack.count(),
+            tag,
+        })
+    }
+}
+
+impl EntityAttribute {
+    pub fn new(attribute: Attribute, base: f64) -> Self {
+        Self {
+            attribute,
+            base,
+            modifiers: Vec::new(),
+        }
+    }
+}
+
+impl Nbt for PlayerData {
+    fn nbt(&self) -> Value {
+        nbt! {
+            DataVersion: (self.data_version),
+            Inventory: (self.inventory),
+            EnderItems: (self.ender_items),
+            Pos: (self.pos),
+            Rotation: (self.rotation),
+            Dimension: (self.dimension),
+            Health: (self.health),
+            foodLevel: (self.food_level),
+            SelectedItemSlot: (self.selected_slot),
+            XpLevel: (self.xp_level),
+            XpP: (self.xp_progress),
+            XpTotal: (self.xp_total),
+            ActiveEffects?: (Some(&self.effects).filter(|effects| !effects.is_empty()).cloned()),
+            Attributes: (self.attributes),
+            ..self.extra
+        }
+    }
+}
+
+impl FromNbt for PlayerData {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let default = Self::new(0);
+        Ok(Self {
+            data_version: field(cmp, "DataVersion")?,
+            inventory: optional_field(cmp, "Inventory")?.unwrap_or_default(),
+            ender_items: optional_field(cmp, "EnderItems")?.unwrap_or_default(),
+            pos: optional_field(cmp, "Pos")?.unwrap_or(default.pos),
+            rotation: optional_field(cmp, "Rotation")?.unwrap_or(default.rotation),
+            dimension: optional_field(cmp, "Dimension")?.unwrap_or(default.dimension),
+            health: optional_field(cmp, "Health")?.unwrap_or(default.health),
+            food_level: optional_field(cmp, "foodLevel")?.unwrap_or(default.food_level),
+            selected_slot: optional_field(cmp, "SelectedItemSlot")?.unwrap_or_default(),
+            xp_level: optional_field(cmp, "XpLevel")?.unwrap_or_default(),
+            xp_progress: optional_field(cmp, "XpP")?.unwrap_or_default(),
+            xp_total: optional_field(cmp, "XpTotal")?.unwrap_or_default(),
+            effects: optional_field(cmp, "ActiveEffects")?.unwrap_or_default(),
+            attributes: optional_field(cmp, "Attributes")?.unwrap_or_default(),
+            extra: unknown_fields(cmp, &PLAYER_KEYS),
+        })
+    }
+}
+
+impl Nbt for InventoryItem {
+    fn nbt(&self) -> Value {
+        nbt! {
+            Slot: (self.slot),
+            id: (self.id),
+            Count: (self.count),
+            tag?: (self.tag),
+        }
+    }
+}
+
+impl FromNbt for InventoryItem {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        Ok(Self {
+            slot: field(cmp, "Slot")?,
+            id: field(cmp, "id")?,
+            count: field(cmp, "Count")?,
+            tag: optional_field(cmp, "tag")?,
+        })
+    }
+}
+
+impl Nbt for EntityAttribute {
+    fn nbt(&self) -> Value {
+        let modifiers: Vec<Value> = self
+            .modifiers
+            .iter()
+            .map(|modifier| {
+                nbt! {
+                    Amount: (modifier.amount),
+                    Name?: (modifier.name),
+                    Operation: (modifier.operation),
+                    UUID?: (modifier.uuid().map(uuid_ints)),
+                }
+            })
+            .collect();
+        nbt! {
+            Name: (namespaced(&self.attribute)),
+            Base: (self.base),
+            Modifiers?: (Some(modifiers).filter(|modifiers| !modifiers.is_empty())),
+        }
+    }
+}
+
+impl FromNbt for EntityAttribute {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let attribute = Attribute::from(field::<String>(cmp, "Name")?);
+        let modifiers: Vec<Value> = optional_field(cmp, "Modifiers")?.unwrap_or_default();
+        let modifiers = modifiers
+            .iter()
+            .map(|modifier| {
+                let cmp = compound(modifier)?;
+                let mut modifier = AttributeModifier::new(
+                    attribute.clone(),
+                    AttributeOperation::Add,
+                    field(cmp, "Amount")?,
+                );
+                modifier.name = optional_field(cmp, "Name")?;
+                modifier.operation = field(cmp, "Operation")?;
+                if let Some(uuid) = optional_field(cmp, "UUID")? {
+                    modifier.set_uuid(uuid);
+                }
+                Ok(modifier)
+            })
+            .enumerate()
+            .map(|(index, modifier)| {
+                modifier.map_err(|err: FromNbtError| {
+                    err.within(ser::PathSegment::Index(index))
+                        .within(ser::PathSegment::Field("Modifiers".to_owned()))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            attribute,
+            base: field(cmp, "Base")?,
+            modifiers,
+        })
+    }
+}
+
+/// Entities store attribute names with their namespace, unlike item modifiers
+fn namespaced(attribute: &Attribute) -> String {
+    match attribute {
+        Attribute::Custom(name) => name.clone(),
+        known => format!("minecraft:{known}"),
+    }
+}
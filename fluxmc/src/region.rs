@@ -0,0 +1,309 @@
+//! Anvil region files (`r.<x>.<z>.mca`), which store the chunks of a world in regions of 32 by 32 chunks.
+//!
+//! A region starts with a header of two tables with an entry per chunk, the location of the
+//! chunk in sectors of 4 KiB and the time it was last saved at. Every chunk is stored as its
+//! length, a compression type and the compressed NBT.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flux_nbt::{
+    macros::FromNbtError,
+    stream::{self, Compression, NbtOptions, StreamError},
+};
+use thiserror::Error;
+
+use crate::{loc::Position, structure::BlockState};
+
+mod chunk;
+mod lz4;
+
+pub use chunk::Chunk;
+
+const SECTOR: usize = 4096;
+const CHUNKS: usize = 32 * 32;
+/// Sectors taken up by the location and timestamp tables
+const HEADER_SECTORS: usize = 2;
+
+/// Compression of a chunk, stored in front of its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkCompression {
+    Gzip,
+    /// Used by the game unless configured otherwise
+    #[default]
+    Zlib,
+    None,
+    /// Available since 1.20.5
+    Lz4,
+}
+
+impl ChunkCompression {
+    pub fn id(self) -> u8 {
+        match self {
+            ChunkCompression::Gzip => 1,
+            ChunkCompression::Zlib => 2,
+            ChunkCompression::None => 3,
+            ChunkCompression::Lz4 => 4,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(ChunkCompression::Gzip),
+            2 => Some(ChunkCompression::Zlib),
+            3 => Some(ChunkCompression::None),
+            4 => Some(ChunkCompression::Lz4),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RegionError {
+    #[error("An IO error has occurred: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Stream(#[from] StreamError),
+    #[error("Invalid chunk NBT: {0}")]
+    Nbt(#[from] FromNbtError),
+    #[error("Unknown chunk compression type {0}")]
+    UnknownCompression(u8),
+    #[error("Chunk {x}, {z} is stored in an external `.mcc` file")]
+    ExternalChunk { x: i32, z: i32 },
+    #[error("Chunk of {0} bytes does not fit into a region file")]
+    ChunkTooLarge(usize),
+    #[error("Invalid chunk data: {0}")]
+    InvalidChunk(&'static str),
+}
+
+/// A region file, reading and writing chunks in place.
+///
+/// Chunk coordinates are taken modulo 32, so both world and region-local chunk coordinates
+/// address the chunks of a region. Chunks too large for a region, which the game moves to
+/// external `.mcc` files, are not supported.
+#[derive(Debug)]
+pub struct RegionFile<F> {
+    inner: F,
+    /// Offset in sectors shifted by 8, combined with the length in sectors, `0` for missing chunks
+    locations: Vec<u32>,
+    /// Seconds since the unix epoch
+    timestamps: Vec<u32>,
+}
+
+/// Name of the region file containing a chunk, e.g. `r.-1.0.mca`
+pub fn file_name(chunk_x: i32, chunk_z: i32) -> String {
+    format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5)
+}
+
+impl RegionFile<File> {
+    /// Opens a region file for reading and writing, creating it if it does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RegionError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Self::new(file)
+    }
+}
+
+impl<F: Read + Seek> RegionFile<F> {
+    /// Reads the header of a region, an empty file is an empty region
+    pub fn new(mut inner: F) -> Result<Self, RegionError> {
+        let mut header = vec![0; HEADER_SECTORS * SECTOR];
+        inner.seek(SeekFrom::Start(0))?;
+        let mut read = 0;
+        while read < header.len() {
+            match inner.read(&mut header[read..])? {
+                0 if read == 0 => break,
+                0 => return Err(RegionError::InvalidChunk("Truncated region header")),
+                len => read += len,
+            }
+        }
+        let table = |start: usize| {
+            header[start..start + SECTOR]
+                .chunks_exact(4)
+                .map(|entry| u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]))
+                .collect()
+        };
+        Ok(Self {
+            locations: table(0),
+            timestamps: table(SECTOR),
+            inner,
+        })
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    pub fn has_chunk(&self, x: i32, z: i32) -> bool {
+        self.locations[index(x, z)] != 0
+    }
+
+    /// Time the chunk was last saved at, in seconds since the unix epoch
+    pub fn timestamp(&self, x: i32, z: i32) -> Option<u32> {
+        self.has_chunk(x, z).then(|| self.timestamps[index(x, z)])
+    }
+
+    /// Region-local coordinates of every stored chunk
+    pub fn chunk_positions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..CHUNKS)
+            .filter(|index| self.locations[*index] != 0)
+            .map(|index| ((index % 32) as i32, (index / 32) as i32))
+    }
+
+    /// Reads every stored chunk, along with its region-local coordinates
+    pub fn chunks(&mut self) -> impl Iterator<Item = Result<(i32, i32, Chunk), RegionError>> + '_ {
+        let positions: Vec<(i32, i32)> = self.chunk_positions().collect();
+        positions.into_iter().filter_map(move |(x, z)| {
+            self.read_chunk(x, z)
+                .map(|chunk| chunk.map(|chunk| (x, z, chunk)))
+                .transpose()
+        })
+    }
+
+    pub fn read_chunk(&mut self, x: i32, z: i32) -> Result<Option<Chunk>, RegionError> {
+        let location = self.locations[index(x, z)];
+        if location == 0 {
+            return Ok(None);
+        }
+        let (offset, sectors) = ((location >> 8) as u64, (location & 0xff) as usize);
+        self.inner.seek(SeekFrom::Start(offset * SECTOR as u64))?;
+        let mut prefix = [0; 5];
+        self.inner.read_exact(&mut prefix)?;
+        let len = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        if prefix[4] & 0x80 != 0 {
+            return Err(RegionError::ExternalChunk { x, z });
+        }
+        if len == 0 || len + 4 > sectors * SECTOR {
+            return Err(RegionError::InvalidChunk(
+                "Chunk length exceeds its sectors",
+            ));
+        }
+        let compression = ChunkCompression::from_id(prefix[4])
+            .ok_or(RegionError::UnknownCompression(prefix[4]))?;
+        let mut data = vec![0; len - 1];
+        self.inner.read_exact(&mut data)?;
+        let (data, compression) = match compression {
+            ChunkCompression::Gzip => (data, Compression::Gzip),
+            ChunkCompression::Zlib => (data, Compression::Zlib),
+            ChunkCompression::None => (data, Compression::None),
+            ChunkCompression::Lz4 => (lz4::decompress(&data)?, Compression::None),
+        };
+        let options = NbtOptions::new().with_compression(compression);
+        let (_, nbt) = stream::read_value(data.as_slice(), &options)?;
+        Ok(Some(Chunk::new(nbt)))
+    }
+
+    /// Looks up the block state at a world position within this region.
+    ///
+    /// Positions outside of this region are `None`, as told by the position stored in the
+    /// chunk at their wrapped coordinates.
+    pub fn block_state(&mut self, pos: Position) -> Result<Option<BlockState>, RegionError> {
+        let (x, z) = (pos.x() >> 4, pos.z() >> 4);
+        match self.read_chunk(x, z)? {
+            Some(chunk) if chunk.position() == Some((x, z)) => Ok(chunk.block_state(pos)?),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<F: Read + Write + Seek> RegionFile<F> {
+    /// Writes a chunk into the first free sectors large enough.
+    ///
+    /// The previous sectors of the chunk are only freed once the header points at the new ones,
+    /// so the old chunk stays readable if writing fails midway.
+    pub fn write_chunk(
+        &mut self,
+        x: i32,
+        z: i32,
+        chunk: &Chunk,
+        compression: ChunkCompression,
+    ) -> Result<(), RegionError> {
+        let options = NbtOptions::new().with_compression(match compression {
+            ChunkCompression::Gzip => Compression::Gzip,
+            ChunkCompression::Zlib => Compression::Zlib,
+            ChunkCompression::None | ChunkCompression::Lz4 => Compression::None,
+        });
+        let mut data = Vec::new();
+        stream::write_value(&mut data, &chunk.nbt, &options)?;
+        if compression == ChunkCompression::Lz4 {
+            data = lz4::compress(&data);
+        }
+
+        let mut payload = Vec::with_capacity(data.len() + 5);
+        payload.extend_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+        payload.push(compression.id());
+        payload.extend_from_slice(&data);
+        let sectors = payload.len().div_ceil(SECTOR);
+        if sectors > 0xff {
+            return Err(RegionError::ChunkTooLarge(payload.len()));
+        }
+        payload.resize(sectors * SECTOR, 0);
+
+        let index = index(x, z);
+        let offset = self.allocate(sectors);
+        self.inner.seek(SeekFrom::Start((offset * SECTOR) as u64))?;
+        self.inner.write_all(&payload)?;
+        self.locations[index] = (offset as u32) << 8 | sectors as u32;
+        self.timestamps[index] = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as u32);
+        self.write_header()
+    }
+
+    /// Removes a chunk from the header, returning whether it was stored
+    pub fn remove_chunk(&mut self, x: i32, z: i32) -> Result<bool, RegionError> {
+        let index = index(x, z);
+        if self.locations[index] == 0 {
+            return Ok(false);
+        }
+        self.locations[index] = 0;
+        self.timestamps[index] = 0;
+        self.write_header()?;
+        Ok(true)
+    }
+
+    pub fn flush(&mut self) -> Result<(), RegionError> {
+        Ok(self.inner.flush()?)
+    }
+
+    /// Finds the first run of sectors not used by any chunk
+    fn allocate(&self, sectors: usize) -> usize {
+        let mut used: Vec<(usize, usize)> = self
+            .locations
+            .iter()
+            .filter(|location| **location != 0)
+            .map(|location| ((location >> 8) as usize, (location & 0xff) as usize))
+            .collect();
+        used.sort_unstable();
+        let mut start = HEADER_SECTORS;
+        for (offset, len) in used {
+            if offset >= start + sectors {
+                break;
+            }
+            start = start.max(offset + len);
+        }
+        start
+    }
+
+    fn write_header(&mut self) -> Result<(), RegionError> {
+        let mut header = Vec::with_capacity(HEADER_SECTORS * SECTOR);
+        for entry in self.locations.iter().chain(&self.timestamps) {
+            header.extend_from_slice(&entry.to_be_bytes());
+        }
+        self.inner.seek(SeekFrom::Start(0))?;
+        self.inner.write_all(&header)?;
+        Ok(())
+    }
+}
+
+fn index(x: i32, z: i32) -> usize {
+    (x.rem_euclid(32) + z.rem_euclid(32) * 32) as usize
+}
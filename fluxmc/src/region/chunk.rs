@@ -0,0 +1,161 @@
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    ser::PathSegment,
+    FromNbt, Map, Value,
+};
+
+use crate::{loc::Position, structure::BlockState};
+
+/// The NBT of a chunk in the format of 1.18 and later, with block lookups by world position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub nbt: Value,
+}
+
+impl Chunk {
+    pub fn new(nbt: Value) -> Self {
+        Self { nbt }
+    }
+
+    pub fn data_version(&self) -> Option<i32> {
+        self.get("DataVersion").and_then(|v| i32::from_nbt(v).ok())
+    }
+
+    /// Chunk coordinates, as stored in `xPos` and `zPos`
+    pub fn position(&self) -> Option<(i32, i32)> {
+        let x = i32::from_nbt(self.get("xPos")?).ok()?;
+        let z = i32::from_nbt(self.get("zPos")?).ok()?;
+        Some((x, z))
+    }
+
+    /// Decodes the block state at a world position, `None` if the chunk has no section there.
+    ///
+    /// Only the x and z coordinates within the chunk are used.
+    pub fn block_state(&self, pos: Position) -> Result<Option<BlockState>, FromNbtError> {
+        let Some(sections) = self.get("sections") else {
+            return Ok(None);
+        };
+        let Value::List(sections) = sections else {
+            return Err(FromNbtError::mismatch("TAG_List", sections).within(field("sections")));
+        };
+        let section_y = pos.y().div_euclid(16);
+        let found = sections.iter().enumerate().find(|(_, section)| {
+            matches!(section, Value::Compound(cmp)
+                if cmp.get("Y").and_then(|y| i32::from_nbt(y).ok()) == Some(section_y))
+        });
+        let Some((index, Value::Compound(section))) = found else {
+            return Ok(None);
+        };
+        let Some(states) = section.get("block_states") else {
+            return Ok(None);
+        };
+        let local = ((pos.y().rem_euclid(16) * 16 + pos.z().rem_euclid(16)) * 16
+            + pos.x().rem_euclid(16)) as usize;
+        block_state(states, local).map(Some).map_err(|err| {
+            err.within(field("block_states"))
+                .within(PathSegment::Index(index))
+                .within(field("sections"))
+        })
+    }
+
+    /// The block entity at a world position
+    pub fn block_entity(&self, pos: Position) -> Option<&Map<String, Value>> {
+        match self.get("block_entities")? {
+            Value::List(entities) => entities.iter().find_map(|entity| match entity {
+                Value::Compound(cmp) if is_at(cmp, pos) => Some(cmp),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn block_entity_mut(&mut self, pos: Position) -> Option<&mut Map<String, Value>> {
+        let Value::Compound(cmp) = &mut self.nbt else {
+            return None;
+        };
+        match cmp.get_mut("block_entities")? {
+            Value::List(entities) => entities.iter_mut().find_map(|entity| match entity {
+                Value::Compound(cmp) if is_at(cmp, pos) => Some(cmp),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Value> {
+        match &self.nbt {
+            Value::Compound(cmp) => cmp.get(key),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for Chunk {
+    fn from(nbt: Value) -> Self {
+        Self::new(nbt)
+    }
+}
+
+/// Reads an entry of a paletted container, whose indices are packed into longs without
+/// spanning across them and use at least 4 bits
+fn block_state(states: &Value, index: usize) -> Result<BlockState, FromNbtError> {
+    let Value::Compound(states) = states else {
+        return Err(FromNbtError::mismatch("TAG_Compound", states));
+    };
+    let palette = match states.get("palette") {
+        Some(Value::List(palette)) if !palette.is_empty() => palette,
+        Some(Value::List(_)) => {
+            return Err(custom("The palette is empty".to_owned()).within(field("palette")))
+        }
+        Some(other) => {
+            return Err(FromNbtError::mismatch("TAG_List", other).within(field("palette")))
+        }
+        None => return Err(FromNbtErrorKind::MissingField("palette").into()),
+    };
+    let palette_index = match states.get("data") {
+        _ if palette.len() == 1 => 0,
+        Some(Value::LongArray(data)) => {
+            let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(4) as usize;
+            let per_long = 64 / bits;
+            let long = data.get(index / per_long).ok_or_else(|| {
+                custom(format!(
+                    "Expected {} longs, found {}",
+                    4096usize.div_ceil(per_long),
+                    data.len()
+                ))
+                .within(field("data"))
+            })?;
+            (*long as u64 >> (index % per_long * bits) & ((1 << bits) - 1)) as usize
+        }
+        Some(other) => {
+            return Err(FromNbtError::mismatch("TAG_Long_Array", other).within(field("data")))
+        }
+        None => return Err(FromNbtErrorKind::MissingField("data").into()),
+    };
+    let state = palette.get(palette_index).ok_or_else(|| {
+        custom(format!(
+            "Palette index {palette_index} is out of bounds for {} states",
+            palette.len()
+        ))
+        .within(field("data"))
+    })?;
+    BlockState::from_nbt(state).map_err(|err| {
+        err.within(PathSegment::Index(palette_index))
+            .within(field("palette"))
+    })
+}
+
+fn is_at(cmp: &Map<String, Value>, pos: Position) -> bool {
+    let coordinate = |key| cmp.get(key).and_then(|v| i32::from_nbt(v).ok());
+    coordinate("x") == Some(pos.x())
+        && coordinate("y") == Some(pos.y())
+        && coordinate("z") == Some(pos.z())
+}
+
+fn field(key: &str) -> PathSegment {
+    PathSegment::Field(key.to_owned())
+}
+
+fn custom(message: String) -> FromNbtError {
+    FromNbtErrorKind::Custom(message).into()
+}
@@ -0,0 +1,89 @@
+//! The block stream format of lz4-java, which the game writes LZ4 compressed chunks in.
+//!
+//! Every block starts with the magic `LZ4Block`, a token holding the compression method and
+//! level, the compressed and decompressed length and a checksum of the decompressed data,
+//! the stream ends with an empty block.
+
+use xxhash_rust::xxh32::xxh32;
+
+use super::RegionError;
+
+const MAGIC: &[u8; 8] = b"LZ4Block";
+const HEADER_LEN: usize = MAGIC.len() + 13;
+const BLOCK_SIZE: usize = 1 << 16;
+const SEED: u32 = 0x9747b28c;
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+/// Compression level lz4-java derives from the block size
+const LEVEL: u8 = 6;
+
+pub fn decompress(mut input: &[u8]) -> Result<Vec<u8>, RegionError> {
+    let mut out = Vec::new();
+    loop {
+        if input.len() < HEADER_LEN || &input[..MAGIC.len()] != MAGIC {
+            return Err(RegionError::InvalidChunk("Invalid LZ4 block header"));
+        }
+        let token = input[8];
+        let compressed_len = read_u32(&input[9..]) as usize;
+        let len = read_u32(&input[13..]) as usize;
+        let checksum = read_u32(&input[17..]);
+        input = &input[HEADER_LEN..];
+        if len == 0 {
+            return Ok(out);
+        }
+        if compressed_len > input.len() || len > BLOCK_SIZE {
+            return Err(RegionError::InvalidChunk("Truncated LZ4 block"));
+        }
+        let (data, rest) = input.split_at(compressed_len);
+        let start = out.len();
+        match token & 0xf0 {
+            METHOD_RAW if compressed_len == len => out.extend_from_slice(data),
+            METHOD_LZ4 => {
+                out.resize(start + len, 0);
+                match lz4_flex::block::decompress_into(data, &mut out[start..]) {
+                    Ok(written) if written == len => {}
+                    _ => return Err(RegionError::InvalidChunk("Malformed LZ4 block")),
+                }
+            }
+            _ => return Err(RegionError::InvalidChunk("Unknown LZ4 block method")),
+        }
+        if xxh32(&out[start..], SEED) & 0x0fff_ffff != checksum {
+            return Err(RegionError::InvalidChunk("LZ4 block checksum mismatch"));
+        }
+        input = rest;
+    }
+}
+
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + HEADER_LEN);
+    for block in input.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        let (method, data) = if compressed.len() < block.len() {
+            (METHOD_LZ4, compressed.as_slice())
+        } else {
+            (METHOD_RAW, block)
+        };
+        write_header(
+            &mut out,
+            method,
+            data.len(),
+            block.len(),
+            xxh32(block, SEED) & 0x0fff_ffff,
+        );
+        out.extend_from_slice(data);
+    }
+    write_header(&mut out, METHOD_RAW, 0, 0, 0);
+    out
+}
+
+fn write_header(out: &mut Vec<u8>, method: u8, compressed_len: usize, len: usize, checksum: u32) {
+    out.extend_from_slice(MAGIC);
+    out.push(method | LEVEL);
+    out.extend_from_slice(&(compressed_len as u32).to_le_bytes());
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+    out.extend_from_slice(&checksum.to_le_bytes());
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
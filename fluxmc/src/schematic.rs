@@ -0,0 +1,21 @@
+//! Schematic files of other tools, converted from and to [structure templates](crate::structure).
+//!
+//! - [`SpongeSchematic`]: the `.schem` files of WorldEdit and other Sponge Schematic tools,
+//!   versions 2 and 3
+//! - [`Litematic`]: the `.litematic` files of Litematica, with one or more regions
+//!
+//! A schematic is usually turned into a structure file or a function file:
+//!
+//! ```no_run
+//! # use fluxmc::schematic::SpongeSchematic;
+//! let schematic = SpongeSchematic::load("house.schem")?;
+//! schematic.template.save("house.nbt")?;
+//! std::fs::write("house.mcfunction", schematic.template.commands().join("\n"))?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod litematica;
+pub mod sponge;
+
+pub use litematica::{Litematic, LitematicRegion};
+pub use sponge::SpongeSchematic;
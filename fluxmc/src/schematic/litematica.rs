@@ -0,0 +1,481 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    nbt,
+    ser::PathSegment,
+    stream::{self, Compression, NbtOptions, StreamError},
+    FromNbt, Map, Nbt, Value,
+};
+
+use crate::{
+    err::NbtFileError,
+    id::Identifier,
+    item::Material,
+    loc::Position,
+    structure::{BlockState, StructureTemplate},
+    util::{compound, field, optional_field, unknown_fields},
+};
+
+/// Metadata entries with typed fields or computed from the regions when writing
+const METADATA_KEYS: [&str; 7] = [
+    "Name",
+    "Author",
+    "Description",
+    "EnclosingSize",
+    "RegionCount",
+    "TotalBlocks",
+    "TotalVolume",
+];
+
+/// A Litematica schematic, made up of named regions.
+///
+/// Air is left out of the region templates, the way Litematica skips it when pasting by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Litematic {
+    /// Format version the schematic is written with
+    pub version: i32,
+    pub sub_version: Option<i32>,
+    pub data_version: i32,
+    pub name: String,
+    pub author: String,
+    pub description: String,
+    /// Other metadata entries, like the creation time and preview image
+    pub metadata: Map<String, Value>,
+    pub regions: Vec<LitematicRegion>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LitematicRegion {
+    pub name: String,
+    /// Minimum corner of the region, relative to the origin of the schematic. Regions selected
+    /// towards negative coordinates are read with their corners swapped.
+    pub position: Position,
+    /// Blocks and entities, with positions relative to the minimum corner. Its size is the size
+    /// of the region.
+    pub template: StructureTemplate,
+    /// Other entries, like pending block and fluid ticks
+    pub extra: Map<String, Value>,
+}
+
+impl Litematic {
+    pub fn new<N: Into<String>>(data_version: i32, name: N) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as i64);
+        Self {
+            version: 6,
+            sub_version: Some(1),
+            data_version,
+            name: name.into(),
+            author: String::new(),
+            description: String::new(),
+            metadata: match nbt! { TimeCreated: now, TimeModified: now } {
+                Value::Compound(metadata) => metadata,
+                _ => unreachable!(),
+            },
+            regions: Vec::new(),
+        }
+    }
+
+    /// A schematic with a single region of a template, named like the schematic
+    pub fn from_template<N: Into<String>>(name: N, template: StructureTemplate) -> Self {
+        let name = name.into();
+        let mut litematic = Self::new(template.data_version, name.clone());
+        litematic.regions.push(LitematicRegion {
+            name,
+            position: Position::new(0, 0, 0),
+            template,
+            extra: Map::new(),
+        });
+        litematic
+    }
+
+    /// Merges the regions into one template, positioned relative to their minimum corner
+    pub fn to_template(&self) -> StructureTemplate {
+        let (min, max) = self.bounds();
+        let mut template = StructureTemplate::new(self.data_version);
+        template.set_size([max[0] - min[0], max[1] - min[1], max[2] - min[2]]);
+        for region in &self.regions {
+            let corner = region.position;
+            let offset = [
+                corner.x() - min[0],
+                corner.y() - min[1],
+                corner.z() - min[2],
+            ];
+            let palette = region.template.palette();
+            for block in region.template.blocks() {
+                let pos = Position::new(
+                    block.pos.x() + offset[0],
+                    block.pos.y() + offset[1],
+                    block.pos.z() + offset[2],
+                );
+                let state = palette[block.state].clone();
+                match &block.nbt {
+                    Some(nbt) => {
+                        template.set_block_entity(pos, state, Value::Compound(nbt.clone()))
+                    }
+                    None => template.set_block(pos, state),
+                }
+            }
+            for entity in region.template.entities() {
+                let pos = [
+                    entity.pos[0] + offset[0] as f64,
+                    entity.pos[1] + offset[1] as f64,
+                    entity.pos[2] + offset[2] as f64,
+                ];
+                template.add_entity(pos, Value::Compound(entity.nbt.clone()));
+            }
+        }
+        template
+    }
+
+    /// Reads a gzipped schematic
+    pub fn read<R: Read>(reader: R) -> Result<Self, NbtFileError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        let (_, value) = stream::read_value(reader, &options)?;
+        Ok(Self::from_nbt(&value)?)
+    }
+
+    /// Writes the schematic gzipped, the way Litematica saves it
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), StreamError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        stream::write_value(writer, &self.nbt(), &options)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NbtFileError> {
+        let file = File::open(path).map_err(StreamError::from)?;
+        Self::read(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StreamError> {
+        self.write(File::create(path)?)
+    }
+
+    /// Minimum and exclusive maximum corner enclosing every region
+    fn bounds(&self) -> ([i32; 3], [i32; 3]) {
+        let mut min = [i32::MAX; 3];
+        let mut max = [i32::MIN; 3];
+        for region in &self.regions {
+            let corner = <[i32; 3]>::from(region.position);
+            let size = region.template.size();
+            for axis in 0..3 {
+                min[axis] = min[axis].min(corner[axis]);
+                max[axis] = max[axis].max(corner[axis] + size[axis]);
+            }
+        }
+        if self.regions.is_empty() {
+            ([0; 3], [0; 3])
+        } else {
+            (min, max)
+        }
+    }
+}
+
+impl LitematicRegion {
+    fn volume(&self) -> usize {
+        self.template
+            .size()
+            .iter()
+            .map(|size| *size as usize)
+            .product()
+    }
+}
+
+impl Nbt for Litematic {
+    fn nbt(&self) -> Value {
+        let (min, max) = self.bounds();
+        let regions: Map<String, Value> = self
+            .regions
+            .iter()
+            .map(|region| (region.name.clone(), region.nbt()))
+            .collect();
+        let total_blocks: usize = self
+            .regions
+            .iter()
+            .map(|region| {
+                let palette = region.template.palette();
+                region
+                    .template
+                    .blocks()
+                    .iter()
+                    .filter(|block| !is_air(&palette[block.state]))
+                    .count()
+            })
+            .sum();
+        nbt! {
+            Version: (self.version),
+            SubVersion?: (self.sub_version),
+            MinecraftDataVersion: (self.data_version),
+            Metadata: {
+                Name: (self.name),
+                Author: (self.author),
+                Description: (self.description),
+                RegionCount: (self.regions.len() as i32),
+                TotalVolume: (self.regions.iter().map(LitematicRegion::volume).sum::<usize>() as i32),
+                TotalBlocks: (total_blocks as i32),
+                EnclosingSize: (xyz([max[0] - min[0], max[1] - min[1], max[2] - min[2]])),
+                ..self.metadata
+            },
+            Regions: regions,
+        }
+    }
+}
+
+impl FromNbt for Litematic {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let metadata: Map<String, Value> = optional_field(cmp, "Metadata")?.unwrap_or_default();
+        let text = |key| {
+            optional_field(&metadata, key)
+                .map(Option::unwrap_or_default)
+                .map_err(|err| err.within(PathSegment::Field("Metadata".to_owned())))
+        };
+        let data_version = field(cmp, "MinecraftDataVersion")?;
+        let regions: Map<String, Value> = field(cmp, "Regions")?;
+        let regions = regions
+            .into_iter()
+            .map(|(name, region)| {
+                LitematicRegion::read(name.clone(), &region, data_version).map_err(|err| {
+                    err.within(PathSegment::Field(name))
+                        .within(PathSegment::Field("Regions".to_owned()))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            version: field(cmp, "Version")?,
+            sub_version: optional_field(cmp, "SubVersion")?,
+            data_version,
+            name: text("Name")?,
+            author: text("Author")?,
+            description: text("Description")?,
+            metadata: unknown_fields(&metadata, &METADATA_KEYS),
+            regions,
+        })
+    }
+}
+
+impl LitematicRegion {
+    fn read(name: String, value: &Value, data_version: i32) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let position = field::<Xyz>(cmp, "Position")?.0;
+        let size = field::<Xyz>(cmp, "Size")?.0;
+        let out_of_range = || {
+            FromNbtError::from(FromNbtErrorKind::Custom(format!(
+                "Region of size {size:?} at {position:?} is out of range"
+            )))
+            .within(PathSegment::Field("Size".to_owned()))
+        };
+        // A negative size extends the region from its position towards negative coordinates
+        let mut corner = position;
+        let mut extent = [0; 3];
+        for axis in 0..3 {
+            let len = size[axis];
+            extent[axis] = len.checked_abs().ok_or_else(out_of_range)?;
+            if len < 0 {
+                corner[axis] = corner[axis].checked_add(len + 1).ok_or_else(out_of_range)?;
+            }
+            corner[axis]
+                .checked_add(extent[axis])
+                .ok_or_else(out_of_range)?;
+        }
+        let volume = extent
+            .iter()
+            .try_fold(1usize, |volume, len| volume.checked_mul(*len as usize))
+            .ok_or_else(out_of_range)?;
+        let mut region = Self {
+            name,
+            position: corner.into(),
+            template: StructureTemplate::new(data_version),
+            extra: unknown_fields(
+                cmp,
+                &[
+                    "Position",
+                    "Size",
+                    "BlockStatePalette",
+                    "BlockStates",
+                    "TileEntities",
+                    "Entities",
+                ],
+            ),
+        };
+        region.template.set_size(extent);
+        let template = &mut region.template;
+
+        let palette: Vec<BlockState> = field(cmp, "BlockStatePalette")?;
+        let states: Vec<i64> = match cmp.get("BlockStates") {
+            Some(Value::LongArray(states)) => states.clone(),
+            Some(other) => {
+                return Err(FromNbtError::mismatch("TAG_Long_Array", other)
+                    .within(PathSegment::Field("BlockStates".to_owned())))
+            }
+            None => return Err(FromNbtErrorKind::MissingField("BlockStates").into()),
+        };
+        let bits = bits(palette.len());
+        if volume > states.len() * 64 / bits {
+            return Err(FromNbtError::from(FromNbtErrorKind::Custom(format!(
+                "Expected {} longs, found {}",
+                (volume as u128 * bits as u128).div_ceil(64),
+                states.len()
+            )))
+            .within(PathSegment::Field("BlockStates".to_owned())));
+        }
+
+        let mut block_entities = std::collections::HashMap::new();
+        let entries: Vec<Map<String, Value>> =
+            optional_field(cmp, "TileEntities")?.unwrap_or_default();
+        for (index, nbt) in entries.into_iter().enumerate() {
+            let pos = Xyz::read(&nbt).map_err(|err| {
+                err.within(PathSegment::Index(index))
+                    .within(PathSegment::Field("TileEntities".to_owned()))
+            })?;
+            let nbt = unknown_fields(&nbt, &["x", "y", "z"]);
+            block_entities.insert(Position::from(pos.0), nbt);
+        }
+
+        for index in 0..volume {
+            let state = &palette
+                .get(get_packed(&states, bits, index))
+                .ok_or_else(|| {
+                    FromNbtError::from(FromNbtErrorKind::Custom(format!(
+                        "Palette index is out of bounds for {} states",
+                        palette.len()
+                    )))
+                    .within(PathSegment::Field("BlockStates".to_owned()))
+                })?;
+            if is_air(state) {
+                continue;
+            }
+            let [width, _, length] = extent.map(|len| len as usize);
+            let pos = Position::new(
+                (index % width) as i32,
+                (index / (width * length)) as i32,
+                (index / width % length) as i32,
+            );
+            match block_entities.remove(&pos) {
+                Some(nbt) => {
+                    template.try_set_block_entity(pos, (*state).clone(), Value::Compound(nbt))
+                }
+                None => template.try_set_block(pos, (*state).clone()),
+            }
+            .map_err(|err| {
+                FromNbtError::from(err).within(PathSegment::Field("BlockStates".to_owned()))
+            })?;
+        }
+
+        let entities: Vec<Map<String, Value>> =
+            optional_field(cmp, "Entities")?.unwrap_or_default();
+        for (index, nbt) in entities.into_iter().enumerate() {
+            let path = |err: FromNbtError| {
+                err.within(PathSegment::Index(index))
+                    .within(PathSegment::Field("Entities".to_owned()))
+            };
+            let pos = field(&nbt, "Pos").map_err(path)?;
+            template
+                .try_add_entity(pos, Value::Compound(unknown_fields(&nbt, &["Pos"])))
+                .map_err(|err| {
+                    path(FromNbtError::from(err).within(PathSegment::Field("Pos".to_owned())))
+                })?;
+        }
+        Ok(region)
+    }
+}
+
+impl Nbt for LitematicRegion {
+    fn nbt(&self) -> Value {
+        let template = &self.template;
+        let mut palette = vec![BlockState::from(Material::Air)];
+        let indices: Vec<usize> = template
+            .palette()
+            .iter()
+            .map(
+                |state| match palette.iter().position(|other| other == state) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(state.clone());
+                        palette.len() - 1
+                    }
+                },
+            )
+            .collect();
+        let bits = bits(palette.len());
+        let [width, _, length] = template.size();
+        let mut states = vec![0; (self.volume() * bits).div_ceil(64)];
+        let mut tile_entities = Vec::new();
+        for block in template.blocks() {
+            let (x, y, z) = (block.pos.x(), block.pos.y(), block.pos.z());
+            let index = ((y * length + z) * width + x) as usize;
+            set_packed(&mut states, bits, index, indices[block.state]);
+            if let Some(nbt) = &block.nbt {
+                tile_entities.push(nbt! { x: x, y: y, z: z, ..*nbt });
+            }
+        }
+        let entities: Vec<Value> = template
+            .entities()
+            .iter()
+            .map(|entity| nbt! { ..entity.nbt, Pos: (entity.pos) })
+            .collect();
+        nbt! {
+            Position: (xyz(self.position.into())),
+            Size: (xyz(template.size())),
+            BlockStatePalette: palette,
+            BlockStates: (Value::LongArray(states)),
+            TileEntities: tile_entities,
+            Entities: entities,
+            ..self.extra
+        }
+    }
+}
+
+/// Vectors are stored as compounds of `x`, `y` and `z`
+struct Xyz([i32; 3]);
+
+impl Xyz {
+    fn read(cmp: &Map<String, Value>) -> Result<Self, FromNbtError> {
+        Ok(Self([field(cmp, "x")?, field(cmp, "y")?, field(cmp, "z")?]))
+    }
+}
+
+impl FromNbt for Xyz {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        Self::read(compound(value)?)
+    }
+}
+
+fn xyz([x, y, z]: [i32; 3]) -> Value {
+    nbt! { x: x, y: y, z: z }
+}
+
+fn is_air(state: &BlockState) -> bool {
+    state.name == Identifier::minecraft("air")
+}
+
+/// Bits per entry of the packed block states, at least 2
+fn bits(palette_len: usize) -> usize {
+    (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(2) as usize
+}
+
+/// Reads an entry of tightly packed states, which may span two longs
+fn get_packed(states: &[i64], bits: usize, index: usize) -> usize {
+    let start = index * bits;
+    let (long, offset) = (start / 64, start % 64);
+    let mut value = states[long] as u64 >> offset;
+    if offset + bits > 64 {
+        value |= (states[long + 1] as u64) << (64 - offset);
+    }
+    (value & ((1 << bits) - 1)) as usize
+}
+
+fn set_packed(states: &mut [i64], bits: usize, index: usize, value: usize) {
+    let start = index * bits;
+    let (long, offset) = (start / 64, start % 64);
+    states[long] |= ((value as u64) << offset) as i64;
+    if offset + bits > 64 {
+        states[long + 1] |= (value as u64 >> (64 - offset)) as i64;
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    nbt,
+    ser::PathSegment,
+    stream::{self, Compression, NbtOptions, StreamError},
+    FromNbt, Map, Nbt, Value,
+};
+
+use crate::{
+    err::NbtFileError,
+    item::Material,
+    loc::Position,
+    structure::{BlockState, StructureTemplate},
+    util::{compound, field, optional_field, unknown_fields},
+};
+
+/// A Sponge schematic, version 2 or 3.
+///
+/// Every position of a schematic holds a block, so positions without a block in the template
+/// are written as air. Biomes are not kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpongeSchematic {
+    /// Format version the schematic is written with, `2` or `3`
+    pub version: i32,
+    /// Offset of the blocks relative to the origin of the schematic when it is pasted
+    pub offset: [i32; 3],
+    /// Tool specific entries, like WorldEdit's origin
+    pub metadata: Option<Map<String, Value>>,
+    /// Blocks and entities, with positions relative to the minimum corner
+    pub template: StructureTemplate,
+}
+
+impl SpongeSchematic {
+    /// A version 3 schematic of a template
+    pub fn new(template: StructureTemplate) -> Self {
+        Self {
+            version: 3,
+            offset: [0; 3],
+            metadata: None,
+            template,
+        }
+    }
+
+    /// Reads a gzipped schematic
+    pub fn read<R: Read>(reader: R) -> Result<Self, NbtFileError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        let (_, value) = stream::read_value(reader, &options)?;
+        Ok(Self::from_nbt(&value)?)
+    }
+
+    /// Writes the schematic gzipped, version 2 as the `Schematic` root compound and version 3
+    /// nested in it
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), StreamError> {
+        let root_name = if self.version >= 3 { "" } else { "Schematic" };
+        let options = NbtOptions::new()
+            .with_compression(Compression::Gzip)
+            .with_root_name(root_name);
+        stream::write_value(writer, &self.nbt(), &options)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NbtFileError> {
+        let file = File::open(path).map_err(StreamError::from)?;
+        Self::read(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StreamError> {
+        self.write(File::create(path)?)
+    }
+}
+
+impl From<StructureTemplate> for SpongeSchematic {
+    fn from(template: StructureTemplate) -> Self {
+        Self::new(template)
+    }
+}
+
+impl From<SpongeSchematic> for StructureTemplate {
+    fn from(schematic: SpongeSchematic) -> Self {
+        schematic.template
+    }
+}
+
+/// Writes the compound of the schematic, version 3 nested in a `Schematic` compound
+impl Nbt for SpongeSchematic {
+    fn nbt(&self) -> Value {
+        let template = &self.template;
+        let [width, height, length] = template.size();
+        let names: Vec<String> = template.palette().iter().map(ToString::to_string).collect();
+        let air = BlockState::from(Material::Air).to_string();
+        let mut palette: Map<String, i32> = Map::new();
+        let mut data = Vec::new();
+        for y in 0..height {
+            for z in 0..length {
+                for x in 0..width {
+                    let name = match template.block(Position::new(x, y, z)) {
+                        Some(block) => &names[block.state],
+                        None => &air,
+                    };
+                    let next = palette.len() as i32;
+                    let id = *palette.entry(name.clone()).or_insert(next);
+                    write_varint(&mut data, id as u32);
+                }
+            }
+        }
+
+        let block_entities: Vec<Value> = template
+            .blocks()
+            .iter()
+            .filter_map(|block| {
+                let nbt = block.nbt.as_ref()?;
+                let id = match nbt.get("id") {
+                    Some(id) => id.clone(),
+                    None => template.palette()[block.state].name.nbt(),
+                };
+                let pos = Value::IntArray(<[i32; 3]>::from(block.pos).to_vec());
+                Some(self.entry(pos, Some(id), nbt))
+            })
+            .collect();
+        let entities: Vec<Value> = template
+            .entities()
+            .iter()
+            .map(|entity| self.entry(entity.pos.nbt(), entity.nbt.get("id").cloned(), &entity.nbt))
+            .collect();
+
+        let schematic = nbt! {
+            Version: (self.version),
+            DataVersion: (template.data_version),
+            Metadata?: (self.metadata),
+            Width: (width as u16 as i16),
+            Height: (height as u16 as i16),
+            Length: (length as u16 as i16),
+            Offset: (Value::IntArray(self.offset.to_vec())),
+        };
+        let entities = Some(entities).filter(|entities| !entities.is_empty());
+        if self.version >= 3 {
+            nbt! {
+                Schematic: {
+                    ..schematic,
+                    Blocks: {
+                        Palette: palette,
+                        Data: (Value::ByteArray(data)),
+                        BlockEntities: block_entities,
+                    },
+                    Entities?: entities,
+                },
+            }
+        } else {
+            nbt! {
+                ..schematic,
+                PaletteMax: (palette.len() as i32),
+                Palette: palette,
+                BlockData: (Value::ByteArray(data)),
+                BlockEntities: block_entities,
+                Entities?: entities,
+            }
+        }
+    }
+}
+
+impl SpongeSchematic {
+    /// A block entity or entity, its data nested in `Data` since version 3
+    fn entry(&self, pos: Value, id: Option<Value>, nbt: &Map<String, Value>) -> Value {
+        let data = unknown_fields(nbt, &["id", "Pos"]);
+        if self.version >= 3 {
+            nbt! { Pos: pos, Id?: id, Data: data }
+        } else {
+            nbt! { Pos: pos, Id?: id, ..data }
+        }
+    }
+}
+
+impl FromNbt for SpongeSchematic {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        if let Some(schematic) = cmp.get("Schematic") {
+            return Self::from_nbt(schematic)
+                .map_err(|err| err.within(PathSegment::Field("Schematic".to_owned())));
+        }
+        let version: i32 = field(cmp, "Version")?;
+        let (blocks, data_key) = match version {
+            2 => (cmp, "BlockData"),
+            3 => match cmp.get("Blocks") {
+                Some(blocks) => (
+                    compound(blocks).map_err(|err| within(err, &["Blocks"]))?,
+                    "Data",
+                ),
+                None => (&Map::new(), "Data"),
+            },
+            _ => {
+                return Err(custom(format!("Unsupported schematic version {version}"))
+                    .within(PathSegment::Field("Version".to_owned())))
+            }
+        };
+        let prefix: &[&str] = if version >= 3 { &["Blocks"] } else { &[] };
+        let size = [
+            field::<i16>(cmp, "Width")? as u16 as i32,
+            field::<i16>(cmp, "Height")? as u16 as i32,
+            field::<i16>(cmp, "Length")? as u16 as i32,
+        ];
+        let mut template = StructureTemplate::new(field(cmp, "DataVersion")?);
+        template.set_size(size);
+
+        let palette: Map<String, i32> = optional_field(blocks, "Palette")
+            .map_err(|err| within(err, prefix))?
+            .unwrap_or_default();
+        let mut states = HashMap::with_capacity(palette.len());
+        for (name, id) in palette {
+            let path = || [prefix, &["Palette", name.as_str()]].concat();
+            let state: BlockState = name
+                .parse()
+                .map_err(|_| within(custom(format!("Invalid block state `{name}`")), &path()))?;
+            let id = u32::try_from(id)
+                .map_err(|_| within(custom(format!("Invalid block id {id}")), &path()))?;
+            states.insert(id, state);
+        }
+
+        let mut block_entities = HashMap::new();
+        let entries: Vec<Value> = optional_field(blocks, "BlockEntities")
+            .map_err(|err| within(err, prefix))?
+            .unwrap_or_default();
+        for (index, entry) in entries.iter().enumerate() {
+            let path = |err: FromNbtError| {
+                within(
+                    err.within(PathSegment::Index(index)),
+                    &[prefix, &["BlockEntities"]].concat(),
+                )
+            };
+            let (pos, nbt) = entry_data(entry, version).map_err(path)?;
+            let [x, y, z]: [i32; 3] = FromNbt::from_nbt(&pos)
+                .map_err(|err| path(err.within(PathSegment::Field("Pos".to_owned()))))?;
+            block_entities.insert(Position::new(x, y, z), Value::Compound(nbt));
+        }
+
+        if let Some(data) = blocks.get(data_key) {
+            let path = || [prefix, &[data_key]].concat();
+            let Value::ByteArray(data) = data else {
+                return Err(within(
+                    FromNbtError::mismatch("TAG_Byte_Array", data),
+                    &path(),
+                ));
+            };
+            // sizes are at most 65535, so the volume always fits
+            let volume = size.iter().map(|v| *v as u64).product::<u64>();
+            if volume > data.len() as u64 {
+                return Err(within(
+                    custom(format!(
+                        "Expected at least {volume} bytes of block data, found {}",
+                        data.len()
+                    )),
+                    &path(),
+                ));
+            }
+            let mut bytes = data.iter().map(|byte| *byte as u8);
+            for index in 0..volume as i64 {
+                let id = read_varint(&mut bytes)
+                    .ok_or_else(|| within(custom("Block data ended early".to_owned()), &path()))?;
+                let state = states.get(&id).cloned().ok_or_else(|| {
+                    within(
+                        custom(format!("Block id {id} is not in the palette")),
+                        &path(),
+                    )
+                })?;
+                let [width, _, length] = size.map(i64::from);
+                let pos = Position::new(
+                    (index % width) as i32,
+                    (index / (width * length)) as i32,
+                    (index / width % length) as i32,
+                );
+                match block_entities.remove(&pos) {
+                    Some(nbt) => template.try_set_block_entity(pos, state, nbt),
+                    None => template.try_set_block(pos, state),
+                }
+                .map_err(|err| within(err.into(), &path()))?;
+            }
+        }
+
+        let entities: Vec<Value> = optional_field(cmp, "Entities")?.unwrap_or_default();
+        for (index, entry) in entities.iter().enumerate() {
+            let path = |err: FromNbtError| {
+                err.within(PathSegment::Index(index))
+                    .within(PathSegment::Field("Entities".to_owned()))
+            };
+            let (pos, nbt) = entry_data(entry, version).map_err(path)?;
+            let pos: [f64; 3] = FromNbt::from_nbt(&pos)
+                .map_err(|err| path(err.within(PathSegment::Field("Pos".to_owned()))))?;
+            template
+                .try_add_entity(pos, Value::Compound(nbt))
+                .map_err(|err| {
+                    path(FromNbtError::from(err).within(PathSegment::Field("Pos".to_owned())))
+                })?;
+        }
+
+        Ok(Self {
+            version,
+            offset: optional_field(cmp, "Offset")?.unwrap_or_default(),
+            metadata: optional_field(cmp, "Metadata")?,
+            template,
+        })
+    }
+}
+
+/// Splits a block entity or entity into its position and its data, including the `id`
+fn entry_data(entry: &Value, version: i32) -> Result<(Value, Map<String, Value>), FromNbtError> {
+    let cmp = compound(entry)?;
+    let pos = field(cmp, "Pos")?;
+    let mut nbt = match version {
+        2 => unknown_fields(cmp, &["Pos", "Id"]),
+        _ => optional_field(cmp, "Data")?.unwrap_or_default(),
+    };
+    if let Some(id) = optional_field::<Value>(cmp, "Id")? {
+        nbt.insert("id".to_owned(), id);
+    }
+    Ok((pos, nbt))
+}
+
+fn write_varint(out: &mut Vec<i8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push((value as u8 | 0x80) as i8);
+        value >>= 7;
+    }
+    out.push(value as i8);
+}
+
+fn read_varint<I: Iterator<Item = u8>>(bytes: &mut I) -> Option<u32> {
+    let mut value = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = bytes.next()?;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn within(err: FromNbtError, path: &[&str]) -> FromNbtError {
+    path.iter().rev().fold(err, |err, key| {
+        err.within(PathSegment::Field((*key).to_owned()))
+    })
+}
+
+fn custom(message: String) -> FromNbtError {
+    FromNbtErrorKind::Custom(message).into()
+}
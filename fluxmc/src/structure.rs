@@ -0,0 +1,571 @@
+//! Structure templates, the gzipped `.nbt` files saved by structure blocks.
+//!
+//! Templates are built by placing blocks at positions relative to their origin, the palette
+//! and the size grow along with them:
+//!
+//! ```
+//! # use fluxmc::{item::Material, loc::Position, structure::{BlockState, StructureTemplate}};
+//! # use fluxmc::nbt::nbt;
+//! let mut arena = StructureTemplate::new(3465);
+//! arena.fill(Position::new(0, 0, 0), Position::new(15, 0, 15), Material::Stone);
+//! arena.set_block(
+//!     Position::new(7, 1, 7),
+//!     BlockState::from(Material::OakStairs).with_property("facing", "north"),
+//! );
+//! arena.set_block_entity(
+//!     Position::new(8, 1, 8),
+//!     Material::Chest,
+//!     nbt! { Items: [{ Slot: 0B, id: "minecraft:bow", Count: 1b }] },
+//! );
+//! assert_eq!(arena.size(), [16, 2, 16]);
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    nbt,
+    ser::PathSegment,
+    snbt,
+    stream::{self, Compression, NbtOptions, StreamError},
+    FromNbt, Map, Nbt, Value,
+};
+
+use crate::{
+    err::{Error, NbtFileError, StructureError},
+    id::Identifier,
+    item::Material,
+    loc::Position,
+    util::{compound, field, optional_field, unknown_fields},
+};
+
+/// A structure template, with positions relative to its origin corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureTemplate {
+    /// `DataVersion` of the game the template was saved with
+    pub data_version: i32,
+    size: [i32; 3],
+    palette: Vec<BlockState>,
+    blocks: Vec<StructureBlock>,
+    /// Index into `blocks` by position
+    positions: HashMap<Position, usize>,
+    entities: Vec<StructureEntity>,
+}
+
+/// A block with its properties, e.g. `minecraft:oak_stairs[facing=north]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockState {
+    pub name: Identifier,
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureBlock {
+    pub pos: Position,
+    /// Index of the block state in the palette
+    pub state: usize,
+    /// Block entity data, without the position
+    pub nbt: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureEntity {
+    pub pos: [f64; 3],
+    /// The block the entity is in
+    pub block_pos: Position,
+    /// Entity data, including its `id`
+    pub nbt: Map<String, Value>,
+}
+
+impl StructureTemplate {
+    pub fn new(data_version: i32) -> Self {
+        Self {
+            data_version,
+            size: [0; 3],
+            palette: Vec::new(),
+            blocks: Vec::new(),
+            positions: HashMap::new(),
+            entities: Vec::new(),
+        }
+    }
+
+    /// Reads a gzipped template
+    pub fn read<R: Read>(reader: R) -> Result<Self, NbtFileError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        let (_, value) = stream::read_value(reader, &options)?;
+        Ok(Self::from_nbt(&value)?)
+    }
+
+    /// Writes the template gzipped, the way structure blocks save it
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), StreamError> {
+        let options = NbtOptions::new().with_compression(Compression::Gzip);
+        stream::write_value(writer, &self.nbt(), &options)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NbtFileError> {
+        let file = File::open(path).map_err(StreamError::from)?;
+        Self::read(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StreamError> {
+        self.write(File::create(path)?)
+    }
+
+    /// Size along the x, y and z axis
+    pub fn size(&self) -> [i32; 3] {
+        self.size
+    }
+
+    /// Sets the size, which otherwise grows to contain every placed block and entity
+    pub fn set_size(&mut self, size: [i32; 3]) {
+        self.size = size
+    }
+
+    pub fn palette(&self) -> &[BlockState] {
+        &self.palette
+    }
+
+    pub fn blocks(&self) -> &[StructureBlock] {
+        &self.blocks
+    }
+
+    pub fn entities(&self) -> &[StructureEntity] {
+        &self.entities
+    }
+
+    pub fn block(&self, pos: Position) -> Option<&StructureBlock> {
+        self.positions.get(&pos).map(|index| &self.blocks[*index])
+    }
+
+    pub fn block_state(&self, pos: Position) -> Option<&BlockState> {
+        self.block(pos).map(|block| &self.palette[block.state])
+    }
+
+    /// Places a block, replacing the block and block entity at the position
+    ///
+    /// # Panics
+    /// If any coordinate of the position is negative or `i32::MAX`, see [`try_set_block`](Self::try_set_block)
+    #[track_caller]
+    pub fn set_block<S: Into<BlockState>>(&mut self, pos: Position, state: S) {
+        if let Err(err) = self.try_set_block(pos, state) {
+            panic!("{err}")
+        }
+    }
+
+    /// Places a block, failing if the position is out of range instead of panicking
+    pub fn try_set_block<S: Into<BlockState>>(
+        &mut self,
+        pos: Position,
+        state: S,
+    ) -> Result<(), StructureError> {
+        self.place(pos, state.into(), None)
+    }
+
+    /// Places a block along with its block entity data
+    ///
+    /// # Panics
+    /// If any coordinate of the position is negative or `i32::MAX`, or the data is not a compound,
+    /// see [`try_set_block_entity`](Self::try_set_block_entity)
+    #[track_caller]
+    pub fn set_block_entity<S: Into<BlockState>>(&mut self, pos: Position, state: S, nbt: Value) {
+        if let Err(err) = self.try_set_block_entity(pos, state, nbt) {
+            panic!("{err}")
+        }
+    }
+
+    /// Places a block along with its block entity data, failing instead of panicking
+    pub fn try_set_block_entity<S: Into<BlockState>>(
+        &mut self,
+        pos: Position,
+        state: S,
+        nbt: Value,
+    ) -> Result<(), StructureError> {
+        let Value::Compound(nbt) = nbt else {
+            return Err(StructureError::NotACompound(
+                "Block entity",
+                nbt.tag_name().to_owned(),
+            ));
+        };
+        self.place(pos, state.into(), Some(nbt))
+    }
+
+    /// Places a block at every position of the box between two corners
+    ///
+    /// # Panics
+    /// If any coordinate of the corners is negative or `i32::MAX`
+    #[track_caller]
+    pub fn fill<S: Into<BlockState>>(&mut self, from: Position, to: Position, state: S) {
+        let state = state.into();
+        for x in from.x().min(to.x())..=from.x().max(to.x()) {
+            for y in from.y().min(to.y())..=from.y().max(to.y()) {
+                for z in from.z().min(to.z())..=from.z().max(to.z()) {
+                    self.set_block(Position::new(x, y, z), state.clone());
+                }
+            }
+        }
+    }
+
+    /// Chaining version of [`set_block`](Self::set_block)
+    ///
+    /// # Panics
+    /// If any coordinate of the position is negative or `i32::MAX`
+    #[track_caller]
+    pub fn with_block<S: Into<BlockState>>(mut self, pos: Position, state: S) -> Self {
+        self.set_block(pos, state);
+        self
+    }
+
+    /// Removes the block at a position, leaving the position untouched when the template is placed
+    pub fn remove_block(&mut self, pos: Position) -> Option<StructureBlock> {
+        let index = self.positions.remove(&pos)?;
+        let removed = self.blocks.swap_remove(index);
+        if let Some(moved) = self.blocks.get(index) {
+            self.positions.insert(moved.pos, index);
+        }
+        Some(removed)
+    }
+
+    /// Adds an entity, its data has to contain the entity `id`
+    ///
+    /// # Panics
+    /// If any coordinate of the position is negative, not finite or out of range, or the data
+    /// is not a compound, see [`try_add_entity`](Self::try_add_entity)
+    #[track_caller]
+    pub fn add_entity(&mut self, pos: [f64; 3], nbt: Value) {
+        if let Err(err) = self.try_add_entity(pos, nbt) {
+            panic!("{err}")
+        }
+    }
+
+    /// Adds an entity, failing instead of panicking
+    pub fn try_add_entity(&mut self, pos: [f64; 3], nbt: Value) -> Result<(), StructureError> {
+        let Value::Compound(nbt) = nbt else {
+            return Err(StructureError::NotACompound(
+                "Entity",
+                nbt.tag_name().to_owned(),
+            ));
+        };
+        if !pos.iter().all(|v| v.is_finite()) {
+            return Err(StructureError::NonFiniteEntityPosition(pos));
+        }
+        let block_pos = Position::new(
+            pos[0].floor() as i32,
+            pos[1].floor() as i32,
+            pos[2].floor() as i32,
+        );
+        self.grow(block_pos)?;
+        self.entities.push(StructureEntity {
+            pos,
+            block_pos,
+            nbt,
+        });
+        Ok(())
+    }
+
+    /// Commands of a function file placing the template at the position it runs at.
+    ///
+    /// Rows of the same block along the x axis are placed with a single `fill`, block entities
+    /// with `setblock` and entities are summoned without their UUID.
+    pub fn commands(&self) -> Vec<String> {
+        let mut blocks: Vec<&StructureBlock> = self.blocks.iter().collect();
+        blocks.sort_by_key(|block| (block.pos.y(), block.pos.z(), block.pos.x()));
+        let mut commands = Vec::new();
+        let mut index = 0;
+        while index < blocks.len() {
+            let start = blocks[index];
+            let state = &self.palette[start.state];
+            let (x, y, z) = (start.pos.x(), start.pos.y(), start.pos.z());
+            if let Some(nbt) = &start.nbt {
+                let nbt = without(nbt, &["id", "x", "y", "z"]);
+                commands.push(format!("setblock ~{x} ~{y} ~{z} {state}{nbt}"));
+                index += 1;
+                continue;
+            }
+            let mut end = index + 1;
+            while end < blocks.len()
+                && end - index < MAX_FILL
+                && blocks[end].nbt.is_none()
+                && blocks[end].state == start.state
+                && blocks[end].pos == Position::new(x + (end - index) as i32, y, z)
+            {
+                end += 1;
+            }
+            commands.push(match end - index {
+                1 => format!("setblock ~{x} ~{y} ~{z} {state}"),
+                len => format!(
+                    "fill ~{x} ~{y} ~{z} ~{} ~{y} ~{z} {state}",
+                    x + len as i32 - 1
+                ),
+            });
+            index = end;
+        }
+        for entity in &self.entities {
+            let Some(Value::String(id)) = entity.nbt.get("id") else {
+                continue;
+            };
+            let [x, y, z] = entity.pos;
+            let nbt = without(&entity.nbt, &["id", "Pos", "UUID"]);
+            let command = format!("summon {id} ~{x} ~{y} ~{z} {nbt}");
+            commands.push(command.trim_end().to_owned());
+        }
+        commands
+    }
+
+    fn place(
+        &mut self,
+        pos: Position,
+        state: BlockState,
+        nbt: Option<Map<String, Value>>,
+    ) -> Result<(), StructureError> {
+        self.grow(pos)?;
+        let state = match self.palette.iter().position(|existing| *existing == state) {
+            Some(index) => index,
+            None => {
+                self.palette.push(state);
+                self.palette.len() - 1
+            }
+        };
+        let block = StructureBlock { pos, state, nbt };
+        match self.positions.get(&pos) {
+            Some(index) => self.blocks[*index] = block,
+            None => {
+                self.positions.insert(pos, self.blocks.len());
+                self.blocks.push(block);
+            }
+        }
+        Ok(())
+    }
+
+    /// Grows the size to contain a position, which is checked before anything is placed
+    fn grow(&mut self, pos: Position) -> Result<(), StructureError> {
+        let pos: [i32; 3] = pos.into();
+        let mut size = self.size;
+        for (size, v) in size.iter_mut().zip(pos) {
+            let end = v
+                .checked_add(1)
+                .filter(|_| v >= 0)
+                .ok_or(StructureError::PositionOutOfRange(pos))?;
+            *size = (*size).max(end);
+        }
+        self.size = size;
+        Ok(())
+    }
+}
+
+impl BlockState {
+    pub fn new(name: Identifier) -> Self {
+        Self {
+            name,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_property<K: Into<String>, V: ToString>(mut self, key: K, value: V) -> Self {
+        self.properties.insert(key.into(), value.to_string());
+        self
+    }
+}
+
+/// The form used by commands, e.g. `minecraft:oak_stairs[facing=north,half=bottom]`
+impl Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.properties.is_empty() {
+            let properties: Vec<String> = self
+                .properties
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, properties)) = s.split_once('[') else {
+            return Ok(Self::new(s.parse()?));
+        };
+        let properties = properties.strip_suffix(']').ok_or(Error::ParsingError)?;
+        let mut state = Self::new(name.parse()?);
+        for property in properties
+            .split(',')
+            .filter(|property| !property.is_empty())
+        {
+            let (key, value) = property.split_once('=').ok_or(Error::ParsingError)?;
+            state = state.with_property(key.trim(), value.trim());
+        }
+        Ok(state)
+    }
+}
+
+impl From<Identifier> for BlockState {
+    fn from(name: Identifier) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<Material> for BlockState {
+    fn from(material: Material) -> Self {
+        Self::new(material.into())
+    }
+}
+
+/// Most blocks a single `fill` command can place
+const MAX_FILL: usize = 32768;
+
+/// SNBT of a compound without some keys, empty if nothing is left
+fn without(nbt: &Map<String, Value>, keys: &[&str]) -> String {
+    let nbt = unknown_fields(nbt, keys);
+    if nbt.is_empty() {
+        String::new()
+    } else {
+        snbt::to_string(&Value::Compound(nbt))
+    }
+}
+
+impl Nbt for StructureTemplate {
+    fn nbt(&self) -> Value {
+        nbt! {
+            DataVersion: (self.data_version),
+            size: (self.size),
+            palette: (self.palette),
+            blocks: (self.blocks),
+            entities: (self.entities),
+        }
+    }
+}
+
+/// Templates with several palettes, like shipwrecks, are read with their first palette
+impl FromNbt for StructureTemplate {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        let palette = match cmp.get("palettes") {
+            Some(_) if !cmp.contains_key("palette") => {
+                let palettes: Vec<Vec<BlockState>> = field(cmp, "palettes")?;
+                palettes.into_iter().next().unwrap_or_default()
+            }
+            _ => field(cmp, "palette")?,
+        };
+        let size: [i32; 3] = field(cmp, "size")?;
+        if size.iter().any(|v| *v < 0) {
+            return Err(FromNbtError::from(StructureError::NegativeSize(size))
+                .within(PathSegment::Field("size".to_owned())));
+        }
+        // errors of an entry of the `blocks` or `entities` list
+        let at = |err: FromNbtError, list: &str, index: usize, key: &str| {
+            err.within(PathSegment::Field(key.to_owned()))
+                .within(PathSegment::Index(index))
+                .within(PathSegment::Field(list.to_owned()))
+        };
+        let inside = |pos: Position| -> Result<(), FromNbtError> {
+            let pos: [i32; 3] = pos.into();
+            match pos.iter().zip(size).all(|(v, size)| (0..size).contains(v)) {
+                true => Ok(()),
+                false => Err(StructureError::OutsideOfSize { pos, size }.into()),
+            }
+        };
+
+        let blocks: Vec<StructureBlock> = field(cmp, "blocks")?;
+        let mut positions = HashMap::with_capacity(blocks.len());
+        for (index, block) in blocks.iter().enumerate() {
+            if block.state >= palette.len() {
+                let err = FromNbtErrorKind::Custom(format!(
+                    "Block state {} is not in the palette of {} states",
+                    block.state,
+                    palette.len()
+                ));
+                return Err(at(err.into(), "blocks", index, "state"));
+            }
+            inside(block.pos).map_err(|err| at(err, "blocks", index, "pos"))?;
+            positions.insert(block.pos, index);
+        }
+        let entities: Vec<StructureEntity> = optional_field(cmp, "entities")?.unwrap_or_default();
+        for (index, entity) in entities.iter().enumerate() {
+            if !entity.pos.iter().all(|v| v.is_finite()) {
+                let err = StructureError::NonFiniteEntityPosition(entity.pos).into();
+                return Err(at(err, "entities", index, "pos"));
+            }
+            inside(entity.block_pos).map_err(|err| at(err, "entities", index, "blockPos"))?;
+        }
+        Ok(Self {
+            data_version: field(cmp, "DataVersion")?,
+            size,
+            palette,
+            blocks,
+            positions,
+            entities,
+        })
+    }
+}
+
+impl Nbt for BlockState {
+    fn nbt(&self) -> Value {
+        nbt! {
+            Name: (self.name),
+            Properties?: (Some(&self.properties).filter(|properties| !properties.is_empty()).cloned()),
+        }
+    }
+}
+
+impl FromNbt for BlockState {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        Ok(Self {
+            name: field(cmp, "Name")?,
+            properties: optional_field(cmp, "Properties")?.unwrap_or_default(),
+        })
+    }
+}
+
+impl Nbt for StructureBlock {
+    fn nbt(&self) -> Value {
+        nbt! {
+            pos: (<[i32; 3]>::from(self.pos)),
+            state: (self.state as i32),
+            nbt?: (self.nbt),
+        }
+    }
+}
+
+impl FromNbt for StructureBlock {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        Ok(Self {
+            pos: field::<[i32; 3]>(cmp, "pos")?.into(),
+            state: field::<u32>(cmp, "state")? as usize,
+            nbt: optional_field(cmp, "nbt")?,
+        })
+    }
+}
+
+impl Nbt for StructureEntity {
+    fn nbt(&self) -> Value {
+        nbt! {
+            pos: (self.pos),
+            blockPos: (<[i32; 3]>::from(self.block_pos)),
+            nbt: (self.nbt),
+        }
+    }
+}
+
+impl FromNbt for StructureEntity {
+    fn from_nbt(value: &Value) -> Result<Self, FromNbtError> {
+        let cmp = compound(value)?;
+        Ok(Self {
+            pos: field(cmp, "pos")?,
+            block_pos: field::<[i32; 3]>(cmp, "blockPos")?.into(),
+            nbt: field(cmp, "nbt")?,
+        })
+    }
+}
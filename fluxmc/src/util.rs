@@ -1,3 +1,9 @@
+use flux_nbt::{
+    macros::{FromNbtError, FromNbtErrorKind},
+    ser::PathSegment,
+    FromNbt, Map, Value,
+};
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord)]
 #[repr(u8)]
 pub enum GeneralColor {
@@ -18,3 +24,36 @@ pub enum GeneralColor {
     Red,
     Black,
 }
+
+pub(crate) fn compound(value: &Value) -> Result<&Map<String, Value>, FromNbtError> {
+    match value {
+        Value::Compound(cmp) => Ok(cmp),
+        other => Err(FromNbtError::mismatch("TAG_Compound", other)),
+    }
+}
+
+pub(crate) fn field<T: FromNbt>(
+    cmp: &Map<String, Value>,
+    key: &'static str,
+) -> Result<T, FromNbtError> {
+    optional_field(cmp, key)?.ok_or_else(|| FromNbtErrorKind::MissingField(key).into())
+}
+
+pub(crate) fn optional_field<T: FromNbt>(
+    cmp: &Map<String, Value>,
+    key: &'static str,
+) -> Result<Option<T>, FromNbtError> {
+    cmp.get(key)
+        .map(|value| {
+            T::from_nbt(value).map_err(|err| err.within(PathSegment::Field(key.to_owned())))
+        })
+        .transpose()
+}
+
+/// Entries of a compound other than the known keys, kept to write them back unchanged
+pub(crate) fn unknown_fields(cmp: &Map<String, Value>, known: &[&str]) -> Map<String, Value> {
+    cmp.iter()
+        .filter(|(key, _)| !known.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}